@@ -1,53 +1,122 @@
 use std::error::Error;
-use std::fs::File;
-use std::io::Read;
+use std::path::Path;
 
-use yasl_core::Shader;
+use yasl_core::{
+    is_program, CompileOptions, Diagnostics, GlslFormat, Naming, OptLevel, ShaderStage,
+};
 
-use spirv_cross::{hlsl, spirv, ErrorCode};
+use spirv_cross::{hlsl, spirv};
 
-pub fn words_from_bytes(buf: &[u8]) -> &[u32] {
-    unsafe {
-        std::slice::from_raw_parts(
-            buf.as_ptr() as *const u32,
-            buf.len() / std::mem::size_of::<u32>(),
-        )
+/// `yasl-cli fmt [--check] paths..`, formats `.yasl` files in place
+/// With `--check` files are left alone, and the exit code is 1 if any of them isn't formatted
+fn fmt(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let check = args.iter().any(|a| a == "--check");
+    let mut formatted = true;
+    for path in args.iter().filter(|a| *a != "--check") {
+        let source = std::fs::read_to_string(path)?;
+        let output = match yasl_core::format_source(&source) {
+            Ok(output) => output,
+            Err(e) => {
+                let start = e.span().start();
+                eprintln!("{}:{}:{}: {}", path, start.line, start.column + 1, e);
+                formatted = false;
+                continue;
+            }
+        };
+        if output == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path);
+            formatted = false;
+        } else {
+            std::fs::write(path, output)?;
+        }
     }
+    Ok(formatted)
 }
 
+/// `yasl-cli [-O0|-O1|-O2] [--minify|--source-comments] [--prefix=name_] [path]`, the path defaults to `./test/main_vert.yasl`
+/// The stage comes from the file name, see `ShaderStage::from_path`, programs compile every stage block they have.
+/// Generated GLSL and its JSON sourcemap are written next to the source, as `.glsl` and `.glsl.map.json`,
+/// stages of a program get one of each, like `.vertex.glsl`
 fn run() -> Result<(), Box<dyn Error>> {
-    let mut file = File::open("./test/main_vert.yasl")?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
-
-    let shader = syn::parse_str::<Shader>(&content)?;
-
-    println!("{:#?}", shader.glsl);
-
-    let sprv = {
-        let mut compiler = shaderc::Compiler::new().unwrap();
-
-        compiler
-            .compile_into_spirv(
-                &shader.glsl,
-                shaderc::ShaderKind::Vertex,
-                "shader.glsl",
-                "main",
-                None,
-            )
-            .unwrap()
-            .as_binary_u8()
-            .to_vec()
-    };
-
-    let sprv = words_from_bytes(&sprv);
-    let module: spirv::Module = spirv::Module::from_words(sprv);
-    let mut ast = spirv::Ast::<hlsl::Target>::parse(&module).unwrap();
-    println!("{}", ast.compile().unwrap());
+    let mut options = CompileOptions::default();
+    let mut path = "./test/main_vert.yasl".to_string();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--minify" => options.format = GlslFormat::Minified,
+            "--source-comments" => {
+                options.format = GlslFormat::Pretty {
+                    indent: 4,
+                    source_comments: true,
+                }
+            }
+            _ if arg.starts_with("--prefix=") => {
+                options.naming = Naming::Prefixed(arg["--prefix=".len()..].to_string())
+            }
+            _ => match arg.strip_prefix('-') {
+                Some(level) => options.opt_level = level.parse::<OptLevel>()?,
+                None => path = arg,
+            },
+        }
+    }
+
+    let path = Path::new(&path);
+    let content = std::fs::read_to_string(path)?;
+    options.stage = Some(ShaderStage::from_path(path));
+    let program = content
+        .parse::<proc_macro2::TokenStream>()
+        .is_ok_and(|t| is_program(&t));
+    let shaders = yasl_core::parse(&content, &options).map_err(|d| d.with_path(path))?;
+
+    for shader in shaders.iter() {
+        println!("{:#?}", shader.glsl);
+
+        let glsl_path = if program {
+            path.with_extension(format!("{}.glsl", shader.stage.name()))
+        } else {
+            path.with_extension("glsl")
+        };
+        std::fs::write(&glsl_path, &shader.glsl)?;
+        std::fs::write(
+            glsl_path.with_extension("glsl.map.json"),
+            shader.sourcemap_json(&path.to_string_lossy()),
+        )?;
+
+        let sprv = shader
+            .spirv(&options)
+            .map_err(|e| Diagnostics::from(e).with_path(path))?;
+        let module: spirv::Module = spirv::Module::from_words(&sprv);
+        let mut ast = spirv::Ast::<hlsl::Target>::parse(&module).unwrap();
+        println!("{}", ast.compile().unwrap());
+    }
 
     Ok(())
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fmt") {
+        if !fmt(&args[1..]).unwrap() {
+            std::process::exit(1);
+        }
+        return;
+    }
     run().unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use yasl_core::{compile_file, CompileOptions};
+
+    #[test]
+    fn test_files_compile() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+        for name in ["main_vert.yasl", "vert.yasl"].iter() {
+            if let Err(d) = compile_file(dir.join(name), &CompileOptions::default()) {
+                panic!("{}", d);
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::glsl::Glsl;
+use crate::yasl_program::ShaderStage;
+use crate::yasl_type::{YaslScalarType, YaslType, YaslVecType};
+
+const UVEC3: YaslType = YaslType::Vec(YaslVecType::Vec3(YaslScalarType::UInt));
+const UINT: YaslType = YaslType::ScalarType(YaslScalarType::UInt);
+
+const COMPUTE: &[ShaderStage] = &[ShaderStage::Compute];
+const BARRIER: &[ShaderStage] = &[ShaderStage::Compute, ShaderStage::TessControl];
+
+/// Variable provided by the pipeline, for example `global_invocation_id`
+#[derive(Debug)]
+pub struct BuiltinVar {
+    pub name: &'static str,
+    pub glsl: &'static str,
+    pub ty: YaslType,
+    pub stages: &'static [ShaderStage],
+    /// Same value for every invocation in a workgroup
+    pub uniform: bool,
+}
+
+#[derive(Debug)]
+pub enum BuiltinReturn {
+    Void,
+    /// Same type as type of the first argument
+    FirstArg,
+    /// Component type of the first argument, like `length(v)`
+    Component,
+    /// u32, like `a.len()`
+    UInt,
+}
+
+/// Values a builtin parameter accepts
+#[derive(Debug)]
+pub enum BuiltinParam {
+    /// f32 scalar or vector, `genFType`
+    Float,
+    /// f32 or f64 scalar or vector, `genFType` and `genDType`
+    FloatOrDouble,
+    /// Scalar or vector of a signed number type
+    Signed,
+    /// Scalar or vector of any number type
+    Number,
+    /// f32 or f64 vector of 3 components
+    Vec3,
+    /// i32 or u32 scalar
+    Integer,
+    /// Same type as the first argument
+    Same,
+    /// Same type as the first argument or its component type, like `clamp(v, 0.0, 1.0)`
+    SameOrComponent,
+    /// Array of any size, runtime sized ones included
+    Array,
+}
+
+impl BuiltinParam {
+    /// `first` is type of the first argument
+    pub fn accepts(&self, ty: &YaslType, first: &YaslType) -> bool {
+        use YaslScalarType::*;
+        let component = match ty {
+            YaslType::ScalarType(s) => s,
+            YaslType::Vec(v) => v.component(),
+            YaslType::Array(..) => return matches!(self, BuiltinParam::Array),
+            _ => return false,
+        };
+        match self {
+            BuiltinParam::Float => *component == Float32,
+            BuiltinParam::FloatOrDouble => matches!(component, Float32 | Float64),
+            BuiltinParam::Signed => matches!(component, Float32 | Float64 | Int),
+            BuiltinParam::Number => *component != Bool,
+            BuiltinParam::Vec3 => matches!(ty, YaslType::Vec(YaslVecType::Vec3(Float32 | Float64))),
+            BuiltinParam::Integer => matches!(ty, YaslType::ScalarType(Int | UInt)),
+            BuiltinParam::Same => ty == first,
+            BuiltinParam::Array => false,
+            BuiltinParam::SameOrComponent => {
+                ty == first
+                    || first.scalar().map(|s| YaslType::ScalarType(s.clone())) == Some(ty.clone())
+            }
+        }
+    }
+
+    pub fn describe(&self, first: &YaslType) -> String {
+        match self {
+            BuiltinParam::Float => "float scalar or vector".into(),
+            BuiltinParam::FloatOrDouble => "float or double scalar or vector".into(),
+            BuiltinParam::Signed => "float, double or int scalar or vector".into(),
+            BuiltinParam::Number => "scalar or vector of numbers".into(),
+            BuiltinParam::Vec3 => "vec3 or dvec3".into(),
+            BuiltinParam::Integer => "int or uint".into(),
+            BuiltinParam::Array => "array".into(),
+            BuiltinParam::Same => first.name(),
+            BuiltinParam::SameOrComponent => match first.scalar() {
+                Some(s) if first != &YaslType::ScalarType(s.clone()) => {
+                    format!("{} or {}", first.name(), Glsl::from(s))
+                }
+                _ => first.name(),
+            },
+        }
+    }
+}
+
+/// Function provided by the target language, for example `barrier()`
+#[derive(Debug)]
+pub struct BuiltinFn {
+    pub name: &'static str,
+    pub glsl: &'static str,
+    pub params: &'static [BuiltinParam],
+    pub ret: BuiltinReturn,
+    /// None when function is available in every stage
+    pub stages: Option<&'static [ShaderStage]>,
+    /// Has to be called from uniform control flow
+    pub uniform_only: bool,
+    /// First argument has to be an integer in a buffer or shared memory
+    pub atomic: bool,
+}
+
+impl BuiltinFn {
+    /// First argument that doesn't fit its parameter and the error about it,
+    /// arguments of unknown type are results of `glsl::` calls, GLSL checks those
+    pub fn mismatched_arg(&self, args: &[Option<YaslType>]) -> Option<(usize, String)> {
+        let first = args.first()?.as_ref()?;
+        args.iter()
+            .zip(self.params)
+            .enumerate()
+            .find_map(|(i, (ty, param))| match ty {
+                Some(ty) if !param.accepts(ty, first) => Some((
+                    i,
+                    format!(
+                        "{}() expects {}, found {}",
+                        self.name,
+                        param.describe(first),
+                        ty.name()
+                    ),
+                )),
+                _ => None,
+            })
+    }
+}
+
+pub static VARS: &[BuiltinVar] = &[
+    BuiltinVar {
+        name: "global_invocation_id",
+        glsl: "gl_GlobalInvocationID",
+        ty: UVEC3,
+        stages: COMPUTE,
+        uniform: false,
+    },
+    BuiltinVar {
+        name: "local_invocation_id",
+        glsl: "gl_LocalInvocationID",
+        ty: UVEC3,
+        stages: COMPUTE,
+        uniform: false,
+    },
+    BuiltinVar {
+        name: "local_invocation_index",
+        glsl: "gl_LocalInvocationIndex",
+        ty: UINT,
+        stages: COMPUTE,
+        uniform: false,
+    },
+    BuiltinVar {
+        name: "workgroup_id",
+        glsl: "gl_WorkGroupID",
+        ty: UVEC3,
+        stages: COMPUTE,
+        uniform: true,
+    },
+    BuiltinVar {
+        name: "num_workgroups",
+        glsl: "gl_NumWorkGroups",
+        ty: UVEC3,
+        stages: COMPUTE,
+        uniform: true,
+    },
+];
+
+macro_rules! barrier {
+    ($name: expr, $glsl: expr, $stages: expr, $uniform_only: expr) => {
+        BuiltinFn {
+            name: $name,
+            glsl: $glsl,
+            params: &[],
+            ret: BuiltinReturn::Void,
+            stages: Some($stages),
+            uniform_only: $uniform_only,
+            atomic: false,
+        }
+    };
+}
+
+macro_rules! atomic {
+    ($name: expr, $glsl: expr) => {
+        atomic!($name, $glsl, &[Integer, Same])
+    };
+    ($name: expr, $glsl: expr, $params: expr) => {
+        BuiltinFn {
+            name: $name,
+            glsl: $glsl,
+            params: $params,
+            ret: BuiltinReturn::FirstArg,
+            stages: None,
+            uniform_only: false,
+            atomic: true,
+        }
+    };
+}
+
+macro_rules! math {
+    ($name: expr, $params: expr) => {
+        BuiltinFn {
+            name: $name,
+            glsl: $name,
+            params: $params,
+            ret: BuiltinReturn::FirstArg,
+            stages: None,
+            uniform_only: false,
+            atomic: false,
+        }
+    };
+}
+
+macro_rules! reduce {
+    ($name: expr, $params: expr) => {
+        BuiltinFn {
+            name: $name,
+            glsl: $name,
+            params: $params,
+            ret: BuiltinReturn::Component,
+            stages: None,
+            uniform_only: false,
+            atomic: false,
+        }
+    };
+}
+
+use BuiltinParam::*;
+
+pub static FNS: &[BuiltinFn] = &[
+    barrier!("barrier", "barrier", BARRIER, true),
+    barrier!("memory_barrier", "memoryBarrier", COMPUTE, false),
+    barrier!(
+        "memory_barrier_shared",
+        "memoryBarrierShared",
+        COMPUTE,
+        false
+    ),
+    barrier!(
+        "memory_barrier_buffer",
+        "memoryBarrierBuffer",
+        COMPUTE,
+        false
+    ),
+    barrier!("memory_barrier_image", "memoryBarrierImage", COMPUTE, false),
+    barrier!("group_memory_barrier", "groupMemoryBarrier", COMPUTE, false),
+    atomic!("atomic_add", "atomicAdd"),
+    atomic!("atomic_min", "atomicMin"),
+    atomic!("atomic_max", "atomicMax"),
+    atomic!("atomic_and", "atomicAnd"),
+    atomic!("atomic_or", "atomicOr"),
+    atomic!("atomic_xor", "atomicXor"),
+    atomic!("atomic_exchange", "atomicExchange"),
+    atomic!("atomic_comp_swap", "atomicCompSwap", &[Integer, Same, Same]),
+    math!("abs", &[Signed]),
+    math!("min", &[Number, SameOrComponent]),
+    math!("max", &[Number, SameOrComponent]),
+    math!("clamp", &[Number, SameOrComponent, SameOrComponent]),
+    math!("pow", &[Float, Same]),
+    math!("sqrt", &[FloatOrDouble]),
+    math!("floor", &[FloatOrDouble]),
+    math!("ceil", &[FloatOrDouble]),
+    math!("round", &[FloatOrDouble]),
+    math!("sin", &[Float]),
+    math!("cos", &[Float]),
+    math!("tan", &[Float]),
+    math!("exp", &[Float]),
+    math!("log", &[Float]),
+    math!("sign", &[Signed]),
+    math!("trunc", &[FloatOrDouble]),
+    math!("mix", &[FloatOrDouble, Same, SameOrComponent]),
+    math!("normalize", &[FloatOrDouble]),
+    math!("cross", &[Vec3, Same]),
+    reduce!("length", &[FloatOrDouble]),
+    reduce!("distance", &[FloatOrDouble, Same]),
+    reduce!("dot", &[FloatOrDouble, Same]),
+    // Number of elements of an array, `a.length()` in GLSL
+    BuiltinFn {
+        name: "len",
+        glsl: "length",
+        params: &[Array],
+        ret: BuiltinReturn::UInt,
+        stages: None,
+        uniform_only: false,
+        atomic: false,
+    },
+];
+
+/// Rust names of builtins that are spelled differently in GLSL, usable as methods
+pub static METHOD_ALIASES: &[(&str, &str)] = &[
+    ("powf", "pow"),
+    ("ln", "log"),
+    ("signum", "sign"),
+    ("lerp", "mix"),
+];
+
+pub fn var(name: &str) -> Option<&'static BuiltinVar> {
+    VARS.iter().find(|v| v.name == name)
+}
+
+pub fn function(name: &str) -> Option<&'static BuiltinFn> {
+    FNS.iter().find(|f| f.name == name)
+}
+
+/// Builtin written with its GLSL name, like `glsl::atomicAdd`
+pub fn glsl_function(name: &str) -> Option<&'static BuiltinFn> {
+    FNS.iter().find(|f| f.glsl == name)
+}
+
+/// Builtin called as a method on its first argument, `v.length()` or `x.powf(2.0)`
+pub fn method(name: &str) -> Option<&'static BuiltinFn> {
+    let name = METHOD_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, builtin)| builtin);
+    function(name)
+}
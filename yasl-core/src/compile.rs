@@ -0,0 +1,122 @@
+//! Compiling YASL source at runtime, for tools, build scripts and hot reloading
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use syn::parse::{ParseStream, Parser};
+
+use crate::{
+    is_program, CompileOptions, Diagnostics, LineColumn, Program, Reflection, Shader, ShaderStage,
+};
+
+/// One compiled stage, owns everything so it can be sent to other threads
+#[derive(Debug, Clone)]
+pub struct CompiledStage {
+    pub stage: ShaderStage,
+    pub glsl: String,
+    /// Compiled by shaderc, with the `shaderc` feature
+    #[cfg(feature = "shaderc")]
+    pub spirv: Vec<u32>,
+    pub reflection: Reflection,
+    /// Start and end of the YASL code of every line of `glsl`, see `Shader::line_span`
+    pub sourcemap: Vec<Option<(LineColumn, LineColumn)>>,
+}
+
+impl CompiledStage {
+    fn new(shader: Shader, _options: &CompileOptions) -> syn::Result<Self> {
+        Ok(Self {
+            #[cfg(feature = "shaderc")]
+            spirv: shader.spirv(_options)?,
+            sourcemap: shader
+                .sourcemap
+                .iter()
+                .map(|l| l.span.map(|s| (s.start(), s.end())))
+                .collect(),
+            stage: shader.stage,
+            glsl: shader.glsl,
+            reflection: shader.reflection,
+        })
+    }
+}
+
+/// Stages compiled from one source, in pipeline order, a single one unless it is a program
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub stages: Vec<CompiledStage>,
+}
+
+impl Output {
+    pub fn stage(&self, stage: ShaderStage) -> Option<&CompiledStage> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledShader {
+    pub path: PathBuf,
+    pub stages: Vec<CompiledStage>,
+}
+
+impl CompiledShader {
+    pub fn stage(&self, stage: ShaderStage) -> Option<&CompiledStage> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+}
+
+fn parse_stages(
+    source: &str,
+    stage: ShaderStage,
+    options: &CompileOptions,
+) -> syn::Result<Vec<Shader>> {
+    let program = source.parse::<TokenStream>().is_ok_and(|t| is_program(&t));
+    if program {
+        let parser = |ps: ParseStream| Program::parse_with(ps, options);
+        Ok(parser.parse_str(source)?.stages)
+    } else {
+        let parser = |ps: ParseStream| Shader::parse_with(ps, stage, options);
+        Ok(vec![parser.parse_str(source)?])
+    }
+}
+
+fn compile_stages(
+    source: &str,
+    stage: ShaderStage,
+    options: &CompileOptions,
+) -> syn::Result<Vec<CompiledStage>> {
+    parse_stages(source, stage, options)?
+        .into_iter()
+        .map(|s| CompiledStage::new(s, options))
+        .collect()
+}
+
+/// Every stage of the source, with spans, declarations and the IR, for tools that need more than the output of `compile`
+pub fn parse(source: &str, options: &CompileOptions) -> Result<Vec<Shader>, Diagnostics> {
+    let stage = options.stage.unwrap_or(ShaderStage::Vertex);
+    Ok(parse_stages(source, stage, options)?)
+}
+
+/// Compiles YASL source, sources with stage blocks are programs, others are a single stage, see `CompileOptions::stage`
+pub fn compile(source: &str, options: &CompileOptions) -> Result<Output, Diagnostics> {
+    let stage = options.stage.unwrap_or(ShaderStage::Vertex);
+    let stages = compile_stages(source, stage, options)?;
+    Ok(Output { stages })
+}
+
+/// Reads and compiles a `.yasl` file, single stage files without `CompileOptions::stage` get it from their name,
+/// see `ShaderStage::from_path`
+pub fn compile_file(
+    path: impl AsRef<Path>,
+    options: &CompileOptions,
+) -> Result<CompiledShader, Diagnostics> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| Diagnostics::new(e.to_string()).with_path(path))?;
+    let stage = options
+        .stage
+        .unwrap_or_else(|| ShaderStage::from_path(path));
+    let stages = compile_stages(&source, stage, options)
+        .map_err(|e| Diagnostics::from(e).with_path(path))?;
+    Ok(CompiledShader {
+        path: path.to_path_buf(),
+        stages,
+    })
+}
@@ -0,0 +1,632 @@
+//! CPU versions of YASL vectors and builtins, so that `#[yasl_macro::shared]` functions
+//! compile as ordinary Rust, for example:
+//! ```ignore
+//! use yasl_core::cpu::*;
+//!
+//! #[yasl_macro::shared]
+//! fn luminance(c: vec3<f32>) -> f32 {
+//!     c.dot(vec3(0.2126, 0.7152, 0.0722))
+//! }
+//! ```
+#![allow(non_camel_case_types)]
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::yasl_type::{YaslScalarType, YaslType, YaslVecType};
+
+/// Component of a vector
+pub trait Scalar:
+    Copy
+    + Default
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+{
+}
+
+impl Scalar for i32 {}
+impl Scalar for u32 {}
+impl Scalar for f32 {}
+impl Scalar for f64 {}
+
+/// Floating point component, for functions like `sqrt`
+pub trait Real: Scalar + Neg<Output = Self> {
+    fn one() -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, e: Self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn trunc(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+}
+
+macro_rules! real {
+    ($t: ty) => {
+        impl Real for $t {
+            fn one() -> Self {
+                1.0
+            }
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+            fn powf(self, e: Self) -> Self {
+                <$t>::powf(self, e)
+            }
+            fn floor(self) -> Self {
+                <$t>::floor(self)
+            }
+            fn ceil(self) -> Self {
+                <$t>::ceil(self)
+            }
+            fn round(self) -> Self {
+                <$t>::round(self)
+            }
+            fn trunc(self) -> Self {
+                <$t>::trunc(self)
+            }
+            fn sin(self) -> Self {
+                <$t>::sin(self)
+            }
+            fn cos(self) -> Self {
+                <$t>::cos(self)
+            }
+            fn tan(self) -> Self {
+                <$t>::tan(self)
+            }
+            fn exp(self) -> Self {
+                <$t>::exp(self)
+            }
+            fn ln(self) -> Self {
+                <$t>::ln(self)
+            }
+        }
+    };
+}
+real!(f32);
+real!(f64);
+
+/// Scalar or vector, builtins work on every component
+pub trait GenType: Copy {
+    type Scalar: Scalar;
+    fn map(self, f: impl Fn(Self::Scalar) -> Self::Scalar) -> Self;
+    fn zip(self, other: Self, f: impl Fn(Self::Scalar, Self::Scalar) -> Self::Scalar) -> Self;
+    /// Sum of every component
+    fn sum(self) -> Self::Scalar;
+}
+
+macro_rules! scalar_gen_type {
+    ($($t: ty),*) => {$(
+        impl GenType for $t {
+            type Scalar = $t;
+            fn map(self, f: impl Fn($t) -> $t) -> Self {
+                f(self)
+            }
+            fn zip(self, other: Self, f: impl Fn($t, $t) -> $t) -> Self {
+                f(self, other)
+            }
+            fn sum(self) -> $t {
+                self
+            }
+        }
+    )*};
+}
+scalar_gen_type!(i32, u32, f32, f64);
+
+macro_rules! vector {
+    ($name: ident, $($c: ident),*) => {
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, Default, PartialEq)]
+        pub struct $name<T> {
+            $(pub $c: T,)*
+        }
+
+        /// Constructor, same as in shaders
+        pub fn $name<T>($($c: T),*) -> $name<T> {
+            $name { $($c),* }
+        }
+
+        impl<T: Scalar> GenType for $name<T> {
+            type Scalar = T;
+            fn map(self, f: impl Fn(T) -> T) -> Self {
+                $name { $($c: f(self.$c)),* }
+            }
+            fn zip(self, other: Self, f: impl Fn(T, T) -> T) -> Self {
+                $name { $($c: f(self.$c, other.$c)),* }
+            }
+            fn sum(self) -> T {
+                T::default() $(+ self.$c)*
+            }
+        }
+
+        impl<T: Scalar> $name<T> {
+            pub fn splat(v: T) -> Self {
+                $name { $($c: v),* }
+            }
+            pub fn dot(self, other: Self) -> T {
+                dot(self, other)
+            }
+            pub fn abs(self) -> Self {
+                abs(self)
+            }
+            pub fn min(self, other: Self) -> Self {
+                min(self, other)
+            }
+            pub fn max(self, other: Self) -> Self {
+                max(self, other)
+            }
+            pub fn clamp(self, lo: Self, hi: Self) -> Self {
+                clamp(self, lo, hi)
+            }
+        }
+
+        impl<T: Real> $name<T> {
+            pub fn length(self) -> T {
+                length(self)
+            }
+            pub fn distance(self, other: Self) -> T {
+                distance(self, other)
+            }
+            pub fn normalize(self) -> Self {
+                normalize(self)
+            }
+            pub fn lerp(self, other: Self, t: T) -> Self {
+                mix(self, other, t)
+            }
+            pub fn sqrt(self) -> Self {
+                sqrt(self)
+            }
+            pub fn powf(self, e: Self) -> Self {
+                pow(self, e)
+            }
+            pub fn floor(self) -> Self {
+                floor(self)
+            }
+            pub fn ceil(self) -> Self {
+                ceil(self)
+            }
+            pub fn round(self) -> Self {
+                round(self)
+            }
+            pub fn exp(self) -> Self {
+                exp(self)
+            }
+            pub fn ln(self) -> Self {
+                log(self)
+            }
+            pub fn signum(self) -> Self {
+                sign(self)
+            }
+        }
+
+        impl<T: Scalar> Add for $name<T> {
+            type Output = Self;
+            fn add(self, o: Self) -> Self {
+                self.zip(o, |a, b| a + b)
+            }
+        }
+        impl<T: Scalar> Sub for $name<T> {
+            type Output = Self;
+            fn sub(self, o: Self) -> Self {
+                self.zip(o, |a, b| a - b)
+            }
+        }
+        impl<T: Scalar> Mul for $name<T> {
+            type Output = Self;
+            fn mul(self, o: Self) -> Self {
+                self.zip(o, |a, b| a * b)
+            }
+        }
+        impl<T: Scalar> Div for $name<T> {
+            type Output = Self;
+            fn div(self, o: Self) -> Self {
+                self.zip(o, |a, b| a / b)
+            }
+        }
+        impl<T: Scalar> Add<T> for $name<T> {
+            type Output = Self;
+            fn add(self, o: T) -> Self {
+                self.map(|a| a + o)
+            }
+        }
+        impl<T: Scalar> Sub<T> for $name<T> {
+            type Output = Self;
+            fn sub(self, o: T) -> Self {
+                self.map(|a| a - o)
+            }
+        }
+        impl<T: Scalar> Mul<T> for $name<T> {
+            type Output = Self;
+            fn mul(self, o: T) -> Self {
+                self.map(|a| a * o)
+            }
+        }
+        impl<T: Scalar> Div<T> for $name<T> {
+            type Output = Self;
+            fn div(self, o: T) -> Self {
+                self.map(|a| a / o)
+            }
+        }
+        impl<T: Scalar + Neg<Output = T>> Neg for $name<T> {
+            type Output = Self;
+            fn neg(self) -> Self {
+                self.map(|a| -a)
+            }
+        }
+
+        scalar_ops!($name, i32, u32, f32, f64);
+    };
+}
+
+/// `scalar op vector`, can't be implemented generically for foreign scalars
+macro_rules! scalar_ops {
+    ($name: ident, $($t: ty),*) => {$(
+        impl Add<$name<$t>> for $t {
+            type Output = $name<$t>;
+            fn add(self, o: $name<$t>) -> $name<$t> {
+                o.map(|a| self + a)
+            }
+        }
+        impl Sub<$name<$t>> for $t {
+            type Output = $name<$t>;
+            fn sub(self, o: $name<$t>) -> $name<$t> {
+                o.map(|a| self - a)
+            }
+        }
+        impl Mul<$name<$t>> for $t {
+            type Output = $name<$t>;
+            fn mul(self, o: $name<$t>) -> $name<$t> {
+                o.map(|a| self * a)
+            }
+        }
+        impl Div<$name<$t>> for $t {
+            type Output = $name<$t>;
+            fn div(self, o: $name<$t>) -> $name<$t> {
+                o.map(|a| self / a)
+            }
+        }
+    )*};
+}
+
+vector!(vec2, x, y);
+vector!(vec3, x, y, z);
+vector!(vec4, x, y, z, w);
+
+impl<T: Real> vec3<T> {
+    pub fn cross(self, other: Self) -> Self {
+        cross(self, other)
+    }
+}
+
+pub fn abs<G: GenType>(x: G) -> G {
+    x.map(|c| {
+        if c < G::Scalar::default() {
+            G::Scalar::default() - c
+        } else {
+            c
+        }
+    })
+}
+
+pub fn min<G: GenType>(a: G, b: G) -> G {
+    a.zip(b, |a, b| if b < a { b } else { a })
+}
+
+pub fn max<G: GenType>(a: G, b: G) -> G {
+    a.zip(b, |a, b| if b > a { b } else { a })
+}
+
+pub fn clamp<G: GenType>(x: G, lo: G, hi: G) -> G {
+    min(max(x, lo), hi)
+}
+
+pub fn dot<G: GenType>(a: G, b: G) -> G::Scalar {
+    a.zip(b, |a, b| a * b).sum()
+}
+
+pub fn mix<G: GenType>(a: G, b: G, t: G::Scalar) -> G {
+    a.zip(b, |a, b| a + (b - a) * t)
+}
+
+pub fn sign<G: GenType>(x: G) -> G
+where
+    G::Scalar: Real,
+{
+    let zero = G::Scalar::default();
+    x.map(|c| {
+        if c > zero {
+            G::Scalar::one()
+        } else if c < zero {
+            -G::Scalar::one()
+        } else {
+            zero
+        }
+    })
+}
+
+pub fn length<G: GenType>(v: G) -> G::Scalar
+where
+    G::Scalar: Real,
+{
+    dot(v, v).sqrt()
+}
+
+pub fn distance<G: GenType>(a: G, b: G) -> G::Scalar
+where
+    G::Scalar: Real,
+{
+    length(a.zip(b, |a, b| a - b))
+}
+
+pub fn normalize<G: GenType>(v: G) -> G
+where
+    G::Scalar: Real,
+{
+    let len = length(v);
+    v.map(|c| c / len)
+}
+
+pub fn cross<T: Real>(a: vec3<T>, b: vec3<T>) -> vec3<T> {
+    vec3(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    )
+}
+
+pub fn pow<G: GenType>(x: G, e: G) -> G
+where
+    G::Scalar: Real,
+{
+    x.zip(e, Real::powf)
+}
+
+macro_rules! componentwise {
+    ($($name: ident => $f: ident),*) => {$(
+        pub fn $name<G: GenType>(x: G) -> G
+        where
+            G::Scalar: Real,
+        {
+            x.map(Real::$f)
+        }
+    )*};
+}
+
+componentwise!(
+    sqrt => sqrt,
+    floor => floor,
+    ceil => ceil,
+    round => round,
+    trunc => trunc,
+    sin => sin,
+    cos => cos,
+    tan => tan,
+    exp => exp,
+    log => ln
+);
+
+/// Builtins and constructors implemented as functions above
+const FUNCTIONS: &[&str] = &[
+    "vec2",
+    "vec3",
+    "vec4",
+    "abs",
+    "min",
+    "max",
+    "clamp",
+    "dot",
+    "mix",
+    "sign",
+    "length",
+    "distance",
+    "normalize",
+    "cross",
+    "pow",
+    "sqrt",
+    "floor",
+    "ceil",
+    "round",
+    "trunc",
+    "sin",
+    "cos",
+    "tan",
+    "exp",
+    "log",
+];
+
+/// Methods of `vector!` types
+const VECTOR_METHODS: &[&str] = &[
+    "dot",
+    "abs",
+    "min",
+    "max",
+    "clamp",
+    "length",
+    "distance",
+    "normalize",
+    "lerp",
+    "sqrt",
+    "powf",
+    "floor",
+    "ceil",
+    "round",
+    "exp",
+    "ln",
+    "signum",
+];
+
+/// Methods std implements on floats that mean the same as the builtin they alias,
+/// `signum` isn't one of them, std gives 1.0 at 0.0 where GLSL `sign` gives 0.0, so floats use `sign(x)`
+const FLOAT_METHODS: &[&str] = &[
+    "abs", "min", "max", "clamp", "sqrt", "powf", "floor", "ceil", "round", "trunc", "sin", "cos",
+    "tan", "exp", "ln",
+];
+
+/// Function `#[shared]` functions can call, checked while validating them
+pub(crate) fn has_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name)
+}
+
+/// Method `#[shared]` functions can call on a value of type `ty`, like `v.length()`
+pub(crate) fn has_method(ty: &YaslType, name: &str) -> bool {
+    use YaslScalarType::*;
+    match ty {
+        YaslType::Vec(YaslVecType::Vec3(_)) if name == "cross" => true,
+        YaslType::Vec(_) => VECTOR_METHODS.contains(&name),
+        YaslType::ScalarType(Float32 | Float64) => FLOAT_METHODS.contains(&name),
+        YaslType::ScalarType(Int) => ["abs", "min", "max", "clamp", "signum"].contains(&name),
+        YaslType::ScalarType(UInt) => ["min", "max", "clamp"].contains(&name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompileOptions;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-6 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn vector_operators() {
+        let a = vec3(1.0, 2.0, 3.0);
+        let b = vec3(4.0, 6.0, 8.0);
+        assert_eq!(a + b, vec3(5.0, 8.0, 11.0));
+        assert_eq!(b - a, vec3(3.0, 4.0, 5.0));
+        assert_eq!(a * b, vec3(4.0, 12.0, 24.0));
+        assert_eq!(b / a, vec3(4.0, 3.0, 8.0 / 3.0));
+        assert_eq!(a + 1.0, vec3(2.0, 3.0, 4.0));
+        assert_eq!(a - 1.0, vec3(0.0, 1.0, 2.0));
+        assert_eq!(a * 2.0, vec3(2.0, 4.0, 6.0));
+        assert_eq!(b / 2.0, vec3(2.0, 3.0, 4.0));
+        assert_eq!(1.0 - a, vec3(0.0, -1.0, -2.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(12.0 / b, vec3(3.0, 2.0, 1.5));
+        assert_eq!(-a, vec3(-1.0, -2.0, -3.0));
+        assert_eq!(vec2(7u32, 9) / 2, vec2(3, 4));
+        assert_eq!(vec4(1, -2, 3, -4) * -1, vec4(-1, 2, -3, 4));
+        assert_eq!(vec2::splat(0.5f64), vec2(0.5, 0.5));
+    }
+
+    #[test]
+    fn builtins() {
+        let a = vec3(3.0f32, -4.0, 0.0);
+        assert_eq!(a.dot(vec3(1.0, 1.0, 1.0)), -1.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.abs().length(), 5.0);
+        assert_eq!(a.normalize(), vec3(0.6, -0.8, 0.0));
+        assert_eq!(a.distance(vec3(3.0, 0.0, 0.0)), 4.0);
+        assert_eq!(a.min(vec3(0.0, 0.0, 0.0)), vec3(0.0, -4.0, 0.0));
+        assert_eq!(a.max(vec3(0.0, 0.0, 0.0)), vec3(3.0, 0.0, 0.0));
+        assert_eq!(
+            a.clamp(vec3::splat(-1.0), vec3::splat(1.0)),
+            vec3(1.0, -1.0, 0.0)
+        );
+        assert_eq!(a.signum(), vec3(1.0, -1.0, 0.0));
+        assert_eq!(sign(-2.5f32), -1.0);
+        assert_eq!(sign(0.0f32), 0.0);
+        assert_eq!(sign(0.0f64), 0.0);
+        assert_eq!(0i32.signum(), 0);
+        assert_eq!(
+            vec3(1.0, 0.0, 0.0).cross(vec3(0.0, 1.0, 0.0)),
+            vec3(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            vec2(0.0f32, 10.0).lerp(vec2(10.0, 20.0), 0.25),
+            vec2(2.5, 12.5)
+        );
+        assert_eq!(mix(1.0f32, 3.0, 0.5), 2.0);
+        assert_eq!(vec2(1.5f32, -1.5).floor(), vec2(1.0, -2.0));
+        assert_eq!(vec2(1.5f32, -1.5).ceil(), vec2(2.0, -1.0));
+        assert_eq!(trunc(vec2(1.5f32, -1.5)), vec2(1.0, -1.0));
+        assert_eq!(vec2(4.0f32, 9.0).sqrt(), vec2(2.0, 3.0));
+        assert_eq!(vec2(2.0f32, 3.0).powf(vec2(3.0, 2.0)), vec2(8.0, 9.0));
+        assert_eq!(pow(2.0f64, 10.0), 1024.0);
+        assert_eq!(abs(-3), 3);
+        assert_eq!(clamp(7u32, 1, 5), 5);
+        assert!(close(
+            vec2(1.0f64, 0.0).exp().ln().length(),
+            vec2(1.0f64, 0.0).length()
+        ));
+    }
+
+    fn shared(source: &str) -> syn::Result<()> {
+        crate::validate_shared(&syn::parse_str(source)?, &CompileOptions::default())
+    }
+
+    #[test]
+    fn shared_functions_only_call_what_cpu_implements() {
+        assert!(shared("fn f(a: vec3<f32>, b: vec3<f32>) -> vec3<f32> { a.lerp(b, 0.5) }").is_ok());
+        assert!(shared("fn f(a: vec3<f32>, b: vec3<f32>) -> vec3<f32> { a.cross(b) }").is_ok());
+        assert!(shared("fn f(a: f32) -> f32 { a.sqrt().max(sin(a)) }").is_ok());
+        assert!(shared("fn f(a: f32, b: f32) -> f32 { a.lerp(b, 0.5) }").is_err());
+        assert!(shared("fn f(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> { a.cross(b) }").is_err());
+        assert!(shared("fn f(a: vec2<f32>) -> vec2<f32> { a.sin() }").is_err());
+        assert!(shared("fn f(a: u32) -> u32 { a.abs() }").is_err());
+        // std `f32::signum` is 1.0 at 0.0, unlike GLSL `sign`
+        assert!(shared("fn f(a: f32) -> f32 { a.signum() }").is_err());
+        assert!(shared("fn f(a: f32) -> f32 { sign(a) }").is_ok());
+        assert!(shared("fn f(a: i32) -> i32 { a.signum() }").is_ok());
+        assert!(shared("fn f(a: f32) -> f32 { glsl::fract(a) }").is_err());
+    }
+
+    /// Value the shader compiler folds `body` to, with parameters replaced by constants
+    fn folded(params: &[(&str, &str, String)], ty: &str, body: &str) -> f64 {
+        let mut source = String::new();
+        for (name, ty, value) in params {
+            source += &format!("const {}: {} = {};\n", name, ty, value);
+        }
+        source += &format!("const OUT: {} = {};\nfn main() {{}}\n", ty, body);
+        let output = crate::compile(&source, &CompileOptions::default()).unwrap();
+        let line = output.stages[0]
+            .glsl
+            .lines()
+            .find(|l| l.contains(" OUT = "))
+            .unwrap();
+        let value = line.split(" = ").nth(1).unwrap();
+        value
+            .trim_end_matches(';')
+            .trim_end_matches(&['u', 'l', 'f'][..])
+            .parse()
+            .unwrap()
+    }
+
+    /// Defines the function like `#[shared]` does, validates it as a shared function,
+    /// and compares its result with what the shader compiler computes for the same arguments
+    macro_rules! same_on_cpu {
+        ($($test: ident: fn($($p: ident: $t: ident = $v: expr),*) -> $r: ident { $body: expr })*) => {$(
+            #[test]
+            fn $test() {
+                fn f($($p: $t),*) -> $r {
+                    $body
+                }
+                let params = [$((stringify!($p), stringify!($t), format!("{:?}", $v as $t))),*];
+                let args: Vec<String> = params.iter().map(|(p, t, _)| format!("{}: {}", p, t)).collect();
+                let item = format!("fn f({}) -> {} {{ {} }}", args.join(", "), stringify!($r), stringify!($body));
+                shared(&item).unwrap();
+
+                let cpu = f($($v as $t),*) as f64;
+                let gpu = folded(&params, stringify!($r), stringify!($body));
+                assert!(close(cpu, gpu), "{} on cpu, {} in shader", cpu, gpu);
+            }
+        )*};
+    }
+
+    same_on_cpu! {
+        same_max_sqrt: fn(a: f32 = 0.75, b: f32 = 2.0) -> f32 { (a * 4.0).max(b).sqrt() }
+        same_clamp_floor: fn(x: f32 = 1.75) -> f32 { x.clamp(0.0, 1.0) + x.floor() - x.ceil() }
+        same_pow_abs: fn(x: f32 = -1.5) -> f32 { x.abs().powf(3.0) / x.round() }
+        same_trig: fn(x: f32 = 0.5) -> f32 { x.sin() * x.cos() + x.tan() - x.exp().ln() }
+        same_int: fn(x: i32 = -7, y: i32 = 3) -> i32 { x.abs().min(y * 2) % 4 - y.max(1) }
+        same_uint: fn(x: u32 = 7) -> u32 { x.max(9) / 2 + x.clamp(1, 5) }
+        same_functions: fn(x: f32 = 0.3) -> f32 { sqrt(x) + min(x, 0.1) * clamp(x * 3.0, 0.0, 1.0) }
+    }
+}
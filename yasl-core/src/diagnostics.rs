@@ -0,0 +1,72 @@
+//! Errors of `compile` and `compile_file`, with line and column numbers instead of spans so they outlive the parse
+use std::fmt;
+use std::path::PathBuf;
+
+pub use proc_macro2::LineColumn;
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Start and end of the YASL code it is about, lines are 1-based and columns 0-based.
+    /// None for errors without a place in the source, like a missing file
+    pub location: Option<(LineColumn, LineColumn)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    /// File the errors are in
+    pub path: Option<PathBuf>,
+    pub errors: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Single error without a location
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            errors: vec![Diagnostic {
+                message: message.into(),
+                location: None,
+            }],
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl From<syn::Error> for Diagnostics {
+    fn from(e: syn::Error) -> Self {
+        let errors = e
+            .into_iter()
+            .map(|e| Diagnostic {
+                message: e.to_string(),
+                location: Some((e.span().start(), e.span().end())),
+            })
+            .collect();
+        Self { path: None, errors }
+    }
+}
+
+/// One `path:line:column: message` line per error, with a 1-based column like compilers print them
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            if let Some(path) = &self.path {
+                write!(f, "{}:", path.display())?;
+            }
+            if let Some((start, _)) = e.location {
+                write!(f, "{}:{}:", start.line, start.column + 1)?;
+            }
+            write!(f, " {}", e.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
@@ -0,0 +1,727 @@
+//! Source formatter for `.yasl` files, reprints tokens in one canonical style and keeps comments
+//!
+//! Comments aren't tokens, they are recovered from the source text between token spans,
+//! so this only works outside of proc macros, where spans have line and column information.
+use proc_macro2::{Delimiter, LineColumn, Spacing, Span, TokenStream, TokenTree};
+use syn::parse::{ParseStream, Parser};
+use syn::{Error, Result};
+
+use crate::yasl_const::Consts;
+use crate::yasl_file::YaslFile;
+use crate::yasl_program::{is_program, YaslProgram};
+
+const INDENT: &str = "    ";
+
+/// Operators made of more than one punctuation character, longest first
+const OPERATORS: &[&str] = &[
+    "..=", "<<=", ">>=", "->", "=>", "::", "..", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>",
+];
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "else", "enum", "fn", "for", "if", "in", "let", "loop",
+    "match", "mut", "return", "shared", "static", "struct", "while",
+];
+
+/// Words followed by generic arguments rather than a comparison
+const GENERIC: &[&str] = &["layout", "vec2", "vec3", "vec4"];
+
+/// Formats a YASL file or program, fails if it doesn't parse
+///
+/// Blocks are indented by 4 spaces, every statement, item, struct field and match arm gets its own line,
+/// and at most one blank line is kept between them. Lines are never wrapped.
+pub fn format_source(source: &str) -> Result<String> {
+    let stream: TokenStream = source
+        .parse()
+        .map_err(|e| Error::new(Span::call_site(), format!("{:?}", e)))?;
+    if is_program(&stream) {
+        YaslProgram::parse.parse2(stream.clone())?;
+    } else {
+        let parser = |ps: ParseStream| YaslFile::parse(ps, Consts::default());
+        parser.parse2(stream.clone())?;
+    }
+
+    let mut lexer = Lexer::new(source);
+    lexer.stream(stream);
+    lexer.gap(source.len());
+
+    let mut printer = Printer::default();
+    for (i, t) in lexer.tokens.iter().enumerate() {
+        printer.token(t, &lexer.tokens[i + 1..]);
+    }
+    Ok(printer.finish())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Word,
+    Literal,
+    Punct,
+    Open(Delimiter),
+    Close(Delimiter),
+    Comment,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: Kind,
+    text: String,
+    /// Source had a line break in front of the token
+    newline_before: bool,
+    /// Source had an empty line in front of the token
+    blank_before: bool,
+}
+
+impl Token {
+    fn is(&self, text: &str) -> bool {
+        self.kind != Kind::Comment && self.text == text
+    }
+}
+
+/// Flattens the token trees, combines punctuation into operators and picks up comments between tokens
+struct Lexer<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    /// Byte offset where the last token ended
+    end: usize,
+    /// Doc comments are lexed into `#[doc = ".."]`, every token of it has the comment's span
+    doc: Option<LineColumn>,
+    puncts: String,
+    /// Line breaks in front of the first of `puncts`
+    puncts_gap: (bool, bool),
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            source,
+            line_starts,
+            end: 0,
+            doc: None,
+            puncts: String::new(),
+            puncts_gap: (false, false),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Byte offset of a line and (character) column
+    fn offset(&self, lc: LineColumn) -> usize {
+        let start = self.line_starts[lc.line.saturating_sub(1).min(self.line_starts.len() - 1)];
+        self.source[start..]
+            .char_indices()
+            .nth(lc.column)
+            .map_or(self.source.len(), |(i, _)| start + i)
+    }
+
+    fn stream(&mut self, stream: TokenStream) {
+        for tt in stream {
+            let span = tt.span();
+            if self.doc == Some(span.start()) {
+                continue;
+            }
+            let text = &self.source[self.offset(span.start())..];
+            if text.starts_with("//") || text.starts_with("/*") {
+                self.flush();
+                self.doc = Some(span.start());
+                self.push(span, Kind::Comment, None);
+                continue;
+            }
+            match tt {
+                TokenTree::Group(g) => {
+                    self.flush();
+                    self.push(g.span_open(), Kind::Open(g.delimiter()), None);
+                    self.stream(g.stream());
+                    self.flush();
+                    self.push(g.span_close(), Kind::Close(g.delimiter()), None);
+                }
+                TokenTree::Ident(i) => {
+                    self.flush();
+                    self.push(span, Kind::Word, Some(i.to_string()));
+                }
+                TokenTree::Literal(l) => {
+                    self.flush();
+                    self.push(span, Kind::Literal, Some(l.to_string()));
+                }
+                TokenTree::Punct(p) => {
+                    if self.puncts.is_empty() {
+                        self.puncts_gap = self.gap(self.offset(span.start()));
+                    }
+                    self.puncts.push(p.as_char());
+                    self.end = self.offset(span.end());
+                    if p.spacing() == Spacing::Alone {
+                        self.flush();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Splits joint punctuation into operators
+    fn flush(&mut self) {
+        let mut rest = std::mem::take(&mut self.puncts);
+        let (mut newline_before, mut blank_before) = self.puncts_gap;
+        while !rest.is_empty() {
+            let len = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .map_or(1, |op| op.len());
+            self.tokens.push(Token {
+                kind: Kind::Punct,
+                text: rest[..len].to_string(),
+                newline_before,
+                blank_before,
+            });
+            newline_before = false;
+            blank_before = false;
+            rest = rest.split_off(len);
+        }
+    }
+
+    /// Pushes a token after the comments in front of it, `text` defaults to the source text of the span
+    fn push(&mut self, span: Span, kind: Kind, text: Option<String>) {
+        let (start, end) = (self.offset(span.start()), self.offset(span.end()));
+        let (newline_before, blank_before) = self.gap(start);
+        self.tokens.push(Token {
+            kind,
+            text: text.unwrap_or_else(|| self.source[start..end].trim_end().to_string()),
+            newline_before,
+            blank_before,
+        });
+        self.end = end;
+    }
+
+    /// Pushes the comments between the last token and `start`, returns line breaks in front of `start`
+    fn gap(&mut self, start: usize) -> (bool, bool) {
+        let mut rest = &self.source[self.end.min(start)..start];
+        let mut newlines = 0;
+        loop {
+            let trimmed = rest.trim_start();
+            newlines += rest[..rest.len() - trimmed.len()].matches('\n').count();
+            rest = trimmed;
+            let len = if rest.starts_with("//") {
+                rest.find('\n').unwrap_or(rest.len())
+            } else if rest.starts_with("/*") {
+                block_comment_len(rest)
+            } else {
+                break;
+            };
+            self.tokens.push(Token {
+                kind: Kind::Comment,
+                text: rest[..len].trim_end().to_string(),
+                newline_before: newlines > 0,
+                blank_before: newlines > 1,
+            });
+            rest = &rest[len..];
+            newlines = 0;
+        }
+        self.end = start;
+        (newlines > 0, newlines > 1)
+    }
+}
+
+/// Length of a `/* */` comment, they nest
+fn block_comment_len(s: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < s.len() {
+        if s[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if s[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += s[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    s.len()
+}
+
+/// What a printed token is to the tokens around it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    Word,
+    Keyword,
+    Open(Delimiter),
+    Close(Delimiter),
+    /// Binary operators and assignments
+    Op,
+    Unary,
+    /// `.`, `::` and ranges
+    Glue,
+    Pound,
+    Comma,
+    Semi,
+    Colon,
+    Question,
+    GenericOpen,
+    GenericClose,
+    Comment,
+}
+
+/// Space between two tokens on the same line
+fn space(prev: Role, next: Role) -> bool {
+    use Role::*;
+    !matches!(
+        (prev, next),
+        (
+            _,
+            Comma | Semi | Colon | Question | Glue | GenericOpen | GenericClose
+        ) | (_, Close(Delimiter::Parenthesis | Delimiter::Bracket))
+            | (Open(Delimiter::Parenthesis | Delimiter::Bracket), _)
+            | (Glue | Pound | Unary | GenericOpen, _)
+            | (
+                Word | Close(_) | GenericClose,
+                Open(Delimiter::Parenthesis | Delimiter::Bracket)
+            )
+            | (Open(Delimiter::Brace), Close(Delimiter::Brace))
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    /// Statements and items, one per line
+    Block,
+    /// Struct fields, enum variants and match arms, one per line with a trailing comma
+    List,
+    Inline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Break {
+    None,
+    Always,
+    /// After a `}`, unless the expression goes on like with `} else {` or `});`
+    UnlessContinued,
+}
+
+struct Group {
+    layout: Layout,
+    /// Attribute at the start of a line, the next token starts a new line
+    attribute: bool,
+    /// Role and end in the output of the last token that isn't a comment
+    last: Option<(Role, usize)>,
+}
+
+struct Printer {
+    out: String,
+    groups: Vec<Group>,
+    prev: Option<Role>,
+    /// Last two words, `struct Name {` is a list even with a single field
+    words: [String; 2],
+    pending: Break,
+    generics: usize,
+    line_start: bool,
+    /// A statement or entry goes on, lines breaking it are indented once more
+    continued: bool,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self {
+            out: String::new(),
+            groups: vec![Group {
+                layout: Layout::Block,
+                attribute: false,
+                last: None,
+            }],
+            prev: None,
+            words: Default::default(),
+            pending: Break::None,
+            generics: 0,
+            line_start: true,
+            continued: false,
+        }
+    }
+}
+
+impl Printer {
+    fn group(&self) -> &Group {
+        self.groups.last().expect("top level group")
+    }
+
+    fn depth(&self) -> usize {
+        self.groups
+            .iter()
+            .skip(1)
+            .filter(|g| g.layout != Layout::Inline)
+            .count()
+    }
+
+    fn newline(&mut self, blank: bool) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        if self.out.is_empty() {
+            return;
+        }
+        if !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        let after_open = self.out.trim_end().ends_with('{');
+        if blank && !after_open && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.line_start = true;
+    }
+
+    fn write(&mut self, role: Role, text: &str) {
+        if self.line_start {
+            let indent = INDENT.repeat(self.depth() + self.continued as usize);
+            self.out += &indent;
+            self.line_start = false;
+        } else if self.prev.is_some_and(|prev| space(prev, role)) {
+            self.out.push(' ');
+        }
+        self.out += text;
+        self.prev = Some(role);
+        if role != Role::Comment {
+            let end = self.out.len();
+            if let Some(g) = self.groups.last_mut() {
+                g.last = Some((role, end));
+            }
+            self.continued = true;
+        }
+    }
+
+    fn role(&mut self, t: &Token) -> Role {
+        // Comments between operands don't make `-` or `*` unary
+        let prev = match self.prev {
+            Some(Role::Comment) => self.group().last.map(|(role, _)| role),
+            prev => prev,
+        };
+        let operand_before = matches!(
+            prev,
+            Some(Role::Word | Role::Close(_) | Role::GenericClose | Role::Question)
+        ) && prev != Some(Role::Close(Delimiter::Brace));
+        match &t.kind {
+            Kind::Word if KEYWORDS.contains(&t.text.as_str()) => Role::Keyword,
+            Kind::Word | Kind::Literal => Role::Word,
+            Kind::Open(d) => Role::Open(*d),
+            Kind::Close(d) => Role::Close(*d),
+            Kind::Comment => Role::Comment,
+            Kind::Punct => match t.text.as_str() {
+                "," => Role::Comma,
+                ";" => Role::Semi,
+                ":" => Role::Colon,
+                "?" => Role::Question,
+                "#" => Role::Pound,
+                "." | "::" | ".." | "..=" => Role::Glue,
+                "<" if GENERIC.contains(&self.words[1].as_str())
+                    && self.prev == Some(Role::Word) =>
+                {
+                    self.generics += 1;
+                    Role::GenericOpen
+                }
+                "<" if self.prev == Some(Role::Glue) => {
+                    self.generics += 1;
+                    Role::GenericOpen
+                }
+                ">" if self.generics > 0 => {
+                    self.generics -= 1;
+                    Role::GenericClose
+                }
+                "-" | "!" | "*" | "&" if !operand_before => Role::Unary,
+                _ => Role::Op,
+            },
+        }
+    }
+
+    /// `rest` are the tokens after `t`
+    fn token(&mut self, t: &Token, rest: &[Token]) {
+        if t.kind == Kind::Comment {
+            self.comment(t);
+            return;
+        }
+        let role = self.role(t);
+
+        // `a // note` followed by `,` or `;`, the punctuation goes before the comment
+        if self.prev == Some(Role::Comment)
+            && self.pending == Break::Always
+            && matches!(role, Role::Comma | Role::Semi)
+        {
+            if let Some((_, end)) = self.group().last {
+                self.out.insert_str(end, &t.text);
+                let layout = self.group().layout;
+                if let Some(g) = self.groups.last_mut() {
+                    g.last = Some((role, end + t.text.len()));
+                }
+                if matches!(
+                    (role, layout),
+                    (Role::Semi, Layout::Block) | (Role::Comma, Layout::List)
+                ) {
+                    self.continued = false;
+                }
+                self.words = Default::default();
+                return;
+            }
+        }
+
+        let continued = matches!(
+            role,
+            Role::Comma
+                | Role::Semi
+                | Role::Glue
+                | Role::Question
+                | Role::Close(Delimiter::Parenthesis | Delimiter::Bracket)
+        ) || t.is("else");
+        let closes_lines = role == Role::Close(Delimiter::Brace)
+            && self.group().layout != Layout::Inline
+            && self.prev != Some(Role::Open(Delimiter::Brace));
+        let breaks = match self.pending {
+            Break::Always => true,
+            Break::UnlessContinued => !continued,
+            Break::None => false,
+        };
+        if breaks || closes_lines {
+            self.newline(t.blank_before && !closes_lines);
+        }
+        self.pending = Break::None;
+
+        match role {
+            Role::Open(d) => {
+                let attribute = self.prev == Some(Role::Pound) && self.line_starts_with_pound();
+                let layout = match d {
+                    Delimiter::Brace => self.brace_layout(rest),
+                    _ => Layout::Inline,
+                };
+                self.write(role, &t.text);
+                self.groups.push(Group {
+                    layout,
+                    attribute,
+                    last: None,
+                });
+                if layout != Layout::Inline {
+                    self.continued = false;
+                    if rest.first().is_some_and(|n| n.kind != Kind::Close(d)) {
+                        self.pending = Break::Always;
+                    }
+                }
+            }
+            Role::Close(d) => {
+                if self.group().layout == Layout::List {
+                    self.trailing_comma();
+                }
+                let group = self.groups.pop().expect("open group");
+                let closes_block = d == Delimiter::Brace && group.layout != Layout::Inline;
+                if closes_block {
+                    self.continued = false;
+                }
+                self.write(role, &t.text);
+                if group.attribute {
+                    self.continued = false;
+                    self.pending = Break::Always;
+                } else if closes_block {
+                    self.continued = false;
+                    self.pending = match self.group().layout {
+                        Layout::Inline => Break::None,
+                        _ => Break::UnlessContinued,
+                    };
+                }
+            }
+            Role::Semi if self.group().layout == Layout::Block => {
+                self.write(role, &t.text);
+                self.continued = false;
+                self.pending = Break::Always;
+            }
+            Role::Comma if self.group().layout == Layout::List => {
+                self.write(role, &t.text);
+                self.continued = false;
+                self.pending = Break::Always;
+            }
+            _ => self.write(role, &t.text),
+        }
+        if matches!(t.kind, Kind::Word | Kind::Literal) {
+            self.words.swap(0, 1);
+            self.words[1] = t.text.clone();
+        } else {
+            self.words = Default::default();
+        }
+    }
+
+    fn line_starts_with_pound(&self) -> bool {
+        let line = &self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..];
+        line.trim_start().starts_with('#')
+    }
+
+    /// Braces holding `;` are blocks, ones with `,`, `=>` or `:` at their top level are lists
+    fn brace_layout(&self, rest: &[Token]) -> Layout {
+        if self.words[0] == "struct" || self.words[0] == "enum" {
+            return Layout::List;
+        }
+        let mut depth = 0;
+        let mut list = false;
+        for t in rest.iter() {
+            match t.kind {
+                Kind::Open(_) => depth += 1,
+                Kind::Close(_) if depth == 0 => break,
+                Kind::Close(_) => depth -= 1,
+                _ if depth > 0 => {}
+                _ if t.is(";") => return Layout::Block,
+                _ if t.is(",") || t.is("=>") || t.is(":") => list = true,
+                _ => {}
+            }
+        }
+        if list {
+            Layout::List
+        } else {
+            Layout::Block
+        }
+    }
+
+    fn trailing_comma(&mut self) {
+        if let Some((role, end)) = self.group().last {
+            if !matches!(
+                role,
+                Role::Comma | Role::Close(Delimiter::Brace) | Role::Open(Delimiter::Brace)
+            ) {
+                self.out.insert(end, ',');
+            }
+        }
+    }
+
+    /// Comments stay on the line they were on, or on their own line
+    fn comment(&mut self, t: &Token) {
+        if t.newline_before {
+            self.newline(t.blank_before);
+        }
+        if self.pending == Break::UnlessContinued {
+            self.pending = Break::Always;
+        }
+        self.write(Role::Comment, &t.text);
+        if t.text.starts_with("//") {
+            self.pending = Break::Always;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.newline(false);
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_source;
+
+    const MESSY: &str = "// File header
+/// Doc for the struct
+struct Light { power: f32, color: vec3<f32> } // trailing
+
+layout<output,0> o_color: vec4<f32>;
+enum Blend { Alpha }
+fn main() {   // opens main
+    // own line
+    let a = -1.0;   /* inline */ let b = !true;
+
+
+    let c = if a<b as f32 { 1.0 } else { 2.0 };
+    let l = 1.0; match 1 { 0 => {} _ => {} }
+    o_color = vec4(a*2.0, c, l, 1.0); // done
+}
+";
+
+    #[test]
+    fn formats_messy_source() {
+        assert_eq!(
+            format_source(MESSY).unwrap(),
+            "// File header
+/// Doc for the struct
+struct Light {
+    power: f32,
+    color: vec3<f32>,
+} // trailing
+
+layout<output, 0> o_color: vec4<f32>;
+enum Blend {
+    Alpha,
+}
+fn main() { // opens main
+    // own line
+    let a = -1.0; /* inline */
+    let b = !true;
+
+    let c = if a < b as f32 {
+        1.0
+    } else {
+        2.0
+    };
+    let l = 1.0;
+    match 1 {
+        0 => {}
+        _ => {}
+    }
+    o_color = vec4(a * 2.0, c, l, 1.0); // done
+}
+"
+        );
+    }
+
+    #[test]
+    fn keeps_trailing_comments_and_indents_continuations() {
+        assert_eq!(
+            format_source(
+                "fn main() {
+    let a = 1.0 +
+        2.0 + // two
+    3.0;
+    let b = vec4(a, // x
+a, a, a);
+    let c = match 1 { 1 => 2 // one
+        , _ => 3 };
+    let d = a // note
+    ;
+    let e = a
+    // own line
+    * 2.0;
+}"
+            )
+            .unwrap(),
+            "fn main() {
+    let a = 1.0 + 2.0 + // two
+        3.0;
+    let b = vec4(a, // x
+        a, a, a);
+    let c = match 1 {
+        1 => 2, // one
+        _ => 3,
+    };
+    let d = a; // note
+    let e = a
+        // own line
+        * 2.0;
+}
+"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            MESSY,
+            "vertex { // v
+layout<output,0,flat> id: u32;
+fn main() { id = 1; } } // after
+fragment { layout<input,0,flat> id: u32; fn main() {} }",
+            "layout<buffer,0> counters: [u32];
+const GROUP: u32 = 64;
+#[workgroup_size(GROUP)] // size
+fn main() { if local_invocation_index == 0 { atomic_add(counters[0], 1); } else { return; } }",
+            "fn split(v: f32, #[out] whole: &mut f32) { *whole = floor(v); }
+fn main() { let mut w = 0.0; split(1.5, &mut w); let x = match 3 { 0..=2 => 1, _ => 2 }; }",
+            "fn main() { let a = vec2(1.0, // x
+2.0) // v
+; let b = match 1 { 0 => 1 // zero
+, _ => -a.x // other
+* 2.0 }; }",
+        ];
+        for source in sources.iter() {
+            let once = format_source(source).unwrap();
+            assert_eq!(format_source(&once).unwrap(), once, "{}", source);
+        }
+    }
+}
@@ -1,21 +1,13 @@
 use proc_macro2::Span;
 
+pub mod format;
+
 #[derive(Debug)]
 pub enum Glsl {
     Fragment(GlslFragment),
     Expr(String),
     Line(GlslLine),
 }
-impl Glsl {
-    fn to_string(&self) -> String {
-        match self {
-            Glsl::Fragment(frag) => frag.to_string(),
-            Glsl::Expr(s) => s.to_owned(),
-            Glsl::Line(l) => l.to_string(),
-        }
-    }
-}
-
 impl From<Glsl> for String {
     fn from(glsl: Glsl) -> String {
         glsl.to_string()
@@ -29,7 +21,11 @@ impl From<&Glsl> for String {
 
 impl std::fmt::Display for Glsl {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self {
+            Glsl::Fragment(frag) => write!(f, "{}", frag),
+            Glsl::Expr(s) => write!(f, "{}", s),
+            Glsl::Line(l) => write!(f, "{}", l),
+        }
     }
 }
 
@@ -38,13 +34,32 @@ pub struct GlslLine {
     pub span: Option<Span>,
     pub ends_with_semi: bool,
     pub glsl_string: String,
+    /// Finer grained spans of parts of `glsl_string`, innermost first
+    pub segments: Vec<GlslSegment>,
 }
 impl GlslLine {
-    pub fn to_string(&self) -> String {
+    /// Innermost span covering given (0-based) column, or the span of the whole line
+    pub fn span_at(&self, column: usize) -> Option<Span> {
+        self.segments
+            .iter()
+            .find(|s| s.columns.contains(&column))
+            .map(|s| s.span)
+            .or(self.span)
+    }
+}
+
+/// Columns of a line of generated GLSL, and the YASL source they were generated from
+#[derive(Debug, Clone)]
+pub struct GlslSegment {
+    pub columns: std::ops::Range<usize>,
+    pub span: Span,
+}
+impl std::fmt::Display for GlslLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         if self.ends_with_semi {
-            self.glsl_string.clone() + ";\n"
+            writeln!(f, "{};", self.glsl_string)
         } else {
-            self.glsl_string.clone() + "\n"
+            writeln!(f, "{}", self.glsl_string)
         }
     }
 }
@@ -53,16 +68,15 @@ impl GlslLine {
 pub struct GlslFragment {
     pub elements: Vec<Glsl>,
 }
-impl GlslFragment {
-    pub fn to_string(&self) -> String {
-        let mut out: String = String::new();
-
+impl std::fmt::Display for GlslFragment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for l in &self.elements {
-            out += &l.to_string();
+            write!(f, "{}", l)?;
         }
-
-        out
+        Ok(())
     }
+}
+impl GlslFragment {
     pub fn squash(self) -> Vec<GlslLine> {
         let mut lines = Vec::new();
         for e in self.elements.into_iter() {
@@ -0,0 +1,168 @@
+//! Final layout of emitted lines, sourcemap columns are moved along with the text
+use std::collections::{HashMap, HashSet};
+
+use super::GlslLine;
+use crate::options::GlslFormat;
+
+/// Afterwards every line holds its final text, including the `;`
+pub fn format(lines: &mut [GlslLine], format: GlslFormat) {
+    match format {
+        GlslFormat::Pretty {
+            indent,
+            source_comments,
+        } => pretty(lines, indent, source_comments),
+        GlslFormat::Minified => minify(lines),
+    }
+    for l in lines.iter_mut() {
+        if l.ends_with_semi {
+            l.ends_with_semi = false;
+            l.glsl_string.insert(l.comment_start(), ';');
+        }
+    }
+}
+
+impl GlslLine {
+    /// Comments are only ever appended by the formatter
+    fn comment_start(&self) -> usize {
+        self.glsl_string
+            .find(" //")
+            .unwrap_or(self.glsl_string.len())
+    }
+}
+
+/// Replaces the text of a line, `map` holds the new column of every old column, and of the old end
+fn rewrite(line: &mut GlslLine, text: String, map: &[usize]) {
+    for s in line.segments.iter_mut() {
+        s.columns = map[s.columns.start]..map[s.columns.end];
+    }
+    line.glsl_string = text;
+}
+
+/// `case` and `default` labels, their bodies are indented under them
+fn is_label(glsl: &str) -> bool {
+    glsl.starts_with("case ") || glsl == "default:"
+}
+
+fn pretty(lines: &mut [GlslLine], indent: usize, source_comments: bool) {
+    let mut depth = 0usize;
+    // Depth of every label whose body is still open, it closes at the next label or the end of the switch
+    let mut labels: Vec<usize> = Vec::new();
+    let mut commented = 0;
+    for l in lines.iter_mut() {
+        let structural = matches!(l.glsl_string.as_str(), "{" | "}" | "else" | "break");
+        let label = is_label(&l.glsl_string);
+        let closes_label = label || l.glsl_string.starts_with('}');
+        if closes_label && labels.last() == Some(&depth.saturating_sub(1)) {
+            labels.pop();
+            depth -= 1;
+        }
+        if l.glsl_string.starts_with('}') {
+            depth = depth.saturating_sub(1);
+        }
+        let pad = depth * indent;
+        if l.glsl_string.ends_with('{') {
+            depth += 1;
+        }
+        if label {
+            labels.push(depth);
+            depth += 1;
+        }
+
+        let mut text = " ".repeat(pad) + &l.glsl_string;
+        // Spans only have lines outside of proc macros, and a source line is named once
+        if let (true, false, Some(span)) = (source_comments, structural, l.span) {
+            let line = span.start().line;
+            if line > 0 && line != commented {
+                text += &format!(" // yasl:{}", line);
+                commented = line;
+            }
+        }
+        let map: Vec<usize> = (0..=l.glsl_string.len()).map(|c| c + pad).collect();
+        rewrite(l, text, &map);
+    }
+}
+
+/// Identifiers, keywords and numbers, a `.` in a number is its own token
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Spaces between words, and between `-` `-` or `+` `+` that would become a decrement or increment
+fn needs_space(prev: Option<&u8>, next: Option<&u8>) -> bool {
+    match (prev, next) {
+        (Some(&p), Some(&n)) => (is_word(p) && is_word(n)) || (p == n && (p == b'-' || p == b'+')),
+        _ => false,
+    }
+}
+
+/// Every identifier, keyword and number in the lines
+pub fn words(lines: &[GlslLine]) -> HashSet<String> {
+    let mut out = HashSet::new();
+    for l in lines.iter() {
+        for w in l.glsl_string.split(|c: char| !is_word(c as u8)) {
+            if !w.is_empty() {
+                out.insert(w.to_string());
+            }
+        }
+    }
+    out
+}
+
+/// Renames whole words, members after a `.` and preprocessor directives are left alone
+pub fn rename(lines: &mut [GlslLine], names: &HashMap<String, String>) {
+    if names.is_empty() {
+        return;
+    }
+    for l in lines.iter_mut() {
+        if l.glsl_string.starts_with('#') {
+            continue;
+        }
+        let old = l.glsl_string.as_bytes();
+        let mut text = String::new();
+        let mut map = vec![0; old.len() + 1];
+        let mut i = 0;
+        while i < old.len() {
+            let start = i;
+            map[i] = text.len();
+            if !is_word(old[i]) {
+                text.push(old[i] as char);
+                i += 1;
+                continue;
+            }
+            while i < old.len() && is_word(old[i]) {
+                i += 1;
+            }
+            let word = &l.glsl_string[start..i];
+            map[start..i].iter_mut().for_each(|m| *m = text.len());
+            match names.get(word) {
+                Some(name) if start == 0 || old[start - 1] != b'.' => text.push_str(name),
+                _ => text.push_str(word),
+            }
+        }
+        map[old.len()] = text.len();
+        rewrite(l, text, &map);
+    }
+}
+
+/// Drops indentation and optional spaces
+fn minify(lines: &mut [GlslLine]) {
+    for l in lines.iter_mut() {
+        // Preprocessor directives are whitespace sensitive
+        if l.glsl_string.starts_with('#') {
+            continue;
+        }
+        let old = l.glsl_string.as_bytes();
+        let mut text = String::new();
+        let mut map = vec![0; old.len() + 1];
+        for i in 0..old.len() {
+            map[i] = text.len();
+            if old[i] != b' ' {
+                text.push(old[i] as char);
+            } else if needs_space(text.as_bytes().last(), old.get(i + 1)) {
+                text.push(' ');
+            }
+        }
+        map[old.len()] = text.len();
+        rewrite(l, text, &map);
+    }
+}
@@ -0,0 +1,604 @@
+//! Typed intermediate representation of a shader stage, lowered from the syntax tree after validation.
+//! Names are resolved to symbols and every expression carries its type, nothing refers to syn.
+//! Backends emit code from it, see `ir::glsl`
+use proc_macro2::Span;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+pub use crate::yasl_item::{Interpolation, LayoutKind, ParamKind, Precision, Sampling};
+pub use crate::yasl_program::ShaderStage;
+pub use crate::yasl_type::{YaslScalarType, YaslType, YaslVecType};
+
+pub mod glsl;
+
+pub mod opt;
+
+#[cfg(feature = "visit")]
+pub mod visit;
+
+/// Items of a single shader stage in declaration order, everything a backend needs to emit it
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Module {
+    pub stage: ShaderStage,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Item {
+    Struct(Struct),
+    Enum(Enum),
+    Constant(Constant),
+    Layout(Layout),
+    Shared(Shared),
+    Global(Global),
+    Function(Function),
+}
+
+/// Items of one kind, in declaration order
+macro_rules! items {
+    ($($name: ident, $name_mut: ident: $variant: ident($ty: ident);)*) => {
+        impl Module {$(
+            pub fn $name(&self) -> impl Iterator<Item = &$ty> {
+                self.items.iter().filter_map(|i| match i {
+                    Item::$variant(item) => Some(item),
+                    _ => None,
+                })
+            }
+            pub fn $name_mut(&mut self) -> impl Iterator<Item = &mut $ty> {
+                self.items.iter_mut().filter_map(|i| match i {
+                    Item::$variant(item) => Some(item),
+                    _ => None,
+                })
+            }
+        )*}
+    };
+}
+
+items! {
+    structs, structs_mut: Struct(Struct);
+    enums, enums_mut: Enum(Enum);
+    constants, constants_mut: Constant(Constant);
+    layouts, layouts_mut: Layout(Layout);
+    shared, shared_mut: Shared(Shared);
+    globals, globals_mut: Global(Global);
+    functions, functions_mut: Function(Function);
+}
+
+impl Module {
+    pub fn new(stage: ShaderStage) -> Self {
+        Self {
+            stage,
+            items: Vec::new(),
+        }
+    }
+    pub fn function(&self, symbol: &Symbol) -> Option<&Function> {
+        self.functions().find(|f| f.symbol == *symbol)
+    }
+    pub fn global(&self, symbol: &Symbol) -> Option<&Global> {
+        self.globals().find(|g| g.symbol == *symbol)
+    }
+    /// `fn main`, the GLSL entry point calls it
+    pub fn entry_point(&self) -> Option<&Function> {
+        self.functions().find(|f| f.symbol.name == "main")
+    }
+    /// Every symbol declared in the module, variables, constants, enum variants, functions, parameters and locals
+    pub fn declarations(&self) -> Vec<Symbol> {
+        let mut out: Vec<Symbol> = Vec::new();
+        for i in self.items.iter() {
+            match i {
+                Item::Struct(_) => {}
+                Item::Enum(e) => out.extend(e.variants.iter().map(|v| v.symbol.clone())),
+                Item::Constant(c) => out.push(c.symbol.clone()),
+                Item::Layout(l) => out.push(l.symbol.clone()),
+                Item::Shared(s) => out.push(s.symbol.clone()),
+                Item::Global(g) => out.push(g.symbol.clone()),
+                Item::Function(f) => {
+                    out.push(f.symbol.clone());
+                    out.extend(f.params.iter().map(|p| p.symbol.clone()));
+                    for s in f.body.stmts.iter() {
+                        s.walk(&mut |s| {
+                            if let Stmt::Local { symbol, .. } = s {
+                                out.push(symbol.clone());
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Binding declared in source, `name` is written like in source, `Blend::Alpha` for enum variants.
+/// `shadow` tells apart bindings with the same name, `let a = 1; let a = a + 1;` declares `a` twice
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Symbol {
+    pub name: String,
+    pub shadow: usize,
+}
+
+/// `struct` item, fields keep their YASL names
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Field {
+    pub name: String,
+    pub ty: YaslType,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// Fieldless `enum` item, every variant is an `int` constant
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Variant {
+    pub symbol: Symbol,
+    pub value: i32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// `const` item, its uses are already folded into literals unless it's a specialization constant
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Constant {
+    pub symbol: Symbol,
+    pub ty: YaslType,
+    pub value: Expr,
+    /// `#[specialization(id = N)]`, the value can be overridden when creating a pipeline
+    pub specialization: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// `layout<..>` item, an input, output, buffer or push constant of the stage
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Layout {
+    pub symbol: Symbol,
+    pub ty: YaslType,
+    pub kind: LayoutKind,
+    /// Location, or binding of a buffer
+    pub pos: usize,
+    pub interpolation: Option<Interpolation>,
+    pub sampling: Option<Sampling>,
+    pub precision: Option<Precision>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// `shared` item, a variable of the compute workgroup
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Shared {
+    pub symbol: Symbol,
+    pub ty: YaslType,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// `static` item, a global variable of the stage
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Global {
+    pub symbol: Symbol,
+    pub ty: YaslType,
+    pub value: Expr,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Function {
+    pub symbol: Symbol,
+    pub params: Vec<Param>,
+    pub ret: YaslType,
+    pub body: Block,
+    /// `#[workgroup_size(x, y, z)]` of the compute entry point
+    pub workgroup_size: Option<[u32; 3]>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Param {
+    pub symbol: Symbol,
+    pub ty: YaslType,
+    pub kind: ParamKind,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Stmt {
+    /// `let`, also `const` and `static` declared inside of a function
+    Local {
+        symbol: Symbol,
+        ty: YaslType,
+        constant: bool,
+        value: Option<Expr>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: Span,
+    },
+    /// `place = value`, or `place op= value` when `op` is set
+    Assign {
+        place: Expr,
+        op: Option<BinOp>,
+        value: Expr,
+    },
+    /// Expression evaluated for its side effects, a call
+    Expr(Expr),
+    Return {
+        value: Option<Expr>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: Span,
+    },
+    If {
+        cond: Expr,
+        then: Block,
+        otherwise: Option<Else>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: Span,
+    },
+    /// `match` on integers without ranges
+    Switch {
+        value: Expr,
+        cases: Vec<Case>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        span: Span,
+    },
+    Block(Block),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Else {
+    /// Block, or another `if`
+    pub body: Box<Stmt>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// Arm of a `Switch`, it never falls through
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Case {
+    pub labels: Vec<Label>,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Label {
+    /// None for the default case
+    pub value: Option<Literal>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: YaslType,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ExprKind {
+    Literal(Literal),
+    Var(Symbol),
+    /// Name of the target language written in source, like `vec3` or `glsl::gl_Position`
+    Native(String),
+    /// Variable provided by the pipeline, by its YASL name like `global_invocation_id`
+    BuiltinVar(String),
+    Unary {
+        op: UnOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Callee,
+        args: Vec<Expr>,
+    },
+    /// Conversion to the type of the expression
+    Cast(Box<Expr>),
+    Field {
+        base: Box<Expr>,
+        member: String,
+    },
+    Index {
+        base: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `if c { a } else { b }` used as a value
+    Select {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Callee {
+    Function(Symbol),
+    /// Builtin function by its YASL name, like `length`
+    Builtin(String),
+    Native(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Literal {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Float(f32),
+    Double(f64),
+}
+
+impl Literal {
+    pub fn ty(&self) -> YaslScalarType {
+        match self {
+            Literal::Bool(_) => YaslScalarType::Bool,
+            Literal::Int(_) => YaslScalarType::Int,
+            Literal::UInt(_) => YaslScalarType::UInt,
+            Literal::Float(_) => YaslScalarType::Float32,
+            Literal::Double(_) => YaslScalarType::Float64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum UnOp {
+    /// `-`
+    Neg,
+    /// `!`, bitwise on integers
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    BitXor,
+    BitAnd,
+    BitOr,
+    Shl,
+    Shr,
+    Eq,
+    Lt,
+    Le,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl BinOp {
+    pub fn symbol(&self) -> &'static str {
+        use BinOp::*;
+        match self {
+            Add => "+",
+            Sub => "-",
+            Mul => "*",
+            Div => "/",
+            Rem => "%",
+            And => "&&",
+            Or => "||",
+            BitXor => "^",
+            BitAnd => "&",
+            BitOr => "|",
+            Shl => "<<",
+            Shr => ">>",
+            Eq => "==",
+            Lt => "<",
+            Le => "<=",
+            Ne => "!=",
+            Ge => ">=",
+            Gt => ">",
+        }
+    }
+    /// Produces a bool
+    pub fn is_comparison(&self) -> bool {
+        use BinOp::*;
+        matches!(self, And | Or | Eq | Lt | Le | Ne | Ge | Gt)
+    }
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, ty: YaslType, span: Span) -> Self {
+        Self { kind, ty, span }
+    }
+    pub fn literal(lit: Literal, span: Span) -> Self {
+        Self::new(ExprKind::Literal(lit), YaslType::ScalarType(lit.ty()), span)
+    }
+    pub fn binary(op: BinOp, left: Expr, right: Expr, ty: YaslType) -> Self {
+        let span = left.span;
+        Self::new(
+            ExprKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+            ty,
+            span,
+        )
+    }
+}
+
+impl Expr {
+    /// Direct subexpressions, in evaluation order
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Unary { expr, .. } | ExprKind::Cast(expr) => vec![expr],
+            ExprKind::Binary { left, right, .. } => vec![left, right],
+            ExprKind::Call { args, .. } => args.iter().collect(),
+            ExprKind::Field { base, .. } => vec![base],
+            ExprKind::Index { base, index } => vec![base, index],
+            ExprKind::Select {
+                cond,
+                then,
+                otherwise,
+            } => vec![cond, then, otherwise],
+            ExprKind::Literal(_)
+            | ExprKind::Var(_)
+            | ExprKind::Native(_)
+            | ExprKind::BuiltinVar(_) => Vec::new(),
+        }
+    }
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match &mut self.kind {
+            ExprKind::Unary { expr, .. } | ExprKind::Cast(expr) => vec![expr],
+            ExprKind::Binary { left, right, .. } => vec![left, right],
+            ExprKind::Call { args, .. } => args.iter_mut().collect(),
+            ExprKind::Field { base, .. } => vec![base],
+            ExprKind::Index { base, index } => vec![base, index],
+            ExprKind::Select {
+                cond,
+                then,
+                otherwise,
+            } => vec![cond, then, otherwise],
+            ExprKind::Literal(_)
+            | ExprKind::Var(_)
+            | ExprKind::Native(_)
+            | ExprKind::BuiltinVar(_) => Vec::new(),
+        }
+    }
+    /// Calls `f` on the expression and every expression inside of it
+    pub fn walk(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        for c in self.children() {
+            c.walk(f);
+        }
+    }
+    /// Variable at the root of a place expression, `a` in `a.x[i]`
+    pub fn root(&self) -> Option<&Symbol> {
+        match &self.kind {
+            ExprKind::Var(s) => Some(s),
+            ExprKind::Field { base, .. } | ExprKind::Index { base, .. } => base.root(),
+            _ => None,
+        }
+    }
+}
+
+impl Stmt {
+    /// Expressions of the statement itself, without nested statements
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Local { value, .. } | Stmt::Return { value, .. } => value.iter().collect(),
+            Stmt::Assign { place, value, .. } => vec![place, value],
+            Stmt::Expr(e) => vec![e],
+            Stmt::If { cond, .. } => vec![cond],
+            Stmt::Switch { value, .. } => vec![value],
+            Stmt::Block(_) => Vec::new(),
+        }
+    }
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Local { value, .. } | Stmt::Return { value, .. } => value.iter_mut().collect(),
+            Stmt::Assign { place, value, .. } => vec![place, value],
+            Stmt::Expr(e) => vec![e],
+            Stmt::If { cond, .. } => vec![cond],
+            Stmt::Switch { value, .. } => vec![value],
+            Stmt::Block(_) => Vec::new(),
+        }
+    }
+    /// Statements nested directly in this one
+    pub fn children(&self) -> Vec<&Stmt> {
+        match self {
+            Stmt::If {
+                then, otherwise, ..
+            } => then
+                .stmts
+                .iter()
+                .chain(otherwise.iter().map(|e| &*e.body))
+                .collect(),
+            Stmt::Switch { cases, .. } => cases.iter().map(|c| &*c.body).collect(),
+            Stmt::Block(b) => b.stmts.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+    /// Control never continues after the statement
+    pub fn diverges(&self) -> bool {
+        match self {
+            Stmt::Return { .. } => true,
+            Stmt::Block(b) => b.stmts.iter().any(Stmt::diverges),
+            Stmt::If {
+                then,
+                otherwise: Some(e),
+                ..
+            } => then.stmts.iter().any(Stmt::diverges) && e.body.diverges(),
+            Stmt::Switch { cases, .. } => {
+                let default = cases
+                    .iter()
+                    .any(|c| c.labels.iter().any(|l| l.value.is_none()));
+                default && cases.iter().all(|c| c.body.diverges())
+            }
+            _ => false,
+        }
+    }
+    /// Calls `f` on the statement and every statement nested in it
+    pub fn walk(&self, f: &mut impl FnMut(&Stmt)) {
+        f(self);
+        for c in self.children() {
+            c.walk(f);
+        }
+    }
+}
+
+impl Block {
+    /// Calls `f` on every expression in the block, including subexpressions
+    pub fn walk_exprs(&self, f: &mut impl FnMut(&Expr)) {
+        for s in self.stmts.iter() {
+            s.walk(&mut |s| {
+                for e in s.exprs() {
+                    e.walk(f);
+                }
+            });
+        }
+    }
+}
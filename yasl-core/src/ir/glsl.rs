@@ -0,0 +1,548 @@
+//! GLSL backend, every statement becomes a line mapped back to its YASL source
+use std::collections::HashSet;
+
+use proc_macro2::Span;
+
+use crate::builtins;
+use crate::glsl::{Glsl, GlslFragment, GlslLine, GlslSegment};
+use crate::naming;
+
+use super::*;
+
+/// GLSL names only the shader itself sees, interface blocks and struct fields, which are reflected, keep theirs
+pub fn private_names(module: &Module) -> Vec<String> {
+    let mut names: Vec<String> = module.declarations().iter().map(symbol).collect();
+
+    let mut interface: HashSet<String> = HashSet::new();
+    for l in module.layouts() {
+        interface.insert(format!("{}_block", symbol(&l.symbol)));
+        interface.insert(symbol(&l.symbol));
+    }
+    for s in module.structs() {
+        interface.extend(s.fields.iter().map(|f| naming::glsl_name(&f.name, 0)));
+    }
+    let mut seen = HashSet::new();
+    names.retain(|n| !interface.contains(n) && seen.insert(n.clone()));
+    names
+}
+
+/// Every item in declaration order, followed by the GLSL entry point calling `fn main`
+impl From<&Module> for Glsl {
+    fn from(module: &Module) -> Glsl {
+        let mut elements: Vec<Glsl> = module.items.iter().map(Glsl::from).collect();
+
+        // Errors in the entry point belong to `fn main`
+        let span = module.entry_point().map(|f| f.span);
+        let entry_line = |glsl_string: String, ends_with_semi| {
+            Glsl::Line(GlslLine {
+                span,
+                ends_with_semi,
+                glsl_string,
+                segments: Vec::new(),
+            })
+        };
+        elements.push(Glsl::Fragment(GlslFragment {
+            elements: vec![
+                entry_line("void main()".into(), false),
+                entry_line("{".into(), false),
+                entry_line(format!("{}()", naming::glsl_name("main", 0)), true),
+                entry_line("}".into(), false),
+            ],
+        }));
+
+        Glsl::Fragment(GlslFragment { elements })
+    }
+}
+
+impl From<&Item> for Glsl {
+    fn from(item: &Item) -> Glsl {
+        match item {
+            Item::Struct(s) => s.into(),
+            Item::Enum(e) => e.into(),
+            Item::Constant(c) => c.into(),
+            Item::Layout(l) => l.into(),
+            Item::Shared(s) => s.into(),
+            Item::Global(g) => g.into(),
+            Item::Function(f) => f.into(),
+        }
+    }
+}
+
+/// GLSL name of a YASL binding, see `naming::glsl_name`
+pub fn symbol(symbol: &Symbol) -> String {
+    naming::glsl_name(&symbol.name, symbol.shadow)
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Bool(b) => b.to_string(),
+        Literal::Int(i) => i.to_string(),
+        Literal::UInt(u) => format!("{}u", u),
+        // Debug always keeps the fraction, `1.0` and not `1`
+        Literal::Float(f) => format!("{:?}", f),
+        Literal::Double(d) => format!("{:?}lf", d),
+    }
+}
+
+/// Binding strength of GLSL operators, operands that bind weaker are parenthesized
+fn precedence(expr: &Expr) -> u8 {
+    use BinOp::*;
+    match &expr.kind {
+        ExprKind::Binary { op, .. } => match op {
+            Mul | Div | Rem => 14,
+            Add | Sub => 13,
+            Shl | Shr => 12,
+            Lt | Le | Gt | Ge => 11,
+            Eq | Ne => 10,
+            BitAnd => 9,
+            BitXor => 8,
+            BitOr => 7,
+            And => 6,
+            Or => 4,
+        },
+        ExprKind::Select { .. } => 3,
+        ExprKind::Unary { .. } => 15,
+        // `-1` can't follow another `-`
+        ExprKind::Literal(Literal::Int(i)) if *i < 0 => 15,
+        ExprKind::Literal(Literal::Float(f)) if f.is_sign_negative() => 15,
+        ExprKind::Literal(Literal::Double(d)) if d.is_sign_negative() => 15,
+        _ => 16,
+    }
+}
+
+fn is_vec(expr: &Expr) -> bool {
+    matches!(expr.ty, YaslType::Vec(_))
+}
+
+/// Text of a single line, with the columns each expression was written to
+#[derive(Default)]
+struct Writer {
+    text: String,
+    segments: Vec<GlslSegment>,
+}
+
+impl Writer {
+    fn str(&mut self, s: &str) -> &mut Self {
+        self.text += s;
+        self
+    }
+
+    /// Text written by `write` maps to `span`, segments of nested parts are recorded before it
+    fn spanned(&mut self, span: Span, write: impl FnOnce(&mut Self)) -> &mut Self {
+        let start = self.text.len();
+        write(self);
+        self.segments.push(GlslSegment {
+            columns: start..self.text.len(),
+            span,
+        });
+        self
+    }
+
+    fn operand(&mut self, expr: &Expr, min: u8) -> &mut Self {
+        if precedence(expr) < min {
+            self.str("(").expr(expr).str(")")
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> &mut Self {
+        self.spanned(expr.span, |w| w.expr_kind(expr))
+    }
+
+    fn expr_kind(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(l) => {
+                self.str(&literal(l));
+            }
+            ExprKind::Var(s) => {
+                self.str(&symbol(s));
+            }
+            ExprKind::Native(n) => {
+                self.str(n);
+            }
+            ExprKind::BuiltinVar(name) => {
+                self.str(builtins::var(name).map_or(name, |v| v.glsl));
+            }
+            ExprKind::Unary { op, expr: value } => {
+                let integer = matches!(
+                    value.ty.scalar(),
+                    Some(YaslScalarType::Int | YaslScalarType::UInt)
+                );
+                // `!` on integers is bitwise, on bool vectors it's componentwise
+                match op {
+                    UnOp::Not if integer => self.str("~").operand(value, 16),
+                    UnOp::Not if is_vec(value) => self.str("not(").expr(value).str(")"),
+                    UnOp::Not => self.str("!").operand(value, 16),
+                    UnOp::Neg => self.str("-").operand(value, 16),
+                };
+            }
+            ExprKind::Binary { op, left, right } => {
+                // Vectors are compared componentwise, then reduced
+                if matches!(op, BinOp::Eq | BinOp::Ne) && is_vec(left) {
+                    let reduce = match op {
+                        BinOp::Eq => "all(equal(",
+                        _ => "any(notEqual(",
+                    };
+                    self.str(reduce).expr(left).str(", ").expr(right).str("))");
+                    return;
+                }
+                let p = precedence(expr);
+                self.operand(left, p)
+                    .str(" ")
+                    .str(op.symbol())
+                    .str(" ")
+                    .operand(right, p + 1);
+            }
+            // GLSL array length is an int method
+            ExprKind::Call {
+                callee: Callee::Builtin(name),
+                args,
+            } if name == "len" => {
+                self.str("uint(").operand(&args[0], 16).str(".length())");
+            }
+            ExprKind::Call { callee, args } => {
+                let name = match callee {
+                    Callee::Function(s) => symbol(s),
+                    Callee::Builtin(name) => builtins::function(name)
+                        .map_or_else(|| name.clone(), |b| b.glsl.to_string()),
+                    Callee::Native(n) => n.clone(),
+                };
+                self.str(&name).str("(");
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.str(", ");
+                    }
+                    self.expr(a);
+                }
+                self.str(")");
+            }
+            ExprKind::Cast(value) => {
+                let ty = &expr.ty;
+                self.str(&Glsl::from(ty).to_string())
+                    .str("(")
+                    .expr(value)
+                    .str(")");
+            }
+            ExprKind::Field { base, member } => {
+                self.operand(base, 16)
+                    .str(".")
+                    .str(&naming::glsl_name(member, 0));
+            }
+            ExprKind::Index { base, index } => {
+                self.operand(base, 16).str("[").expr(index).str("]");
+            }
+            ExprKind::Select {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.operand(cond, 4)
+                    .str(" ? ")
+                    .operand(then, 3)
+                    .str(" : ")
+                    .operand(otherwise, 3);
+            }
+        }
+    }
+
+    fn line(self, span: Span, ends_with_semi: bool) -> Glsl {
+        Glsl::Line(GlslLine {
+            span: Some(span),
+            ends_with_semi,
+            glsl_string: self.text,
+            segments: self.segments,
+        })
+    }
+}
+
+pub fn expr(expr: &Expr) -> String {
+    let mut w = Writer::default();
+    w.expr(expr);
+    w.text
+}
+
+/// Line without expressions in it
+fn line(span: Span, glsl_string: String, ends_with_semi: bool) -> Glsl {
+    let mut w = Writer::default();
+    w.str(&glsl_string);
+    w.line(span, ends_with_semi)
+}
+
+impl From<&Block> for Glsl {
+    fn from(block: &Block) -> Glsl {
+        let mut elements = vec![line(block.span, "{".into(), false)];
+        elements.extend(block.stmts.iter().map(Glsl::from));
+        elements.push(line(block.span, "}".into(), false));
+        Glsl::Fragment(GlslFragment { elements })
+    }
+}
+
+impl From<&Stmt> for Glsl {
+    fn from(stmt: &Stmt) -> Glsl {
+        match stmt {
+            Stmt::Local {
+                symbol: s,
+                ty,
+                constant,
+                value,
+                span,
+            } => {
+                let mut w = Writer::default();
+                if *constant {
+                    w.str("const ");
+                }
+                w.str(&Glsl::from(ty).to_string())
+                    .str(" ")
+                    .spanned(*span, |w| {
+                        w.str(&symbol(s));
+                    });
+                if let Some(v) = value {
+                    w.str(" = ").expr(v);
+                }
+                w.line(*span, true)
+            }
+            Stmt::Assign { place, op, value } => {
+                let op = op.map_or("", |op| op.symbol());
+                let mut w = Writer::default();
+                w.expr(place).str(" ").str(op).str("= ").expr(value);
+                w.line(place.span, true)
+            }
+            Stmt::Expr(e) => {
+                let mut w = Writer::default();
+                w.expr(e);
+                w.line(e.span, true)
+            }
+            Stmt::Return { value, span } => {
+                let mut w = Writer::default();
+                w.str("return");
+                if let Some(v) = value {
+                    w.str(" ").expr(v);
+                }
+                w.line(*span, true)
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                let mut w = Writer::default();
+                w.str("if (").expr(cond).str(")");
+                let mut elements = vec![w.line(*span, false), then.into()];
+                let mut next = otherwise.as_ref();
+                while let Some(e) = next.take() {
+                    match &*e.body {
+                        // `else if` continues the chain on the same line
+                        Stmt::If {
+                            cond,
+                            then,
+                            otherwise,
+                            span,
+                        } => {
+                            let mut w = Writer::default();
+                            w.spanned(e.span, |w| {
+                                w.str("else");
+                            })
+                            .str(" if (")
+                            .expr(cond)
+                            .str(")");
+                            elements.extend(vec![w.line(*span, false), then.into()]);
+                            next = otherwise.as_ref();
+                        }
+                        Stmt::Block(b) => {
+                            elements.extend(vec![line(e.span, "else".into(), false), b.into()]);
+                        }
+                        // Single statement gets braces like every other branch
+                        body => elements.extend(vec![
+                            line(e.span, "else".into(), false),
+                            line(e.span, "{".into(), false),
+                            body.into(),
+                            line(e.span, "}".into(), false),
+                        ]),
+                    }
+                }
+                Glsl::Fragment(GlslFragment { elements })
+            }
+            Stmt::Switch { value, cases, span } => {
+                let mut w = Writer::default();
+                w.str("switch (").expr(value).str(")");
+                let mut elements = vec![w.line(*span, false), line(*span, "{".into(), false)];
+                for case in cases.iter() {
+                    for label in case.labels.iter() {
+                        let glsl = match &label.value {
+                            Some(v) => format!("case {}:", literal(v)),
+                            None => "default:".into(),
+                        };
+                        elements.push(line(label.span, glsl, false));
+                    }
+                    elements.push((&*case.body).into());
+                    // Arms never fall through, a `break` after a `return` would be unreachable
+                    if !case.body.diverges() {
+                        let span = case.labels.first().map_or(*span, |l| l.span);
+                        elements.push(line(span, "break".into(), true));
+                    }
+                }
+                elements.push(line(*span, "}".into(), false));
+                Glsl::Fragment(GlslFragment { elements })
+            }
+            Stmt::Block(b) => b.into(),
+        }
+    }
+}
+
+impl From<&Struct> for Glsl {
+    fn from(s: &Struct) -> Glsl {
+        let mut elements = vec![line(
+            s.span,
+            format!(
+                "struct {} {{",
+                Glsl::from(&YaslType::Struct(s.name.clone()))
+            ),
+            false,
+        )];
+        for f in s.fields.iter() {
+            let glsl = format!("{} {}", Glsl::from(&f.ty), naming::glsl_name(&f.name, 0));
+            elements.push(line(f.span, glsl, true));
+        }
+        elements.push(line(s.span, "}".into(), true));
+        Glsl::Fragment(GlslFragment { elements })
+    }
+}
+
+impl From<&Enum> for Glsl {
+    fn from(e: &Enum) -> Glsl {
+        let elements = e
+            .variants
+            .iter()
+            .map(|v| {
+                let glsl = format!("const int {} = {}", symbol(&v.symbol), v.value);
+                line(v.span, glsl, true)
+            })
+            .collect();
+        Glsl::Fragment(GlslFragment { elements })
+    }
+}
+
+impl From<&Constant> for Glsl {
+    fn from(c: &Constant) -> Glsl {
+        let mut w = Writer::default();
+        if let Some(id) = c.specialization {
+            w.str(&format!("layout(constant_id = {}) ", id));
+        }
+        w.str("const ")
+            .str(&Glsl::from(&c.ty).to_string())
+            .str(" ")
+            .spanned(c.span, |w| {
+                w.str(&symbol(&c.symbol));
+            })
+            .str(" = ")
+            .expr(&c.value);
+        w.line(c.span, true)
+    }
+}
+
+impl From<&Layout> for Glsl {
+    fn from(l: &Layout) -> Glsl {
+        let name = symbol(&l.symbol);
+        let glsl = match l.kind {
+            LayoutKind::PushConstant => format!(
+                "layout(push_constant) uniform {}_block {{ {} {}; }}",
+                name,
+                Glsl::from(&l.ty),
+                name
+            ),
+            LayoutKind::Buffer => {
+                let member = match &l.ty {
+                    YaslType::Array(ty, None) => format!("{} {}[]", Glsl::from(&**ty), name),
+                    ty => format!("{} {}", Glsl::from(ty), name),
+                };
+                format!(
+                    "layout(std430, binding={}) buffer {}_block {{ {}; }}",
+                    l.pos, name, member
+                )
+            }
+            LayoutKind::Input | LayoutKind::Output => {
+                let mut qualifiers = String::new();
+                if let Some(i) = &l.interpolation {
+                    qualifiers += &format!("{} ", Glsl::from(i));
+                }
+                if let Some(s) = &l.sampling {
+                    qualifiers += &format!("{} ", Glsl::from(s));
+                }
+                let precision = match &l.precision {
+                    Some(p) => format!("{} ", Glsl::from(p)),
+                    None => String::new(),
+                };
+                format!(
+                    "layout(location={}) {}{} {}{} {}",
+                    l.pos,
+                    qualifiers,
+                    Glsl::from(&l.kind),
+                    precision,
+                    Glsl::from(&l.ty),
+                    name,
+                )
+            }
+        };
+        line(l.span, glsl, true)
+    }
+}
+
+impl From<&Shared> for Glsl {
+    fn from(s: &Shared) -> Glsl {
+        let glsl = format!("shared {} {}", Glsl::from(&s.ty), symbol(&s.symbol));
+        line(s.span, glsl, true)
+    }
+}
+
+impl From<&Global> for Glsl {
+    fn from(g: &Global) -> Glsl {
+        let mut w = Writer::default();
+        w.str(&Glsl::from(&g.ty).to_string())
+            .str(" ")
+            .spanned(g.span, |w| {
+                w.str(&symbol(&g.symbol));
+            })
+            .str(" = ")
+            .expr(&g.value);
+        w.line(g.span, true)
+    }
+}
+
+impl From<&Function> for Glsl {
+    fn from(f: &Function) -> Glsl {
+        let params: Vec<String> = f
+            .params
+            .iter()
+            .map(|p| {
+                let qualifier = match p.kind {
+                    ParamKind::In => "",
+                    ParamKind::InOut => "inout ",
+                    ParamKind::Out => "out ",
+                };
+                format!("{}{} {}", qualifier, Glsl::from(&p.ty), symbol(&p.symbol))
+            })
+            .collect();
+
+        let signature = format!(
+            "{} {}({})",
+            Glsl::from(&f.ret),
+            symbol(&f.symbol),
+            params.join(", ")
+        );
+
+        let mut elements = Vec::new();
+        if let Some([x, y, z]) = f.workgroup_size {
+            let glsl = format!(
+                "layout(local_size_x = {}, local_size_y = {}, local_size_z = {}) in",
+                x, y, z
+            );
+            elements.push(line(f.span, glsl, true));
+        }
+        elements.push(line(f.span, signature, false));
+        elements.push((&f.body).into());
+
+        Glsl::Fragment(GlslFragment { elements })
+    }
+}
@@ -0,0 +1,899 @@
+//! Optimization passes over the IR, enabled with `CompileOptions::opt_level`
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::builtins;
+use crate::options::OptLevel;
+
+use super::*;
+
+/// Functions with at most this many expression nodes are inlined at `O2`
+const INLINE_SIZE: usize = 16;
+/// Calls nested deeper in inlined bodies are kept
+const INLINE_DEPTH: usize = 4;
+
+pub fn optimize(module: &mut Module, level: OptLevel) {
+    if level == OptLevel::O0 {
+        return;
+    }
+    if level >= OptLevel::O2 {
+        inline(module);
+    }
+
+    for g in module.globals_mut() {
+        walk_expr_mut(&mut g.value, &mut |e| {
+            fold_expr(e);
+        });
+    }
+    let params = param_kinds(module);
+    for f in module.functions_mut() {
+        // Each pass can open up more work for the others
+        loop {
+            let mut changed = fold_block(&mut f.body);
+            changed |= remove_unreachable(&mut f.body);
+            changed |= propagate_constants(f, &params);
+            changed |= remove_dead_stores(f, &params);
+            changed |= remove_overwritten(&mut f.body);
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    strip_unused(module);
+}
+
+fn param_kinds(module: &Module) -> HashMap<String, Vec<ParamKind>> {
+    module
+        .functions()
+        .map(|f| {
+            let kinds = f.params.iter().map(|p| p.kind).collect();
+            (glsl::symbol(&f.symbol), kinds)
+        })
+        .collect()
+}
+
+/// Calls to user functions, atomics and builtins without a result are kept even when their value isn't used
+fn has_side_effects(expr: &Expr) -> bool {
+    let mut effects = false;
+    expr.walk(&mut |e| {
+        if let ExprKind::Call { callee, .. } = &e.kind {
+            effects |= match callee {
+                Callee::Function(_) => true,
+                Callee::Builtin(name) => {
+                    builtins::function(name).is_none_or(|b| b.atomic || e.ty == YaslType::Void)
+                }
+                Callee::Native(name) => !pure_native(name),
+            };
+        }
+    });
+    effects
+}
+
+/// GLSL constructors, and builtins that only compute a value, other native calls can write memory like `atomicAdd`
+fn pure_native(name: &str) -> bool {
+    let constructor = ["vec", "ivec", "uvec", "bvec", "dvec", "mat", "dmat"]
+        .iter()
+        .any(|p| {
+            name.strip_prefix(p).is_some_and(|n| {
+                !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit() || b == b'x')
+            })
+        })
+        || ["float", "double", "int", "uint", "bool"].contains(&name);
+    constructor
+        || builtins::glsl_function(name)
+            .is_some_and(|b| !b.atomic && !matches!(b.ret, builtins::BuiltinReturn::Void))
+}
+
+/// Roots of places written by assignments and by `&mut` arguments of calls
+fn written(block: &Block, params: &HashMap<String, Vec<ParamKind>>) -> HashSet<String> {
+    let mut out = HashSet::new();
+    for s in block.stmts.iter() {
+        s.walk(&mut |s| {
+            if let Stmt::Assign { place, .. } = s {
+                out.extend(place.root().map(glsl::symbol));
+            }
+            for e in s.exprs() {
+                e.walk(&mut |e| {
+                    if let ExprKind::Call { callee, args } = &e.kind {
+                        let by_ref: Vec<bool> = match callee {
+                            Callee::Function(f) => params
+                                .get(&glsl::symbol(f))
+                                .map(|p| p.iter().map(|k| *k != ParamKind::In).collect())
+                                .unwrap_or_else(|| vec![true; args.len()]),
+                            Callee::Builtin(name) => {
+                                let atomic = builtins::function(name).is_some_and(|b| b.atomic);
+                                vec![atomic]
+                            }
+                            Callee::Native(name) => vec![!pure_native(name); args.len()],
+                        };
+                        for (a, by_ref) in args.iter().zip(by_ref) {
+                            if by_ref {
+                                out.extend(a.root().map(glsl::symbol));
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+    out
+}
+
+/// Number of declarations of each local, sibling scopes can declare the same symbol
+fn declarations(block: &Block) -> HashMap<String, usize> {
+    let mut out = HashMap::new();
+    for s in block.stmts.iter() {
+        s.walk(&mut |s| {
+            if let Stmt::Local { symbol, .. } = s {
+                *out.entry(glsl::symbol(symbol)).or_insert(0) += 1;
+            }
+        });
+    }
+    out
+}
+
+fn for_each_stmt_mut(block: &mut Block, f: &mut impl FnMut(&mut Stmt)) {
+    for s in block.stmts.iter_mut() {
+        walk_stmt_mut(s, f);
+    }
+}
+
+fn walk_stmt_mut(stmt: &mut Stmt, f: &mut impl FnMut(&mut Stmt)) {
+    f(stmt);
+    match stmt {
+        Stmt::If {
+            then, otherwise, ..
+        } => {
+            for_each_stmt_mut(then, f);
+            if let Some(e) = otherwise {
+                walk_stmt_mut(&mut e.body, f);
+            }
+        }
+        Stmt::Switch { cases, .. } => {
+            for c in cases.iter_mut() {
+                walk_stmt_mut(&mut c.body, f);
+            }
+        }
+        Stmt::Block(b) => for_each_stmt_mut(b, f),
+        _ => {}
+    }
+}
+
+fn walk_expr_mut(expr: &mut Expr, f: &mut impl FnMut(&mut Expr)) {
+    for c in expr.children_mut() {
+        walk_expr_mut(c, f);
+    }
+    f(expr);
+}
+
+fn for_each_expr_mut(block: &mut Block, f: &mut impl FnMut(&mut Expr)) {
+    for_each_stmt_mut(block, &mut |s| {
+        for e in s.exprs_mut() {
+            walk_expr_mut(e, f);
+        }
+    });
+}
+
+// Constant folding
+
+fn fold_unary(op: UnOp, v: Literal) -> Option<Literal> {
+    use Literal::*;
+    Some(match (op, v) {
+        (UnOp::Neg, Int(v)) => Int(v.checked_neg()?),
+        (UnOp::Neg, Float(v)) => Float(-v),
+        (UnOp::Neg, Double(v)) => Double(-v),
+        (UnOp::Not, Bool(v)) => Bool(!v),
+        (UnOp::Not, Int(v)) => Int(!v),
+        (UnOp::Not, UInt(v)) => UInt(!v),
+        _ => return None,
+    })
+}
+
+macro_rules! int_op {
+    ($op: expr, $a: expr, $b: expr, $wrap: path) => {{
+        let (a, b) = ($a, $b);
+        match $op {
+            BinOp::Add => a.checked_add(b).map($wrap),
+            BinOp::Sub => a.checked_sub(b).map($wrap),
+            BinOp::Mul => a.checked_mul(b).map($wrap),
+            BinOp::Div => a.checked_div(b).map($wrap),
+            BinOp::Rem => a.checked_rem(b).map($wrap),
+            BinOp::BitAnd => Some($wrap(a & b)),
+            BinOp::BitOr => Some($wrap(a | b)),
+            BinOp::BitXor => Some($wrap(a ^ b)),
+            op => compare(op, a, b),
+        }
+    }};
+}
+
+macro_rules! float_op {
+    ($op: expr, $a: expr, $b: expr, $wrap: path) => {{
+        let (a, b) = ($a, $b);
+        let v = match $op {
+            BinOp::Add => a + b,
+            BinOp::Sub => a - b,
+            BinOp::Mul => a * b,
+            BinOp::Div => a / b,
+            op => return compare(op, a, b),
+        };
+        // Infinity and NaN have no literal
+        if v.is_finite() {
+            Some($wrap(v))
+        } else {
+            None
+        }
+    }};
+}
+
+fn compare<T: PartialOrd>(op: BinOp, a: T, b: T) -> Option<Literal> {
+    Some(Literal::Bool(match op {
+        BinOp::Eq => a == b,
+        BinOp::Ne => a != b,
+        BinOp::Lt => a < b,
+        BinOp::Le => a <= b,
+        BinOp::Gt => a > b,
+        BinOp::Ge => a >= b,
+        _ => return None,
+    }))
+}
+
+fn fold_binary(op: BinOp, a: Literal, b: Literal) -> Option<Literal> {
+    use Literal::*;
+    let shift = |b: Literal| match b {
+        Int(v) => u32::try_from(v).ok(),
+        UInt(v) => Some(v),
+        _ => None,
+    };
+    match (op, a) {
+        (BinOp::Shl, Int(a)) => return a.checked_shl(shift(b)?).map(Int),
+        (BinOp::Shr, Int(a)) => return a.checked_shr(shift(b)?).map(Int),
+        (BinOp::Shl, UInt(a)) => return a.checked_shl(shift(b)?).map(UInt),
+        (BinOp::Shr, UInt(a)) => return a.checked_shr(shift(b)?).map(UInt),
+        _ => {}
+    }
+    match (a, b) {
+        (Bool(a), Bool(b)) => Some(Bool(match op {
+            BinOp::And => a && b,
+            BinOp::Or => a || b,
+            BinOp::Eq => a == b,
+            BinOp::Ne => a != b,
+            _ => return None,
+        })),
+        (Int(a), Int(b)) => int_op!(op, a, b, Int),
+        (UInt(a), UInt(b)) => int_op!(op, a, b, UInt),
+        (Float(a), Float(b)) => float_op!(op, a, b, Float),
+        (Double(a), Double(b)) => float_op!(op, a, b, Double),
+        _ => None,
+    }
+}
+
+fn fold_cast(v: Literal, ty: &YaslScalarType) -> Option<Literal> {
+    use Literal::*;
+    let v = match v {
+        Bool(b) => b as i32 as f64,
+        Int(i) => i as f64,
+        UInt(u) => u as f64,
+        Float(f) => f as f64,
+        Double(d) => d,
+    };
+    // Out of range conversions are undefined in GLSL, they are left to the driver
+    Some(match ty {
+        YaslScalarType::Bool => Bool(v != 0.0),
+        YaslScalarType::Int if v > i32::MIN as f64 - 1.0 && v < i32::MAX as f64 + 1.0 => {
+            Int(v as i32)
+        }
+        YaslScalarType::UInt if v > -1.0 && v < u32::MAX as f64 + 1.0 => UInt(v as u32),
+        YaslScalarType::Float32 => Float(v as f32),
+        YaslScalarType::Float64 => Double(v),
+        _ => return None,
+    })
+}
+
+fn literal(expr: &Expr) -> Option<Literal> {
+    match expr.kind {
+        ExprKind::Literal(l) => Some(l),
+        _ => None,
+    }
+}
+
+/// Folds the expression when its operands are already folded
+fn fold_expr(expr: &mut Expr) -> bool {
+    let folded = match &mut expr.kind {
+        ExprKind::Unary { op, expr: value } => literal(value).and_then(|v| fold_unary(*op, v)),
+        ExprKind::Binary { op, left, right } => match (literal(left), literal(right)) {
+            (Some(a), Some(b)) => fold_binary(*op, a, b),
+            _ => None,
+        },
+        ExprKind::Cast(value) => match (literal(value), &expr.ty) {
+            (Some(v), YaslType::ScalarType(ty)) => fold_cast(v, ty),
+            _ => None,
+        },
+        ExprKind::Select {
+            cond,
+            then,
+            otherwise,
+        } => {
+            let branch = match literal(cond) {
+                Some(Literal::Bool(true)) => then,
+                Some(Literal::Bool(false)) => otherwise,
+                _ => return false,
+            };
+            let branch = std::mem::replace(
+                &mut **branch,
+                Expr::literal(Literal::Bool(false), expr.span),
+            );
+            *expr = branch;
+            return true;
+        }
+        _ => None,
+    };
+    match folded {
+        Some(l) => {
+            *expr = Expr::literal(l, expr.span);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Folds expressions, and replaces `if` with a constant condition by the branch taken
+fn fold_block(block: &mut Block) -> bool {
+    let mut changed = false;
+    for_each_expr_mut(block, &mut |e| changed |= fold_expr(e));
+
+    for_each_stmt_mut(block, &mut |s| {
+        let taken = match s {
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => match literal(cond) {
+                Some(Literal::Bool(true)) => Some(Stmt::Block(then.clone())),
+                // Branch keeps its own scope, a missing one is an empty block
+                Some(Literal::Bool(false)) => Some(otherwise.take().map_or_else(
+                    || {
+                        Stmt::Block(Block {
+                            stmts: Vec::new(),
+                            span: *span,
+                        })
+                    },
+                    |e| *e.body,
+                )),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(taken) = taken {
+            *s = taken;
+            changed = true;
+        }
+    });
+    changed
+}
+
+// Unreachable code
+
+fn remove_unreachable(block: &mut Block) -> bool {
+    let mut changed = false;
+    for_each_stmt_mut(block, &mut |s| {
+        let blocks: Vec<&mut Block> = match s {
+            Stmt::If { then, .. } => vec![then],
+            Stmt::Block(b) => vec![b],
+            _ => Vec::new(),
+        };
+        for b in blocks {
+            changed |= truncate(b);
+        }
+    });
+    changed | truncate(block)
+}
+
+fn truncate(block: &mut Block) -> bool {
+    match block.stmts.iter().position(Stmt::diverges) {
+        Some(i) if i + 1 < block.stmts.len() => {
+            block.stmts.truncate(i + 1);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Constant propagation and dead stores
+
+/// Locals that are declared once with a literal and never written again are replaced by the literal
+fn propagate_constants(f: &mut Function, params: &HashMap<String, Vec<ParamKind>>) -> bool {
+    let written = written(&f.body, params);
+    let declarations = declarations(&f.body);
+
+    let mut constants: HashMap<String, Literal> = HashMap::new();
+    for s in f.body.stmts.iter() {
+        s.walk(&mut |s| {
+            if let Stmt::Local {
+                symbol,
+                value: Some(value),
+                ..
+            } = s
+            {
+                let name = glsl::symbol(symbol);
+                if let (Some(l), false, Some(1)) = (
+                    literal(value),
+                    written.contains(&name),
+                    declarations.get(&name),
+                ) {
+                    constants.insert(name, l);
+                }
+            }
+        });
+    }
+    if constants.is_empty() {
+        return false;
+    }
+
+    let mut changed = false;
+    for_each_expr_mut(&mut f.body, &mut |e| {
+        if let ExprKind::Var(s) = &e.kind {
+            if let Some(l) = constants.get(&glsl::symbol(s)) {
+                *e = Expr::literal(*l, e.span);
+                changed = true;
+            }
+        }
+    });
+    changed
+}
+
+/// Locals that are never read, with their initializers and assignments to them.
+/// Values with side effects are still evaluated, as expression statements
+fn remove_dead_stores(f: &mut Function, params: &HashMap<String, Vec<ParamKind>>) -> bool {
+    let declarations = declarations(&f.body);
+
+    // Root of an assigned place isn't a read, its indices are
+    let mut read = HashSet::new();
+    let mut note_reads = |e: &Expr| {
+        e.walk(&mut |e| {
+            if let ExprKind::Var(s) = &e.kind {
+                read.insert(glsl::symbol(s));
+            }
+        })
+    };
+    for s in f.body.stmts.iter() {
+        s.walk(&mut |s| match s {
+            Stmt::Assign { place, value, .. } if !has_side_effects(place) => {
+                for c in place.children().into_iter().skip(1) {
+                    note_reads(c);
+                }
+                let mut place = place;
+                while let ExprKind::Field { base, .. } | ExprKind::Index { base, .. } = &place.kind
+                {
+                    place = base;
+                    for c in place.children().into_iter().skip(1) {
+                        note_reads(c);
+                    }
+                }
+                note_reads(value);
+            }
+            s => s.exprs().into_iter().for_each(&mut note_reads),
+        });
+    }
+    // Locals passed as `&mut` arguments are kept, the callee can read them
+    let written = written(&f.body, params);
+    f.body.walk_exprs(&mut |e| {
+        if let ExprKind::Call { args, .. } = &e.kind {
+            for a in args.iter() {
+                read.extend(a.root().map(glsl::symbol).filter(|s| written.contains(s)));
+            }
+        }
+    });
+
+    let dead = |symbol: &Symbol| {
+        let name = glsl::symbol(symbol);
+        declarations.contains_key(&name) && !read.contains(&name)
+    };
+    let remove = |s: &mut Stmt| -> Option<Option<Stmt>> {
+        let value = match s {
+            Stmt::Local { symbol, value, .. } if dead(symbol) => value.take(),
+            Stmt::Assign { place, value, .. } if place.root().is_some_and(dead) => {
+                Some(value.clone())
+            }
+            Stmt::Block(b) if b.stmts.is_empty() => None,
+            _ => return None,
+        };
+        Some(value.filter(has_side_effects).map(Stmt::Expr))
+    };
+
+    let mut changed = false;
+    let mut retain = |stmts: &mut Vec<Stmt>| {
+        let old = std::mem::take(stmts);
+        for mut s in old.into_iter() {
+            match remove(&mut s) {
+                Some(replacement) => {
+                    changed = true;
+                    stmts.extend(replacement);
+                }
+                None => stmts.push(s),
+            }
+        }
+    };
+    retain(&mut f.body.stmts);
+    for_each_stmt_mut(&mut f.body, &mut |s| match s {
+        Stmt::If { then, .. } => retain(&mut then.stmts),
+        Stmt::Block(b) => retain(&mut b.stmts),
+        _ => {}
+    });
+    changed
+}
+
+fn mentions(stmt: &Stmt, name: &str) -> bool {
+    let mut found = false;
+    stmt.walk(&mut |s| {
+        for e in s.exprs() {
+            found |= mentions_expr(e, name);
+        }
+    });
+    found
+}
+
+fn mentions_expr(expr: &Expr, name: &str) -> bool {
+    let mut found = false;
+    expr.walk(&mut |e| {
+        if let ExprKind::Var(s) = &e.kind {
+            found |= glsl::symbol(s) == name;
+        }
+    });
+    found
+}
+
+/// Local assigned as a whole, `x = value` or `let x = value`
+fn stored(stmt: &Stmt) -> Option<(String, &Expr)> {
+    match stmt {
+        Stmt::Local {
+            symbol,
+            value: Some(value),
+            constant: false,
+            ..
+        } => Some((glsl::symbol(symbol), value)),
+        Stmt::Assign {
+            place: Expr {
+                kind: ExprKind::Var(s),
+                ..
+            },
+            op: None,
+            value,
+        } => Some((glsl::symbol(s), value)),
+        _ => None,
+    }
+}
+
+/// Stores to locals that are overwritten before anything reads them, `x = 1.0; x = 2.0;` keeps only the second.
+/// There are no loops, so the next statement that mentions the local is the next one to see it
+fn remove_overwritten(block: &mut Block) -> bool {
+    let declarations = declarations(block);
+    let overwritten = |stmts: &[Stmt], i: usize| -> bool {
+        let name = match stored(&stmts[i]) {
+            Some((name, _)) if declarations.contains_key(&name) => name,
+            _ => return false,
+        };
+        match stmts[i + 1..].iter().find(|s| mentions(s, &name)) {
+            Some(next @ Stmt::Assign { value, .. }) => {
+                stored(next).is_some_and(|(n, _)| n == name) && !mentions_expr(value, &name)
+            }
+            _ => false,
+        }
+    };
+
+    let mut changed = false;
+    let mut retain = |stmts: &mut Vec<Stmt>| {
+        let dead: Vec<bool> = (0..stmts.len()).map(|i| overwritten(stmts, i)).collect();
+        if !dead.contains(&true) {
+            return;
+        }
+        changed = true;
+        let old = std::mem::take(stmts);
+        for (s, dead) in old.into_iter().zip(dead) {
+            if !dead {
+                stmts.push(s);
+                continue;
+            }
+            // Values with side effects are still evaluated, the declaration stays without a value
+            match s {
+                Stmt::Local {
+                    symbol,
+                    ty,
+                    constant,
+                    value,
+                    span,
+                } => {
+                    stmts.extend(value.filter(has_side_effects).map(Stmt::Expr));
+                    stmts.push(Stmt::Local {
+                        symbol,
+                        ty,
+                        constant,
+                        value: None,
+                        span,
+                    });
+                }
+                Stmt::Assign { value, .. } => {
+                    stmts.extend(Some(value).filter(has_side_effects).map(Stmt::Expr))
+                }
+                s => stmts.push(s),
+            }
+        }
+    };
+    retain(&mut block.stmts);
+    for_each_stmt_mut(block, &mut |s| match s {
+        Stmt::If { then, .. } => retain(&mut then.stmts),
+        Stmt::Block(b) => retain(&mut b.stmts),
+        _ => {}
+    });
+    changed
+}
+
+// Inlining
+
+/// `fn f(a: T) -> U { return expr; }`, small and taking everything by value
+fn inlinable(f: &Function) -> Option<&Expr> {
+    if f.symbol.name == "main" || f.params.iter().any(|p| p.kind != ParamKind::In) {
+        return None;
+    }
+    let value = match f.body.stmts.as_slice() {
+        [Stmt::Return {
+            value: Some(value), ..
+        }] => value,
+        _ => return None,
+    };
+    let mut size = 0;
+    let mut recursive = false;
+    value.walk(&mut |e| {
+        size += 1;
+        recursive |=
+            matches!(&e.kind, ExprKind::Call { callee: Callee::Function(s), .. } if *s == f.symbol);
+    });
+    if size <= INLINE_SIZE && !recursive {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Argument that is as cheap to evaluate twice as to store
+fn trivial(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) | ExprKind::Var(_) | ExprKind::Native(_) | ExprKind::BuiltinVar(_) => {
+            true
+        }
+        ExprKind::Field { base, .. } => trivial(base),
+        _ => false,
+    }
+}
+
+/// Replaces calls to small functions by their returned expression, with parameters substituted.
+/// Arguments with side effects would be reordered, and expensive ones evaluated more than once, those calls are kept
+fn inline(module: &mut Module) {
+    let bodies: HashMap<String, (Vec<String>, Expr)> = module
+        .functions()
+        .filter_map(|f| {
+            let value = inlinable(f)?;
+            let params = f.params.iter().map(|p| glsl::symbol(&p.symbol)).collect();
+            Some((glsl::symbol(&f.symbol), (params, value.clone())))
+        })
+        .collect();
+    if bodies.is_empty() {
+        return;
+    }
+
+    let inline_call = |e: &mut Expr| -> bool {
+        let ((params, value), args) = match &e.kind {
+            ExprKind::Call {
+                callee: Callee::Function(s),
+                args,
+            } => match bodies.get(&glsl::symbol(s)) {
+                Some(body) => (body, args),
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        let mut uses = vec![0; params.len()];
+        value.walk(&mut |v| {
+            if let ExprKind::Var(s) = &v.kind {
+                if let Some(i) = params.iter().position(|p| *p == glsl::symbol(s)) {
+                    uses[i] += 1;
+                }
+            }
+        });
+        let safe = args
+            .iter()
+            .zip(uses)
+            .all(|(a, uses)| !has_side_effects(a) && (uses <= 1 || trivial(a)));
+        if !safe {
+            return false;
+        }
+
+        let args = args.clone();
+        let mut value = value.clone();
+        walk_expr_mut(&mut value, &mut |v| {
+            if let ExprKind::Var(s) = &v.kind {
+                if let Some(i) = params.iter().position(|p| *p == glsl::symbol(s)) {
+                    *v = args[i].clone();
+                }
+            }
+        });
+        *e = value;
+        true
+    };
+
+    // Inlined bodies can call other small functions
+    for f in module.functions_mut() {
+        for _ in 0..INLINE_DEPTH {
+            let mut changed = false;
+            for_each_expr_mut(&mut f.body, &mut |e| changed |= inline_call(e));
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+// Unused items
+
+/// Functions not reachable from the entry point, and statics no function uses
+fn strip_unused(module: &mut Module) {
+    let calls = |f: &Function| {
+        let mut out = Vec::new();
+        f.body.walk_exprs(&mut |e| {
+            if let ExprKind::Call {
+                callee: Callee::Function(s),
+                ..
+            } = &e.kind
+            {
+                out.push(s.clone());
+            }
+        });
+        out
+    };
+
+    let main = Symbol {
+        name: "main".into(),
+        shadow: 0,
+    };
+    if module.function(&main).is_none() {
+        return;
+    }
+    let mut reachable = vec![main];
+    let mut i = 0;
+    while i < reachable.len() {
+        if let Some(f) = module.function(&reachable[i]) {
+            for s in calls(f) {
+                if !reachable.contains(&s) {
+                    reachable.push(s);
+                }
+            }
+        }
+        i += 1;
+    }
+    module.items.retain(|i| match i {
+        Item::Function(f) => reachable.contains(&f.symbol),
+        _ => true,
+    });
+
+    let mut used = HashSet::new();
+    let note = |used: &mut HashSet<String>, e: &Expr| {
+        if let ExprKind::Var(s) = &e.kind {
+            used.insert(glsl::symbol(s));
+        }
+    };
+    for f in module.functions() {
+        f.body.walk_exprs(&mut |e| note(&mut used, e));
+    }
+    // Statics can be initialized from other statics
+    let globals: Vec<&Global> = module.globals().collect();
+    for g in globals.into_iter().rev() {
+        if used.contains(&glsl::symbol(&g.symbol)) {
+            g.value.walk(&mut |e| note(&mut used, e));
+        }
+    }
+    module.items.retain(|i| match i {
+        Item::Global(g) => used.contains(&glsl::symbol(&g.symbol)),
+        _ => true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions, OptLevel, ShaderStage};
+
+    const SOURCE: &str = "
+layout<buffer,0> counters: [u32];
+static G: f32 = 2.0;
+static UNUSED: f32 = 3.0;
+
+fn sq(x: f32) -> f32 { x * x }
+fn bump() -> u32 { atomic_add(counters[1], 1) }
+fn never() -> f32 { 1.0 }
+fn split(v: f32, w: &mut f32) { *w = v; }
+
+#[workgroup_size(1)]
+fn main() {
+    let a = 1.0 + 2.0 * 3.0;
+    let b = sq(a) + sq(G);
+    let mut dead = 1.0;
+    dead = 2.0;
+    let old = atomic_add(counters[0], 1);
+    let c = bump();
+    let mut w = 0.0;
+    split(b, &mut w);
+    let n = glsl::atomicAdd(counters[2], 1u32);
+    if false {
+        counters[3] = 1;
+    }
+    counters[4] = b as u32 + w as u32;
+    return;
+    counters[5] = 1;
+}";
+
+    fn optimized(level: OptLevel) -> String {
+        let options = CompileOptions {
+            opt_level: level,
+            stage: Some(ShaderStage::Compute),
+            ..CompileOptions::default()
+        };
+        compile(SOURCE, &options).unwrap().stages[0].glsl.clone()
+    }
+
+    /// Statements of `yasl_main`, without indentation
+    fn main_body(glsl: &str) -> Vec<&str> {
+        glsl.lines()
+            .skip_while(|l| *l != "void yasl_main()")
+            .skip(2)
+            .take_while(|l| *l != "}")
+            .map(str::trim)
+            .collect()
+    }
+
+    #[test]
+    fn o1_folds_and_removes_dead_code() {
+        let glsl = optimized(OptLevel::O1);
+        assert_eq!(
+            main_body(&glsl),
+            [
+                "float b = sq(7.0) + sq(G);",
+                "atomicAdd(counters[0], 1u);",
+                "bump();",
+                "float w = 0.0;",
+                "split(b, w);",
+                "atomicAdd(counters[2], 1u);",
+                "counters[4] = uint(b) + uint(w);",
+                "return;",
+            ]
+        );
+        assert!(!glsl.contains("never") && !glsl.contains("UNUSED"));
+    }
+
+    #[test]
+    fn o2_inlines_small_functions() {
+        let glsl = optimized(OptLevel::O2);
+        assert_eq!(
+            main_body(&glsl),
+            [
+                "float b = 49.0 + G * G;",
+                "atomicAdd(counters[0], 1u);",
+                "atomicAdd(counters[1], 1u);",
+                "float w = 0.0;",
+                "split(b, w);",
+                "atomicAdd(counters[2], 1u);",
+                "counters[4] = uint(b) + uint(w);",
+                "return;",
+            ]
+        );
+        assert!(!glsl.contains("sq(") && !glsl.contains("bump("));
+    }
+
+    #[test]
+    fn o0_keeps_everything() {
+        let glsl = optimized(OptLevel::O0);
+        assert!(glsl.contains("float dead = 1.0;"));
+        assert!(glsl.contains("counters[5] = 1u;"));
+        assert!(glsl.contains("float never()"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Read-only traversal of the IR in the style of `syn::visit`, with the `visit` feature.
+//! Every method defaults to the free function of the same name, which visits the children,
+//! so overriding one and calling the function from it keeps the traversal going:
+//! ```ignore
+//! struct Calls(Vec<String>);
+//! impl Visit for Calls {
+//!     fn visit_expr(&mut self, e: &Expr) {
+//!         if let ExprKind::Call { callee: Callee::Builtin(name), .. } = &e.kind {
+//!             self.0.push(name.clone());
+//!         }
+//!         visit::visit_expr(self, e);
+//!     }
+//! }
+//! ```
+use super::{
+    Block, Callee, Constant, Enum, Expr, ExprKind, Field, Function, Global, Item, Layout, Module,
+    Param, Shared, Stmt, Struct, Symbol, Variant,
+};
+
+pub trait Visit {
+    fn visit_module(&mut self, m: &Module) {
+        visit_module(self, m)
+    }
+    fn visit_item(&mut self, i: &Item) {
+        visit_item(self, i)
+    }
+    fn visit_struct(&mut self, s: &Struct) {
+        visit_struct(self, s)
+    }
+    fn visit_field(&mut self, _f: &Field) {}
+    fn visit_enum(&mut self, e: &Enum) {
+        visit_enum(self, e)
+    }
+    fn visit_variant(&mut self, v: &Variant) {
+        visit_variant(self, v)
+    }
+    fn visit_constant(&mut self, c: &Constant) {
+        visit_constant(self, c)
+    }
+    fn visit_layout(&mut self, l: &Layout) {
+        visit_layout(self, l)
+    }
+    fn visit_shared(&mut self, s: &Shared) {
+        visit_shared(self, s)
+    }
+    fn visit_global(&mut self, g: &Global) {
+        visit_global(self, g)
+    }
+    fn visit_function(&mut self, f: &Function) {
+        visit_function(self, f)
+    }
+    fn visit_param(&mut self, p: &Param) {
+        visit_param(self, p)
+    }
+    fn visit_block(&mut self, b: &Block) {
+        visit_block(self, b)
+    }
+    fn visit_stmt(&mut self, s: &Stmt) {
+        visit_stmt(self, s)
+    }
+    fn visit_expr(&mut self, e: &Expr) {
+        visit_expr(self, e)
+    }
+    /// Every declaration and use of a binding or function
+    fn visit_symbol(&mut self, _s: &Symbol) {}
+}
+
+pub fn visit_module<V: Visit + ?Sized>(v: &mut V, m: &Module) {
+    for i in m.items.iter() {
+        v.visit_item(i);
+    }
+}
+
+pub fn visit_item<V: Visit + ?Sized>(v: &mut V, i: &Item) {
+    match i {
+        Item::Struct(s) => v.visit_struct(s),
+        Item::Enum(e) => v.visit_enum(e),
+        Item::Constant(c) => v.visit_constant(c),
+        Item::Layout(l) => v.visit_layout(l),
+        Item::Shared(s) => v.visit_shared(s),
+        Item::Global(g) => v.visit_global(g),
+        Item::Function(f) => v.visit_function(f),
+    }
+}
+
+pub fn visit_struct<V: Visit + ?Sized>(v: &mut V, s: &Struct) {
+    for f in s.fields.iter() {
+        v.visit_field(f);
+    }
+}
+
+pub fn visit_enum<V: Visit + ?Sized>(v: &mut V, e: &Enum) {
+    for variant in e.variants.iter() {
+        v.visit_variant(variant);
+    }
+}
+
+pub fn visit_variant<V: Visit + ?Sized>(v: &mut V, variant: &Variant) {
+    v.visit_symbol(&variant.symbol);
+}
+
+pub fn visit_constant<V: Visit + ?Sized>(v: &mut V, c: &Constant) {
+    v.visit_symbol(&c.symbol);
+    v.visit_expr(&c.value);
+}
+
+pub fn visit_layout<V: Visit + ?Sized>(v: &mut V, l: &Layout) {
+    v.visit_symbol(&l.symbol);
+}
+
+pub fn visit_shared<V: Visit + ?Sized>(v: &mut V, s: &Shared) {
+    v.visit_symbol(&s.symbol);
+}
+
+pub fn visit_global<V: Visit + ?Sized>(v: &mut V, g: &Global) {
+    v.visit_symbol(&g.symbol);
+    v.visit_expr(&g.value);
+}
+
+pub fn visit_function<V: Visit + ?Sized>(v: &mut V, f: &Function) {
+    v.visit_symbol(&f.symbol);
+    for p in f.params.iter() {
+        v.visit_param(p);
+    }
+    v.visit_block(&f.body);
+}
+
+pub fn visit_param<V: Visit + ?Sized>(v: &mut V, p: &Param) {
+    v.visit_symbol(&p.symbol);
+}
+
+pub fn visit_block<V: Visit + ?Sized>(v: &mut V, b: &Block) {
+    for s in b.stmts.iter() {
+        v.visit_stmt(s);
+    }
+}
+
+pub fn visit_stmt<V: Visit + ?Sized>(v: &mut V, s: &Stmt) {
+    if let Stmt::Local { symbol, .. } = s {
+        v.visit_symbol(symbol);
+    }
+    for e in s.exprs() {
+        v.visit_expr(e);
+    }
+    match s {
+        Stmt::If {
+            then, otherwise, ..
+        } => {
+            v.visit_block(then);
+            if let Some(otherwise) = otherwise {
+                v.visit_stmt(&otherwise.body);
+            }
+        }
+        Stmt::Switch { cases, .. } => {
+            for c in cases.iter() {
+                v.visit_stmt(&c.body);
+            }
+        }
+        Stmt::Block(b) => v.visit_block(b),
+        _ => {}
+    }
+}
+
+pub fn visit_expr<V: Visit + ?Sized>(v: &mut V, e: &Expr) {
+    match &e.kind {
+        ExprKind::Var(s)
+        | ExprKind::Call {
+            callee: Callee::Function(s),
+            ..
+        } => v.visit_symbol(s),
+        _ => {}
+    }
+    for c in e.children() {
+        v.visit_expr(c);
+    }
+}
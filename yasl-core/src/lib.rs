@@ -1,52 +1,217 @@
-use syn::parse::{Parse, ParseStream, Result};
+use std::collections::HashMap;
 
+use proc_macro2::Span;
+use syn::parse::{Parse, ParseStream, Parser, Result};
+
+pub mod builtins;
+mod compile;
+pub mod cpu;
+mod diagnostics;
+mod formatter;
 mod glsl;
-use glsl::{Glsl, GlslLine};
+use glsl::{Glsl, GlslFragment};
+pub use glsl::{GlslLine, GlslSegment};
+
+pub mod ir;
 
+pub mod naming;
+mod options;
+pub mod outline;
+pub mod reflection;
+mod sourcemap;
+#[cfg(feature = "shaderc")]
+mod spirv;
 mod yasl_block;
+mod yasl_const;
 mod yasl_expr;
 mod yasl_file;
 mod yasl_ident;
 mod yasl_item;
+mod yasl_program;
 mod yasl_stmt;
 mod yasl_type;
 
+mod validation;
+
+use yasl_const::Consts;
 use yasl_file::YaslFile;
+use yasl_program::YaslProgram;
+
+pub use compile::{compile, compile_file, parse, CompiledShader, CompiledStage, Output};
+pub use diagnostics::{Diagnostic, Diagnostics, LineColumn};
+pub use formatter::format_source;
+pub use naming::Naming;
+pub use options::{CompileOptions, GlslFormat, GlslTarget, OptLevel};
+pub use outline::Declaration;
+pub use reflection::Reflection;
+pub use yasl_program::{is_program, ShaderStage};
 
 pub struct Shader {
+    pub stage: ShaderStage,
     pub glsl: String,
+    /// One entry for every line of `glsl`
     pub sourcemap: Vec<GlslLine>,
+    pub reflection: Reflection,
+    /// Top level declarations, shared ones included
+    pub declarations: Vec<Declaration>,
+    /// Items the GLSL was emitted from, functions and statics after optimization
+    pub ir: ir::Module,
 }
 
-impl Parse for Shader {
-    fn parse(input: ParseStream) -> Result<Self> {
-        let version = "#version 450\n";
-        let mut out = String::new();
-        out += version;
-
-        let file = YaslFile::parse(input)?;
-        // println!("{:#?}", file);
-
-        let glsl: Glsl = file.into();
-        // println!("{:#?}", glsl);
-        let glsl = if let Glsl::Fragment(f) = glsl {
-            f
-        } else {
-            panic!("Expected Fragment On TopLevel");
-        };
-
-        out += &glsl.to_string();
-
-        out += "\n";
-        out += "void main(){ yasl_main(); }";
+impl Shader {
+    /// Expects file that already passed validation
+    fn new(file: YaslFile, stage: ShaderStage, options: &CompileOptions) -> Result<Self> {
+        let reflection = file.reflect();
+        let declarations = file.declarations();
+        let mut ir = file.lower(stage)?;
+        ir::opt::optimize(&mut ir, options.opt_level);
 
-        let sourcemap = glsl.squash();
+        let header = options.target.header().lines().map(|l| {
+            Glsl::Line(GlslLine {
+                span: None,
+                ends_with_semi: false,
+                glsl_string: l.into(),
+                segments: Vec::new(),
+            })
+        });
+        let mut elements: Vec<Glsl> = header.collect();
+        elements.push((&ir).into());
 
-        println!("{}", out);
+        let mut sourcemap = GlslFragment { elements }.squash();
+        let private = ir::glsl::private_names(&ir);
+        let renames = match (&options.format, &options.naming) {
+            (GlslFormat::Minified, _) => {
+                naming::short_names(&private, &glsl::format::words(&sourcemap))
+            }
+            (_, Naming::Prefixed(prefix)) => {
+                // Private names are all renamed, every other word stays
+                let mut taken = glsl::format::words(&sourcemap);
+                for name in private.iter() {
+                    taken.remove(name);
+                }
+                naming::prefixed_names(&private, prefix, &taken)
+            }
+            (_, Naming::Verbatim) => HashMap::new(),
+        };
+        glsl::format::rename(&mut sourcemap, &renames);
+        glsl::format::format(&mut sourcemap, options.format);
+        let out: String = sourcemap.iter().map(|l| l.to_string()).collect();
 
         Ok(Self {
+            stage,
             glsl: out,
             sourcemap,
+            reflection,
+            declarations,
+            ir,
+        })
+    }
+
+    pub fn parse_with(
+        input: ParseStream,
+        stage: ShaderStage,
+        options: &CompileOptions,
+    ) -> Result<Self> {
+        let mut file = YaslFile::parse(input, Consts::default())?;
+        file.attempt_type_anotation();
+        file.validate(stage, options)?;
+        Self::new(file, stage, options)
+    }
+
+    /// Span of YASL source that produced given (1-based) line of generated GLSL
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        self.sourcemap.get(line.checked_sub(1)?)?.span
+    }
+
+    /// Innermost span of YASL source that produced given (1-based) line and (0-based) column of generated GLSL
+    pub fn span_at(&self, line: usize, column: usize) -> Option<Span> {
+        self.sourcemap.get(line.checked_sub(1)?)?.span_at(column)
+    }
+
+    /// Sourcemap as JSON, `source` is the path of the YASL file written into it
+    pub fn sourcemap_json(&self, source: &str) -> String {
+        sourcemap::json(&self.sourcemap, source)
+    }
+
+    /// Errors from the log of a GLSL compiler, like shaderc or glslang, reported at the YASL code they were generated from
+    pub fn glsl_errors(&self, log: &str) -> syn::Error {
+        let mut errors: Option<syn::Error> = None;
+        for l in log.lines() {
+            let (line, message) = match parse_glsl_error(l) {
+                Some(e) => e,
+                None => continue,
+            };
+            // glslang ends with a summary that has no location of its own
+            if message.ends_with("compilation terminated") {
+                continue;
+            }
+            if let Some(span) = self.line_span(line) {
+                let e = syn::Error::new(span, message);
+                match &mut errors {
+                    Some(errors) => errors.combine(e),
+                    None => errors = Some(e),
+                }
+            }
+        }
+        errors.unwrap_or_else(|| {
+            syn::Error::new(
+                Span::call_site(),
+                format!("GLSL compilation failed:\n{}", log),
+            )
         })
     }
 }
+
+/// Line number and message of a `file:line: message` error, as printed by shaderc and glslang.
+/// glslang prefixes them with `ERROR: `, and file names can contain `:` on windows
+fn parse_glsl_error(l: &str) -> Option<(usize, String)> {
+    let fields: Vec<&str> = l.split(':').collect();
+    for i in 1..fields.len().saturating_sub(1) {
+        if let Ok(line) = fields[i].trim().parse::<usize>() {
+            return Some((line, fields[i + 1..].join(":").trim().to_string()));
+        }
+    }
+    None
+}
+
+/// Vertex shader with default options
+impl Parse for Shader {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Self::parse_with(input, ShaderStage::Vertex, &CompileOptions::default())
+    }
+}
+
+/// Function compiled both as ordinary Rust and into shaders, see `yasl_macro::shared`
+pub fn validate_shared(item: &syn::ItemFn, options: &CompileOptions) -> Result<()> {
+    let tokens = quote::quote!(#item);
+    let parser = |ps: ParseStream| YaslFile::parse(ps, Consts::default());
+    let mut file = parser.parse2(tokens)?;
+    file.attempt_type_anotation();
+    file.validate_shared(options)
+}
+
+/// Shader stages compiled from one source, in pipeline order
+pub struct Program {
+    pub stages: Vec<Shader>,
+}
+
+impl Program {
+    pub fn parse_with(input: ParseStream, options: &CompileOptions) -> Result<Self> {
+        let program = YaslProgram::parse(input)?;
+        program.validate(options)?;
+
+        let stages = program
+            .stages
+            .into_iter()
+            .map(|(stage, file)| Shader::new(file, stage, options))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { stages })
+    }
+}
+
+impl Parse for Program {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Self::parse_with(input, &CompileOptions::default())
+    }
+}
@@ -0,0 +1,259 @@
+//! GLSL names of YASL bindings, names are kept as written unless the target reserves them
+use std::collections::{HashMap, HashSet};
+
+/// Keywords, reserved words, types and builtin functions of GLSL 4.50 and GLSL ES 3.10
+#[rustfmt::skip]
+pub const GLSL_RESERVED: &[&str] = &[
+    // Keywords
+    "attribute", "const", "uniform", "varying", "buffer", "shared", "coherent", "volatile",
+    "restrict", "readonly", "writeonly", "atomic_uint", "layout", "centroid", "flat", "smooth",
+    "noperspective", "patch", "sample", "break", "continue", "do", "for", "while", "switch",
+    "case", "default", "if", "else", "subroutine", "in", "out", "inout", "true", "false",
+    "invariant", "precise", "discard", "return", "lowp", "mediump", "highp", "precision",
+    "struct", "void", "bool", "int", "uint", "float", "double",
+    // Reserved for future use
+    "common", "partition", "active", "asm", "class", "union", "enum", "typedef", "template",
+    "this", "resource", "goto", "inline", "noinline", "public", "static", "extern", "external",
+    "interface", "long", "short", "half", "fixed", "unsigned", "superp", "input", "output",
+    "hvec2", "hvec3", "hvec4", "fvec2", "fvec3", "fvec4", "filter", "sizeof", "cast",
+    "namespace", "using", "sampler3DRect",
+    // Types, samplers and images like `sampler2D` are matched by prefix in `is_reserved`
+    "vec2", "vec3", "vec4", "dvec2", "dvec3", "dvec4", "bvec2", "bvec3", "bvec4",
+    "ivec2", "ivec3", "ivec4", "uvec2", "uvec3", "uvec4",
+    "mat2", "mat3", "mat4", "mat2x2", "mat2x3", "mat2x4", "mat3x2", "mat3x3", "mat3x4",
+    "mat4x2", "mat4x3", "mat4x4", "dmat2", "dmat3", "dmat4", "dmat2x2", "dmat2x3", "dmat2x4",
+    "dmat3x2", "dmat3x3", "dmat3x4", "dmat4x2", "dmat4x3", "dmat4x4",
+    // Builtin functions
+    "radians", "degrees", "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh",
+    "asinh", "acosh", "atanh", "pow", "exp", "log", "exp2", "log2", "sqrt", "inversesqrt",
+    "abs", "sign", "floor", "trunc", "round", "roundEven", "ceil", "fract", "mod", "modf",
+    "min", "max", "clamp", "mix", "step", "smoothstep", "isnan", "isinf", "floatBitsToInt",
+    "floatBitsToUint", "intBitsToFloat", "uintBitsToFloat", "fma", "frexp", "ldexp",
+    "packUnorm2x16", "packSnorm2x16", "packUnorm4x8", "packSnorm4x8", "unpackUnorm2x16",
+    "unpackSnorm2x16", "unpackUnorm4x8", "unpackSnorm4x8", "packHalf2x16", "unpackHalf2x16",
+    "packDouble2x32", "unpackDouble2x32", "length", "distance", "dot", "cross", "normalize",
+    "faceforward", "reflect", "refract", "matrixCompMult", "outerProduct", "transpose",
+    "determinant", "inverse", "lessThan", "lessThanEqual", "greaterThan", "greaterThanEqual",
+    "equal", "notEqual", "any", "all", "not", "uaddCarry", "usubBorrow", "umulExtended",
+    "imulExtended", "bitfieldExtract", "bitfieldInsert", "bitfieldReverse", "bitCount",
+    "findLSB", "findMSB", "textureSize", "textureQueryLod", "textureQueryLevels",
+    "textureSamples", "texture", "textureProj", "textureLod", "textureOffset", "texelFetch",
+    "texelFetchOffset", "textureProjOffset", "textureLodOffset", "textureProjLod",
+    "textureProjLodOffset", "textureGrad", "textureGradOffset", "textureProjGrad",
+    "textureProjGradOffset", "textureGather", "textureGatherOffset", "textureGatherOffsets",
+    "atomicCounterIncrement", "atomicCounterDecrement", "atomicCounter", "atomicAdd",
+    "atomicMin", "atomicMax", "atomicAnd", "atomicOr", "atomicXor", "atomicExchange",
+    "atomicCompSwap", "imageSize", "imageSamples", "imageLoad", "imageStore", "imageAtomicAdd",
+    "imageAtomicMin", "imageAtomicMax", "imageAtomicAnd", "imageAtomicOr", "imageAtomicXor",
+    "imageAtomicExchange", "imageAtomicCompSwap", "dFdx", "dFdy", "dFdxFine", "dFdyFine",
+    "dFdxCoarse", "dFdyCoarse", "fwidth", "fwidthFine", "fwidthCoarse", "interpolateAtCentroid",
+    "interpolateAtSample", "interpolateAtOffset", "EmitStreamVertex", "EndStreamPrimitive",
+    "EmitVertex", "EndPrimitive", "barrier", "memoryBarrier", "memoryBarrierAtomicCounter",
+    "memoryBarrierBuffer", "memoryBarrierShared", "memoryBarrierImage", "groupMemoryBarrier",
+    // The GLSL entry point calls the YASL one
+    "main",
+];
+
+/// Prefix of mangled names, names written in YASL never start with it
+pub const MANGLE_PREFIX: &str = "yasl_";
+
+/// `a_1` is left for shadowing bindings, see `glsl_name`
+fn is_shadow_name(name: &str) -> bool {
+    match name.rfind('_') {
+        Some(i) => name.len() > i + 1 && name[i + 1..].bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+pub fn is_reserved(name: &str) -> bool {
+    name.starts_with("gl_")
+        || is_shadow_name(name)
+        || name.starts_with(MANGLE_PREFIX)
+        || name.contains("__")
+        || [
+            "sampler", "isampler", "usampler", "image", "iimage", "uimage", "texture",
+        ]
+        .iter()
+        .any(|p| {
+            let rest = name.strip_prefix(p).unwrap_or("");
+            rest.starts_with(|c: char| c.is_ascii_digit() || c.is_ascii_uppercase())
+        })
+        || GLSL_RESERVED.contains(&name)
+}
+
+/// `Blend::Alpha` is `Blend_Alpha`, reserved names are prefixed with `yasl_`,
+/// and shadowing bindings get a `_N` suffix, written names that end like that are reserved
+pub fn glsl_name(name: &str, shadow: usize) -> String {
+    let name = name.replace("::", "_");
+    let mut out = if is_reserved(&name) {
+        format!("{}{}", MANGLE_PREFIX, name)
+    } else {
+        name
+    };
+    if shadow > 0 {
+        out += &format!("_{}", shadow);
+    }
+    out
+}
+
+/// How names of private bindings are written into GLSL, interface names like inputs and buffers never change
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Naming {
+    /// Names as written in YASL, mangled only when GLSL reserves them
+    #[default]
+    Verbatim,
+    /// Every name gets the prefix, for GLSL that is combined with hand written code
+    Prefixed(String),
+}
+
+/// New names for `names`, in order, skipping every name in `taken`
+pub fn short_names(names: &[String], taken: &HashSet<String>) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let mut n = 0;
+    for name in names.iter() {
+        if out.contains_key(name) {
+            continue;
+        }
+        let short = loop {
+            let short = short_name(n);
+            n += 1;
+            if !taken.contains(&short) && !is_reserved(&short) {
+                break short;
+            }
+        };
+        out.insert(name.clone(), short);
+    }
+    out
+}
+
+/// `y0`, `y1`, .. `yz`, `y10`
+fn short_name(mut n: usize) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((n % 36) as u32, 36).unwrap_or('0'));
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    std::iter::once('y')
+        .chain(digits.into_iter().rev())
+        .collect()
+}
+
+/// `prefix` in front of every name, names mangled with `yasl_` swap it for `prefix`,
+/// unless that gives the new name of another binding or a name in `taken`
+pub fn prefixed_names(
+    names: &[String],
+    prefix: &str,
+    taken: &HashSet<String>,
+) -> HashMap<String, String> {
+    let mut used = taken.clone();
+    let mut out = HashMap::new();
+    // Names that aren't mangled always get the plain prefix, `a_1` is `fx_a_1` even when there is a `yasl_a_1`
+    let (plain, mangled): (Vec<_>, Vec<_>) = names
+        .iter()
+        .partition(|name| !name.starts_with(MANGLE_PREFIX));
+    for name in plain.into_iter().chain(mangled) {
+        if out.contains_key(name) {
+            continue;
+        }
+        let base = name.strip_prefix(MANGLE_PREFIX).unwrap_or(name);
+        let new = vec![format!("{}{}", prefix, base), format!("{}{}", prefix, name)]
+            .into_iter()
+            .chain((1..).map(|n| format!("{}{}_{}", prefix, name, n)))
+            .find(|n| !used.contains(n))
+            .unwrap_or_default();
+        used.insert(new.clone());
+        out.insert(name.clone(), new);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, CompileOptions};
+
+    fn prefixed(source: &str) -> String {
+        let options = CompileOptions {
+            naming: Naming::Prefixed("fx_".into()),
+            ..Default::default()
+        };
+        compile(source, &options).unwrap().stages[0].glsl.clone()
+    }
+
+    #[test]
+    fn prefixed_names_stay_unique() {
+        let names = ["yasl_a_1".to_string(), "a_1".into(), "a".into()];
+        let renames = prefixed_names(&names, "fx_", &HashSet::new());
+        assert_eq!(renames["a"], "fx_a");
+        assert_eq!(renames["a_1"], "fx_a_1");
+        assert_eq!(renames["yasl_a_1"], "fx_yasl_a_1");
+
+        let taken = ["fx_b".to_string()].iter().cloned().collect();
+        let renames = prefixed_names(&["b".into(), "yasl_b".into()], "fx_", &taken);
+        assert_eq!(renames["b"], "fx_b_1");
+        assert_eq!(renames["yasl_b"], "fx_yasl_b");
+    }
+
+    #[test]
+    fn prefixed_function_and_shadowed_local() {
+        let glsl = prefixed(
+            "fn a_1() -> f32 { 2.0 }\nfn main() { let a = 1.0; let a = a; let b = a_1(); }",
+        );
+        assert!(glsl.contains("float fx_yasl_a_1()"), "{}", glsl);
+        assert!(glsl.contains("float fx_a_1 = fx_a;"), "{}", glsl);
+        assert!(glsl.contains("float fx_b = fx_yasl_a_1();"), "{}", glsl);
+        assert!(glsl.contains("void main()"), "{}", glsl);
+    }
+
+    fn minified(source: &str) -> String {
+        let options = CompileOptions {
+            format: crate::GlslFormat::Minified,
+            ..Default::default()
+        };
+        compile(source, &options).unwrap().stages[0].glsl.clone()
+    }
+
+    #[test]
+    fn short_names_skip_taken() {
+        let taken = ["y0".to_string()].iter().cloned().collect();
+        let names = ["a".to_string(), "b".into(), "a".into()];
+        let renames = short_names(&names, &taken);
+        assert_eq!(renames["a"], "y1");
+        assert_eq!(renames["b"], "y2");
+        assert_eq!(renames.len(), 2);
+    }
+
+    #[test]
+    fn minified_keeps_interface_names() {
+        let glsl = minified(
+            "struct Light { power: f32 }
+layout<input,0> pos: vec2<f32>;
+layout<output,0> y0: f32;
+layout<push_constant> pc: Light;
+fn scale(value: f32) -> f32 { value * pc.power }
+fn main() {
+    let amount = scale(pos.x);
+    let amount = amount + 1.0;
+    y0 = amount;
+}",
+        );
+        assert!(glsl.contains("struct Light{\nfloat power;"), "{}", glsl);
+        assert!(glsl.contains("in vec2 pos;"), "{}", glsl);
+        assert!(glsl.contains("out float y0;"), "{}", glsl);
+        assert!(glsl.contains("uniform pc_block{Light pc;};"), "{}", glsl);
+        assert!(
+            glsl.contains("float y1(float y2)\n{\nreturn y2*pc.power;"),
+            "{}",
+            glsl
+        );
+        assert!(
+            glsl.contains("float y4=y1(pos.x);\nfloat y5=y4+1.0;\ny0=y5;"),
+            "{}",
+            glsl
+        );
+        assert!(glsl.contains("void main()\n{\ny3();\n}"), "{}", glsl);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::naming::Naming;
+use crate::yasl_program::ShaderStage;
+
+/// GLSL dialect emitted by the compiler
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GlslTarget {
+    /// `#version 450`, Vulkan and desktop GL
+    #[default]
+    Glsl450,
+    /// `#version 310 es`, mobile and web
+    GlslEs310,
+}
+
+impl GlslTarget {
+    pub fn is_es(&self) -> bool {
+        *self == GlslTarget::GlslEs310
+    }
+    pub fn header(&self) -> &'static str {
+        match self {
+            GlslTarget::Glsl450 => "#version 450\n",
+            GlslTarget::GlslEs310 => {
+                "#version 310 es\nprecision highp float;\nprecision highp int;\n"
+            }
+        }
+    }
+}
+
+/// How much the IR is optimized before emitting code, also passed on to the SPIR-V compiler
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum OptLevel {
+    /// Code is emitted as written
+    #[default]
+    O0,
+    /// Strips unused functions and statics, dead stores and unreachable code, propagates constants
+    O1,
+    /// Also inlines small functions
+    O2,
+}
+
+impl std::str::FromStr for OptLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "O0" | "0" => Ok(OptLevel::O0),
+            "O1" | "1" => Ok(OptLevel::O1),
+            "O2" | "2" => Ok(OptLevel::O2),
+            _ => Err(format!(
+                "Unknown optimization level {}, expected O0, O1 or O2",
+                s
+            )),
+        }
+    }
+}
+
+/// Layout of the emitted GLSL, every statement stays on its own line so the sourcemap keeps working
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlslFormat {
+    /// Blocks indented by `indent` spaces, `source_comments` end statements with the YASL line they came from, `// yasl:12`
+    Pretty {
+        indent: usize,
+        source_comments: bool,
+    },
+    /// No indentation or optional spaces, and short identifiers, for shipping
+    Minified,
+}
+
+impl Default for GlslFormat {
+    fn default() -> Self {
+        GlslFormat::Pretty {
+            indent: 4,
+            source_comments: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub target: GlslTarget,
+    pub opt_level: OptLevel,
+    pub format: GlslFormat,
+    /// Ignored by `GlslFormat::Minified`, which shortens every private name
+    pub naming: Naming,
+    /// Stage of sources without stage blocks in `compile` and `compile_file`, vertex or the one in the file name when None.
+    /// Macros pick the stage themselves
+    pub stage: Option<ShaderStage>,
+}
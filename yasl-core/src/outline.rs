@@ -0,0 +1,30 @@
+//! Top level declarations of a shader with their source spans, for editor tooling
+use proc_macro2::Span;
+
+use crate::yasl_type::YaslType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclarationKind {
+    Function,
+    Static,
+    Const,
+    /// `layout<..>` input, output, buffer or push constant
+    Layout,
+    Shared,
+    Struct,
+    Enum,
+    EnumVariant,
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    /// Name as written in YASL, `Blend::Alpha` for enum variants
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// Span of the name
+    pub span: Span,
+    /// Type of variables, constants and enum variants
+    pub ty: Option<YaslType>,
+    /// Written like in source, `fn add(a: f32, b: f32) -> f32` or `layout<input, 0> pos: vec2<f32>`
+    pub detail: String,
+}
@@ -0,0 +1,83 @@
+pub use crate::yasl_const::ConstValue;
+pub use crate::yasl_item::{Interpolation, Precision, Sampling};
+
+/// Stage input or output declared with `layout<input,_>` or `layout<output,_>`
+#[derive(Debug, Clone)]
+pub struct LayoutReflection {
+    /// Name as written in YASL
+    pub name: String,
+    pub location: usize,
+    /// GLSL type name
+    pub ty: String,
+    pub interpolation: Option<Interpolation>,
+    pub sampling: Option<Sampling>,
+    pub precision: Option<Precision>,
+}
+
+/// Shader storage buffer declared with `layout<buffer,_>`
+#[derive(Debug, Clone)]
+pub struct BufferReflection {
+    pub name: String,
+    pub binding: usize,
+    /// GLSL type name
+    pub ty: String,
+}
+
+/// Struct member with its std430 offset
+#[derive(Debug, Clone)]
+pub struct MemberReflection {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+    /// GLSL type name
+    pub ty: String,
+    /// Rust type with the same size
+    pub rust_ty: String,
+}
+
+/// Struct declared in the shader, laid out with std430 rules
+#[derive(Debug, Clone)]
+pub struct StructReflection {
+    pub name: String,
+    pub size: usize,
+    pub members: Vec<MemberReflection>,
+}
+
+/// Fieldless enum, variants with their discriminants
+#[derive(Debug, Clone)]
+pub struct EnumReflection {
+    pub name: String,
+    pub variants: Vec<(String, i32)>,
+}
+
+/// Push constant block declared with `layout<push_constant>`
+#[derive(Debug, Clone)]
+pub struct PushConstantReflection {
+    pub name: String,
+    pub struct_name: String,
+    pub size: usize,
+}
+
+/// Constant declared with `#[specialization(id = N)]`
+#[derive(Debug, Clone)]
+pub struct SpecializationReflection {
+    pub name: String,
+    pub id: u32,
+    /// GLSL type name
+    pub ty: String,
+    /// Value used when pipeline doesn't override it
+    pub default: ConstValue,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Reflection {
+    pub inputs: Vec<LayoutReflection>,
+    pub outputs: Vec<LayoutReflection>,
+    pub buffers: Vec<BufferReflection>,
+    pub structs: Vec<StructReflection>,
+    pub enums: Vec<EnumReflection>,
+    pub push_constant: Option<PushConstantReflection>,
+    pub specialization_constants: Vec<SpecializationReflection>,
+    /// Only present in compute stage
+    pub workgroup_size: Option<[u32; 3]>,
+}
@@ -0,0 +1,66 @@
+//! JSON export of the mapping from generated GLSL back to YASL source
+use proc_macro2::Span;
+
+use crate::glsl::GlslLine;
+
+/// `[start_line, start_column, end_line, end_column]`, lines are 1-based and columns 0-based like in `proc_macro2`
+fn range(span: Span) -> String {
+    let (start, end) = (span.start(), span.end());
+    format!(
+        "[{}, {}, {}, {}]",
+        start.line, start.column, end.line, end.column
+    )
+}
+
+fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+/// Every line of the GLSL that has a source span, with the spans of its segments:
+/// ```json
+/// { "version": 1, "source": "main.yasl", "lines": [
+///     { "line": 5, "source": [3, 4, 3, 20], "segments": [{ "columns": [6, 12], "source": [3, 8, 3, 14] }] }
+/// ] }
+/// ```
+pub fn json(lines: &[GlslLine], source: &str) -> String {
+    let mut entries = Vec::new();
+    for (i, l) in lines.iter().enumerate() {
+        let span = match l.span {
+            Some(span) => span,
+            None => continue,
+        };
+        let segments: Vec<String> = l
+            .segments
+            .iter()
+            .map(|s| {
+                format!(
+                    "{{ \"columns\": [{}, {}], \"source\": {} }}",
+                    s.columns.start,
+                    s.columns.end,
+                    range(s.span)
+                )
+            })
+            .collect();
+        entries.push(format!(
+            "    {{ \"line\": {}, \"source\": {}, \"segments\": [{}] }}",
+            i + 1,
+            range(span),
+            segments.join(", ")
+        ));
+    }
+    format!(
+        "{{\n  \"version\": 1,\n  \"source\": {},\n  \"lines\": [\n{}\n  ]\n}}\n",
+        string(source),
+        entries.join(",\n")
+    )
+}
@@ -0,0 +1,38 @@
+//! GLSL to SPIR-V with shaderc, behind the `shaderc` feature
+use syn::Result;
+
+use crate::{CompileOptions, OptLevel, Shader, ShaderStage};
+
+impl Shader {
+    /// SPIR-V words of the generated GLSL, GLSL errors are reported at the YASL code like in `glsl_errors`
+    pub fn spirv(&self, options: &CompileOptions) -> Result<Vec<u32>> {
+        let kind = match self.stage {
+            ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderStage::TessControl => shaderc::ShaderKind::TessControl,
+            ShaderStage::TessEval => shaderc::ShaderKind::TessEvaluation,
+            ShaderStage::Geometry => shaderc::ShaderKind::Geometry,
+            ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+            ShaderStage::Compute => shaderc::ShaderKind::Compute,
+        };
+
+        let mut compiler = shaderc::Compiler::new().unwrap();
+        let mut shaderc_options = shaderc::CompileOptions::new().unwrap();
+        shaderc_options.set_optimization_level(match options.opt_level {
+            OptLevel::O0 => shaderc::OptimizationLevel::Zero,
+            OptLevel::O1 => shaderc::OptimizationLevel::Size,
+            OptLevel::O2 => shaderc::OptimizationLevel::Performance,
+        });
+
+        match compiler.compile_into_spirv(
+            &self.glsl,
+            kind,
+            "shader.glsl",
+            "main",
+            Some(&shaderc_options),
+        ) {
+            Ok(sprv) => Ok(sprv.as_binary().to_vec()),
+            Err(shaderc::Error::CompilationError(_n, log)) => Err(self.glsl_errors(&log)),
+            Err(e) => Err(self.glsl_errors(&format!("{:?}", e))),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use proc_macro2::Span;
+use syn::{Error, Result};
+
+use crate::options::CompileOptions;
+use crate::yasl_expr::YaslExprLineScope;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_item::ParamKind;
+use crate::yasl_program::ShaderStage;
+use crate::yasl_type::{std430::StructFields, Typed, YaslScalarType, YaslType};
+
+/// State passed down while validating function bodies
+#[derive(Clone)]
+pub struct ValidationContext<'a> {
+    pub stage: ShaderStage,
+    pub options: &'a CompileOptions,
+    /// Inside of a branch that might be taken only by some invocations
+    pub non_uniform: bool,
+    /// Buffers and shared variables, the only memory atomics can operate on
+    pub atomic_targets: HashSet<String>,
+    /// Structs declared in the stage
+    pub structs: StructFields,
+    /// Variants of every enum declared in the stage
+    pub enums: HashMap<String, Vec<(String, i32)>>,
+    /// Variables that can't be assigned to, with a description used in errors
+    pub immutable: HashMap<String, &'static str>,
+    /// `&mut` parameters of the function being validated, the only values `*` works on
+    pub references: HashSet<String>,
+    /// Parameters of every function declared so far
+    pub functions: HashMap<String, Vec<(ParamKind, YaslType)>>,
+    /// Return type of the function being validated
+    pub returns: YaslType,
+    /// Functions that call `barrier()`, directly or through other functions, they need uniform control flow too
+    pub uniform_functions: HashSet<String>,
+    /// Set when something that needs uniform control flow is called, see `YaslFile::validate`
+    pub calls_uniform_only: Rc<Cell<bool>>,
+    /// Validating a `#[shared]` function, which is compiled as Rust against `crate::cpu` too
+    pub shared: bool,
+}
+
+impl<'a> ValidationContext<'a> {
+    pub fn new(stage: ShaderStage, options: &'a CompileOptions) -> Self {
+        Self {
+            stage,
+            options,
+            non_uniform: false,
+            atomic_targets: HashSet::new(),
+            structs: StructFields::new(),
+            enums: HashMap::new(),
+            immutable: HashMap::new(),
+            references: HashSet::new(),
+            functions: HashMap::new(),
+            returns: YaslType::Void,
+            uniform_functions: HashSet::new(),
+            calls_uniform_only: Rc::new(Cell::new(false)),
+            shared: false,
+        }
+    }
+
+    /// Context for a branch guarded by a condition
+    pub fn branch(&self, uniform_cond: bool) -> Self {
+        let mut ctx = self.clone();
+        ctx.non_uniform |= !uniform_cond;
+        ctx
+    }
+
+    pub fn check_stage(&self, name: &str, stages: &[ShaderStage], span: Span) -> Result<()> {
+        if stages.contains(&self.stage) {
+            Ok(())
+        } else {
+            let stages: Vec<&str> = stages.iter().map(|s| s.name()).collect();
+            Err(Error::new(
+                span,
+                format!(
+                    "{} is not available in {} stage, only in: {}",
+                    name,
+                    self.stage.name(),
+                    stages.join(", ")
+                ),
+            ))
+        }
+    }
+
+    /// Declaration shadows previous ones with the same name,
+    /// `immutable` describes what is declared, for example "input"
+    pub fn declare(&mut self, ident: &YaslIdent, immutable: Option<&'static str>) {
+        match immutable {
+            Some(what) => self.immutable.insert(ident.to_string(), what),
+            None => self.immutable.remove(&ident.to_string()),
+        };
+    }
+
+    /// Target of an assignment or `&mut` argument
+    pub fn check_mutable(&self, place: &YaslExprLineScope) -> Result<()> {
+        let root = match place.root_ident() {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        match self.immutable.get(&root.to_string()) {
+            Some(what) => {
+                let hint = match *what {
+                    "local" => ", declare it with `let mut`",
+                    "static" => ", declare it with `static mut`",
+                    _ => "",
+                };
+                Err(Error::new(
+                    place.span(),
+                    format!("Cannot assign to {} `{}`{}", what, root.name(), hint),
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Value assigned, passed or returned where `expected` is required,
+    /// values of unknown type, like `glsl::` calls, take the expected type
+    pub fn check_value(&self, expected: &YaslType, value: &YaslExprLineScope) -> Result<()> {
+        match value.get_type() {
+            Some(ty) if ty != *expected => Err(Error::new(
+                value.span(),
+                format!(
+                    "Mismatched types, expected {}, found {}",
+                    expected.name(),
+                    ty.name()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Every struct referenced by the type has to be declared
+    pub fn check_type(&self, ty: &YaslType, span: Span) -> Result<()> {
+        match ty {
+            YaslType::Array(ty, _) => self.check_type(ty, span),
+            YaslType::Struct(name) if !self.structs.contains_key(name) => {
+                Err(Error::new(span, format!("Unknown struct {}", name)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Struct that contains itself, directly or through other structs
+    pub fn is_recursive(&self, name: &str) -> bool {
+        fn contains(ctx: &ValidationContext, ty: &YaslType, name: &str, depth: usize) -> bool {
+            match ty {
+                YaslType::Array(ty, _) => contains(ctx, ty, name, depth),
+                YaslType::Struct(s) if s == name => true,
+                YaslType::Struct(s) if depth < ctx.structs.len() => {
+                    ctx.structs.get(s).map(|fields| {
+                        fields
+                            .iter()
+                            .any(|(_, ty)| contains(ctx, ty, name, depth + 1))
+                    }) == Some(true)
+                }
+                _ => false,
+            }
+        }
+        self.structs
+            .get(name)
+            .map(|fields| fields.iter().any(|(_, ty)| contains(self, ty, name, 0)))
+            == Some(true)
+    }
+
+    pub fn check_atomic_target(
+        &self,
+        name: &str,
+        target: Option<&YaslExprLineScope>,
+        span: Span,
+    ) -> Result<()> {
+        let target = if let Some(t) = target {
+            t
+        } else {
+            return Err(Error::new(span, format!("{} expects arguments", name)));
+        };
+
+        let in_memory = target
+            .root_ident()
+            .map(|i| self.atomic_targets.contains(&i.to_string()))
+            == Some(true);
+
+        if !in_memory {
+            return Err(Error::new(
+                target.span(),
+                format!("{} can only operate on buffer or shared variables", name),
+            ));
+        }
+
+        let ty = target.get_type();
+        if !matches!(
+            ty,
+            Some(YaslType::ScalarType(YaslScalarType::Int))
+                | Some(YaslType::ScalarType(YaslScalarType::UInt))
+        ) {
+            return Err(Error::new(
+                target.span(),
+                format!("{} can only operate on i32 or u32", name),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Type of an operand, operators are never applied to values of unknown type
+pub fn operand_type(expr: &YaslExprLineScope) -> Result<YaslType> {
+    expr.get_type().ok_or_else(|| {
+        Error::new(
+            expr.span(),
+            "Type of the operand is unknown, cast it with `as`",
+        )
+    })
+}
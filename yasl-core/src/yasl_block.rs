@@ -1,14 +1,13 @@
-use crate::glsl::{Glsl, GlslFragment, GlslLine};
+use crate::ir;
+use proc_macro2::{Delimiter, Group, Span, TokenStream};
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
 };
-use syn::{spanned::Spanned, Block, Error, ExprBlock, Result};
+use syn::{Block, Error, ExprBlock, Result};
 
 use crate::{
-    yasl_ident::YaslIdent,
-    yasl_stmt::YaslStmt,
-    yasl_type::{Typed, YaslType},
+    validation::ValidationContext, yasl_ident::YaslIdent, yasl_stmt::YaslStmt, yasl_type::YaslType,
 };
 
 #[derive(Debug)]
@@ -17,38 +16,74 @@ pub struct YaslBlock {
     stmts: Vec<YaslStmt>,
 }
 impl YaslBlock {
-    pub fn attempt_type_anotation(&mut self, global_idents: &HashMap<String, YaslType>) {
+    pub fn attempt_type_anotation(&mut self, global_idents: &HashMap<String, YaslIdent>) {
         let mut idents = global_idents.clone();
 
+        // Locals are visible only after their own initializer
         for stmt in self.stmts.iter_mut() {
+            stmt.attempt_type_anotation(&idents);
             for ident in stmt.update_idents() {
-                idents.insert(ident.to_string(), ident.get_type().unwrap().clone());
+                idents.insert(ident.key(), ident);
+            }
+        }
+    }
+    /// Type of the value assigned by the last statement, see `YaslExprFunctionScope::assigned_type`
+    pub fn assigned_type(&self) -> Option<YaslType> {
+        self.stmts.iter().rev().find_map(|s| match s {
+            YaslStmt::Expr(e) => e.assigned_type(),
+            _ => None,
+        })
+    }
+    pub fn fixed_assigned_type(&self) -> Option<YaslType> {
+        self.stmts.iter().rev().find_map(|s| match s {
+            YaslStmt::Expr(e) => e.fixed_assigned_type(),
+            _ => None,
+        })
+    }
+    pub fn set_assigned_type(&mut self, target: &YaslIdent, ty: &YaslType) {
+        if let Some(YaslStmt::Expr(e)) = self.stmts.last_mut() {
+            e.set_assigned_type(target, ty);
+        }
+    }
+    /// Some statement returns on every path, statements after it are never reached
+    pub fn always_returns(&self) -> bool {
+        self.stmts.iter().any(|s| s.always_returns())
+    }
+    /// Span of the closing brace, the end of the block
+    pub fn close_span(&self) -> Span {
+        let mut group = Group::new(Delimiter::Brace, TokenStream::new());
+        group.set_span(self.brace_token.span);
+        group.span_close()
+    }
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        self.stmts
+            .iter()
+            .any(|s| s.returns_non_uniformly(non_uniform))
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        let mut ctx = ctx.clone();
+        for stmt in self.stmts.iter() {
+            stmt.validate(&ctx)?;
+            stmt.declare(&mut ctx);
+            // Invocations that returned don't reach the statements after it
+            if stmt.returns_non_uniformly(false) {
+                ctx.non_uniform = true;
             }
-            stmt.attempt_type_anotation(&idents);
         }
+        Ok(())
     }
 }
-impl From<&YaslBlock> for Glsl {
-    fn from(block: &YaslBlock) -> Glsl {
-        let mut elements = Vec::new();
-
-        elements.push(Glsl::Line(GlslLine {
-            span: Some(block.brace_token.span),
-            ends_with_semi: false,
-            glsl_string: "{".into(),
-        }));
-
+impl TryFrom<&YaslBlock> for ir::Block {
+    type Error = Error;
+    fn try_from(block: &YaslBlock) -> Result<ir::Block> {
+        let mut stmts = Vec::new();
         for s in block.stmts.iter() {
-            elements.push(s.into());
+            s.lower(&mut stmts)?;
         }
-
-        elements.push(Glsl::Line(GlslLine {
-            span: Some(block.brace_token.span),
-            ends_with_semi: false,
-            glsl_string: "}".into(),
-        }));
-
-        Glsl::Fragment(GlslFragment { elements })
+        Ok(ir::Block {
+            stmts,
+            span: block.brace_token.span,
+        })
     }
 }
 
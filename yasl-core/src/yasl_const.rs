@@ -0,0 +1,570 @@
+//! Compile time evaluation of `const` items and constant expressions
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+
+use proc_macro2::Span;
+use quote::quote;
+use syn::visit_mut::{self, VisitMut};
+use syn::{punctuated::Punctuated, spanned::Spanned, Error, Expr, Result, Token};
+
+use crate::glsl::Glsl;
+use crate::yasl_expr::YaslExprLineScope;
+use crate::yasl_item::parse_specialization;
+use crate::yasl_type::{YaslScalarType, YaslType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Int(i32),
+    UInt(u32),
+    Float32(f32),
+    Float64(f64),
+    Bool(bool),
+}
+
+impl ConstValue {
+    pub fn scalar_type(&self) -> YaslScalarType {
+        match self {
+            ConstValue::Int(_) => YaslScalarType::Int,
+            ConstValue::UInt(_) => YaslScalarType::UInt,
+            ConstValue::Float32(_) => YaslScalarType::Float32,
+            ConstValue::Float64(_) => YaslScalarType::Float64,
+            ConstValue::Bool(_) => YaslScalarType::Bool,
+        }
+    }
+
+    /// Conversion done by `as`
+    /// Follows Rust `as`, integers wrap, floats saturate when cast to integers and NaN becomes 0
+    pub fn cast(self, ty: &YaslScalarType, span: Span) -> Result<Self> {
+        use ConstValue::*;
+        Ok(match (self, ty) {
+            (_, YaslScalarType::Bool) => {
+                return Err(Error::new(span, "Casting to bool is not supported"));
+            }
+            (Int(v), YaslScalarType::Int) => Int(v),
+            (Int(v), YaslScalarType::UInt) => UInt(v as u32),
+            (Int(v), YaslScalarType::Float32) => Float32(v as f32),
+            (Int(v), YaslScalarType::Float64) => Float64(v as f64),
+            (UInt(v), YaslScalarType::Int) => Int(v as i32),
+            (UInt(v), YaslScalarType::UInt) => UInt(v),
+            (UInt(v), YaslScalarType::Float32) => Float32(v as f32),
+            (UInt(v), YaslScalarType::Float64) => Float64(v as f64),
+            (Float32(v), YaslScalarType::Int) => Int(v as i32),
+            (Float32(v), YaslScalarType::UInt) => UInt(v as u32),
+            (Float32(v), YaslScalarType::Float32) => Float32(v),
+            (Float32(v), YaslScalarType::Float64) => Float64(v as f64),
+            (Float64(v), YaslScalarType::Int) => Int(v as i32),
+            (Float64(v), YaslScalarType::UInt) => UInt(v as u32),
+            (Float64(v), YaslScalarType::Float32) => Float32(v as f32),
+            (Float64(v), YaslScalarType::Float64) => Float64(v),
+            (Bool(v), YaslScalarType::Int) => Int(v as i32),
+            (Bool(v), YaslScalarType::UInt) => UInt(v as u32),
+            (Bool(v), YaslScalarType::Float32) => Float32(v as u8 as f32),
+            (Bool(v), YaslScalarType::Float64) => Float64(v as u8 as f64),
+        })
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            ConstValue::Int(v) if v >= 0 => Some(v as usize),
+            ConstValue::UInt(v) => Some(v as usize),
+            _ => None,
+        }
+    }
+
+    /// Literal expression with the same value, unsuffixed ints and floats take their type from context
+    pub fn to_expr(self, suffixed: bool, span: Span) -> Result<Expr> {
+        use syn::{Lit, LitBool, LitFloat, LitInt};
+
+        let (negative, lit) = match self {
+            ConstValue::Int(v) => (
+                v < 0,
+                Lit::Int(LitInt::new(
+                    &format!("{}{}", v.unsigned_abs(), if suffixed { "i32" } else { "" }),
+                    span,
+                )),
+            ),
+            ConstValue::UInt(v) => (false, Lit::Int(LitInt::new(&format!("{}u32", v), span))),
+            ConstValue::Float32(v) if v.is_finite() => (
+                v.is_sign_negative(),
+                Lit::Float(LitFloat::new(
+                    &format!("{:?}{}", v.abs(), if suffixed { "f32" } else { "" }),
+                    span,
+                )),
+            ),
+            ConstValue::Float64(v) if v.is_finite() => (
+                v.is_sign_negative(),
+                Lit::Float(LitFloat::new(&format!("{:?}f64", v.abs()), span)),
+            ),
+            ConstValue::Float32(_) | ConstValue::Float64(_) => {
+                return Err(Error::new(span, "Constant expression is not finite"));
+            }
+            ConstValue::Bool(value) => (false, Lit::Bool(LitBool { value, span })),
+        };
+
+        let lit = Expr::Lit(syn::ExprLit {
+            attrs: Vec::new(),
+            lit,
+        });
+        Ok(if negative {
+            Expr::Unary(syn::ExprUnary {
+                attrs: Vec::new(),
+                op: syn::UnOp::Neg(Token![-](span)),
+                expr: Box::new(lit),
+            })
+        } else {
+            lit
+        })
+    }
+}
+
+/// Value of a constant expression
+/// It's untyped when only built from unsuffixed literals, like a literal it then takes its type from context
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Folded {
+    pub value: ConstValue,
+    pub untyped: bool,
+}
+
+impl Folded {
+    pub fn to_expr(self, span: Span) -> Result<Expr> {
+        self.value.to_expr(!self.untyped, span)
+    }
+}
+
+/// Values of `const` items declared so far, inlined into every expression that uses them
+#[derive(Debug, Clone, Default)]
+pub struct Consts {
+    values: HashMap<String, ConstValue>,
+    /// Names of declared enums, their variants are stored in `values` as `Enum::Variant`
+    enums: HashSet<String>,
+    /// Names of declared functions, they hide builtins of the same name
+    functions: HashSet<String>,
+}
+
+impl Consts {
+    pub fn get(&self, name: &str) -> Option<ConstValue> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn is_function(&self, name: &str) -> bool {
+        self.functions.contains(name)
+    }
+
+    /// Value of a constant expression, None when expression is not constant
+    pub fn eval(&self, expr: &Expr) -> Result<Option<ConstValue>> {
+        Ok(self.fold(expr, None)?.map(|f| f.value))
+    }
+
+    /// Like `eval`, unsuffixed literals get the expected type when they can have it
+    pub fn fold(&self, expr: &Expr, expected: Option<&YaslScalarType>) -> Result<Option<Folded>> {
+        match YaslExprLineScope::try_from(expr.clone()) {
+            Ok(e) => e.fold(self, expected),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Folds every constant expression of the item, and registers it when it's a `const`
+    pub fn fold_item(&mut self, item: &mut syn::Item) -> Result<()> {
+        let mut folder = Folder {
+            consts: self,
+            error: None,
+        };
+        folder.visit_item_mut(item);
+        if let Some(e) = folder.error {
+            return Err(e);
+        }
+
+        if let syn::Item::Const(c) = item {
+            self.fold_const(c)?;
+        }
+
+        if let syn::Item::Enum(e) = item {
+            self.fold_enum(e)?;
+        }
+
+        if let syn::Item::Fn(f) = item {
+            self.functions.insert(f.sig.ident.to_string());
+        }
+        Ok(())
+    }
+
+    /// Folds the value of a `const` and registers it for the expressions after it
+    fn fold_const(&mut self, c: &mut syn::ItemConst) -> Result<()> {
+        let ty: YaslType = (*c.ty).clone().try_into()?;
+        let scalar = match ty {
+            YaslType::ScalarType(s) => s,
+            _ => {
+                return Err(Error::new(
+                    c.ty.span(),
+                    "Only scalar constants are supported",
+                ))
+            }
+        };
+        let value = match self.fold(&c.expr, Some(&scalar))? {
+            Some(v) if v.value.scalar_type() == scalar => v.value,
+            Some(v) => {
+                return Err(Error::new(
+                    c.expr.span(),
+                    format!(
+                        "Expected {}, found {}",
+                        Glsl::from(&scalar),
+                        Glsl::from(&v.value.scalar_type())
+                    ),
+                ))
+            }
+            None => return Err(self.not_constant(&c.expr)),
+        };
+        *c.expr = value.to_expr(true, c.expr.span())?;
+
+        // Specialization constants are only known when pipeline is created
+        if parse_specialization(&c.attrs)?.is_none() {
+            self.values.insert(c.ident.to_string(), value);
+        }
+        Ok(())
+    }
+
+    /// Error for an expression that isn't constant, naming the first value that isn't a known constant
+    fn not_constant(&self, expr: &Expr) -> Error {
+        let mut finder = UnknownFinder {
+            consts: self,
+            found: None,
+        };
+        finder.visit_expr_mut(&mut expr.clone());
+        match finder.found {
+            Some(i) => Error::new(
+                i.span(),
+                format!(
+                    "`{}` is not a constant, constants can only use constants declared above them",
+                    i
+                ),
+            ),
+            None => Error::new(expr.span(), "Expected constant expression"),
+        }
+    }
+
+    /// Discriminants are written out, variants without one follow the previous variant
+    fn fold_enum(&mut self, e: &mut syn::ItemEnum) -> Result<()> {
+        let mut next = Some(0);
+        for v in e.variants.iter_mut() {
+            let span = v.ident.span();
+            if !matches!(v.fields, syn::Fields::Unit) {
+                return Err(Error::new(
+                    v.fields.span(),
+                    "Only fieldless enum variants are supported",
+                ));
+            }
+            let value = match &v.discriminant {
+                Some((_, expr)) => match self.eval(expr)? {
+                    Some(ConstValue::Int(d)) => d,
+                    Some(d) => {
+                        let found = Glsl::from(&d.scalar_type());
+                        return Err(Error::new(
+                            expr.span(),
+                            format!("Expected int discriminant, found {}", found),
+                        ));
+                    }
+                    None => return Err(self.not_constant(expr)),
+                },
+                None => next.ok_or_else(|| Error::new(span, "Enum discriminant overflows"))?,
+            };
+            next = value.checked_add(1);
+
+            let value = ConstValue::Int(value);
+            v.discriminant = Some((Token![=](span), value.to_expr(false, span)?));
+            self.values
+                .insert(format!("{}::{}", e.ident, v.ident), value);
+        }
+        self.enums.insert(e.ident.to_string());
+        Ok(())
+    }
+
+    pub fn fold_type(&self, ty: &mut syn::Type) -> Result<()> {
+        let mut folder = Folder {
+            consts: self,
+            error: None,
+        };
+        folder.visit_type_mut(ty);
+        folder.error.map_or(Ok(()), Err)
+    }
+}
+
+struct Folder<'a> {
+    consts: &'a Consts,
+    error: Option<Error>,
+}
+
+impl Folder<'_> {
+    fn fail(&mut self, e: Error) {
+        if self.error.is_none() {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl VisitMut for Folder<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        // Operands are folded first, so parentheses around them can be dropped
+        visit_mut::visit_expr_mut(self, expr);
+
+        if let Expr::Paren(p) = expr {
+            if let Ok(Some(_)) = self.consts.eval(&p.expr) {
+                *expr = (*p.expr).clone();
+            }
+        }
+
+        // Literals are left as written, blocks have to stay blocks to remain valid branches
+        if let Expr::Lit(_) | Expr::Block(_) = expr {
+            return;
+        }
+        // Enum variants keep their type
+        if let Expr::Path(p) = expr {
+            if p.path.segments.len() > 1 {
+                return;
+            }
+        }
+        match self.consts.fold(expr, None) {
+            Ok(Some(v)) => match v.to_expr(expr.span()) {
+                Ok(folded) => *expr = folded,
+                Err(e) => self.fail(e),
+            },
+            Ok(None) => {}
+            Err(e) => self.fail(e),
+        }
+    }
+
+    /// Value of a const is folded knowing its type, by `fold_const`
+    fn visit_item_const_mut(&mut self, c: &mut syn::ItemConst) {
+        for attr in c.attrs.iter_mut() {
+            self.visit_attribute_mut(attr);
+        }
+        self.visit_type_mut(&mut c.ty);
+    }
+
+    /// Constants declared in a block are only visible to the statements after them
+    fn visit_block_mut(&mut self, block: &mut syn::Block) {
+        let mut scope = self.consts.clone();
+        for stmt in block.stmts.iter_mut() {
+            let mut folder = Folder {
+                consts: &scope,
+                error: None,
+            };
+            folder.visit_stmt_mut(stmt);
+            if let Some(e) = folder.error {
+                self.fail(e);
+                return;
+            }
+            if let syn::Stmt::Item(syn::Item::Const(c)) = stmt {
+                let result = match parse_specialization(&c.attrs) {
+                    Ok(Some((span, _))) => Err(Error::new(
+                        span,
+                        "Specialization constants have to be declared outside of functions",
+                    )),
+                    Ok(None) => scope.fold_const(c),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    self.fail(e);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn visit_expr_if_mut(&mut self, i: &mut syn::ExprIf) {
+        self.visit_expr_mut(&mut i.cond);
+        self.visit_block_mut(&mut i.then_branch);
+        // `else if` can't be replaced with a literal
+        if let Some((_, e)) = &mut i.else_branch {
+            visit_mut::visit_expr_mut(self, e);
+        }
+    }
+
+    /// Enum types are marked as `enum::Name`, which can't be written in source
+    fn visit_type_mut(&mut self, ty: &mut syn::Type) {
+        visit_mut::visit_type_mut(self, ty);
+
+        if let syn::Type::Path(p) = ty {
+            let name = match p.path.get_ident() {
+                Some(i) if p.qself.is_none() && self.consts.enums.contains(&i.to_string()) => {
+                    i.clone()
+                }
+                _ => return,
+            };
+            let marker = syn::Ident::new("enum", name.span());
+            p.path.segments = vec![syn::PathSegment::from(marker), name.into()]
+                .into_iter()
+                .collect();
+        }
+    }
+
+    /// Constants used as match patterns become literal patterns
+    fn visit_pat_mut(&mut self, pat: &mut syn::Pat) {
+        visit_mut::visit_pat_mut(self, pat);
+
+        let name = match pat {
+            syn::Pat::Ident(i) if i.subpat.is_none() => i.ident.to_string(),
+            syn::Pat::Path(p) => p
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::"),
+            _ => return,
+        };
+        if let Some(v) = self.consts.get(&name) {
+            match v.to_expr(false, pat.span()) {
+                Ok(expr) => {
+                    *pat = syn::Pat::Lit(syn::PatLit {
+                        attrs: Vec::new(),
+                        expr: Box::new(expr),
+                    })
+                }
+                Err(e) => self.fail(e),
+            }
+        }
+    }
+
+    /// `#[workgroup_size(X, Y, Z)]` can use constants too
+    fn visit_attribute_mut(&mut self, attr: &mut syn::Attribute) {
+        if !attr.path.is_ident("workgroup_size") {
+            return;
+        }
+        let dims = attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated);
+        match dims {
+            Ok(mut dims) => {
+                for d in dims.iter_mut() {
+                    self.visit_expr_mut(d);
+                }
+                attr.tokens = quote!((#dims));
+            }
+            Err(e) => self.fail(e),
+        }
+    }
+}
+
+/// First path that isn't a known constant, function names of calls are skipped
+struct UnknownFinder<'a> {
+    consts: &'a Consts,
+    found: Option<syn::Ident>,
+}
+
+impl VisitMut for UnknownFinder<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if self.found.is_some() {
+            return;
+        }
+        match expr {
+            Expr::Path(p) => {
+                let name = p
+                    .path
+                    .segments
+                    .iter()
+                    .map(|s| s.ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("::");
+                if self.consts.get(&name).is_none() {
+                    self.found = p.path.segments.last().map(|s| s.ident.clone());
+                }
+            }
+            Expr::Call(c) => {
+                for a in c.args.iter_mut() {
+                    self.visit_expr_mut(a);
+                }
+            }
+            e => visit_mut::visit_expr_mut(self, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// GLSL declaration of constant `X` declared by `items`, or the first error
+    fn fold(items: &str) -> Result<String, String> {
+        let source = format!("{}\nfn main() {{}}", items);
+        let output = compile(&source, &CompileOptions::default())
+            .map_err(|d| d.errors[0].message.clone())?;
+        let glsl = &output.stages[0].glsl;
+        let line = glsl.lines().find(|l| l.contains(" X = ")).unwrap_or("");
+        Ok(line.trim().to_string())
+    }
+
+    #[test]
+    fn folds_expressions() {
+        assert_eq!(
+            fold("const X: i32 = 1 << 4 | 3;"),
+            Ok("const int X = 19;".into())
+        );
+        assert_eq!(
+            fold("const X: f32 = (2.0 * 3.0 + 1.0) / 2.0;"),
+            Ok("const float X = 3.5;".into())
+        );
+        assert_eq!(
+            fold("const A: i32 = 19;\nconst X: bool = A > 10 && !(A < 1);"),
+            Ok("const bool X = true;".into())
+        );
+        assert_eq!(
+            fold("const X: u32 = if 1 > 2 { 7 } else { 8 };"),
+            Ok("const uint X = 8u;".into())
+        );
+        assert_eq!(
+            fold("const X: i32 = max(-3, 2).abs();"),
+            Ok("const int X = 2;".into())
+        );
+    }
+
+    #[test]
+    fn casts_follow_rust() {
+        let casts = [
+            ("-1.0 as u32", "uint", "0u"),
+            ("1.9 as u32", "uint", "1u"),
+            ("3000000000.0 as i32", "int", "2147483647"),
+            ("(0.0 / 0.0) as i32", "int", "0"),
+            ("-1 as u32", "uint", "4294967295u"),
+            ("4294967295u32 as i32", "int", "-1"),
+            ("16777217 as f32", "float", "16777216.0"),
+        ];
+        for (cast, ty, value) in casts.iter() {
+            let rust_ty = match *ty {
+                "uint" => "u32",
+                "int" => "i32",
+                _ => "f32",
+            };
+            assert_eq!(
+                fold(&format!("const X: {} = {};", rust_ty, cast)),
+                Ok(format!("const {} X = {};", ty, value)),
+                "{}",
+                cast
+            );
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let errors = [
+            ("const X: u32 = 0 - 1;", "Constant expression overflows"),
+            (
+                "const X: i32 = 2147483647 + 1;",
+                "Constant expression overflows",
+            ),
+            ("const X: i32 = 1 << 32;", "Constant expression overflows"),
+            (
+                "const X: i32 = 1 / 0;",
+                "Division by zero in constant expression",
+            ),
+            (
+                "const K: i32 = 1;\nconst X: u32 = K;",
+                "Expected uint, found int",
+            ),
+            (
+                "const X: u32 = if true { 1 } else { 2.0 };",
+                "`if` and `else` have different types, uint and float",
+            ),
+        ];
+        for (items, message) in errors.iter() {
+            assert_eq!(fold(items), Err(message.to_string()), "{}", items);
+        }
+    }
+}
@@ -3,11 +3,12 @@ use std::{
     convert::{TryFrom, TryInto},
 };
 
+use proc_macro2::Span;
 use syn::{spanned::Spanned, Error, Result};
 
 use syn::Expr;
 
-use crate::glsl::{Glsl, GlslLine};
+use crate::ir;
 use crate::yasl_ident::YaslIdent;
 
 mod binary;
@@ -32,6 +33,7 @@ mod expr_if;
 use expr_if::YaslExprIf;
 
 mod expr_return;
+pub use expr_return::return_target;
 use expr_return::YaslExprReturn;
 
 mod unary;
@@ -40,9 +42,30 @@ use unary::YaslExprUnary;
 mod field;
 use field::YaslExprField;
 
+mod index;
+use index::YaslExprIndex;
+
+mod eval;
+
+mod paren;
+use paren::YaslExprParen;
+
+mod ternary;
+use ternary::YaslExprTernary;
+
+mod expr_match;
+use expr_match::YaslExprMatch;
+
+pub mod tail;
+
+mod builtin_var;
+use builtin_var::YaslExprBuiltinVar;
+
 use crate::{
+    builtins,
+    validation::ValidationContext,
     yasl_block::YaslBlock,
-    yasl_type::{Typed, YaslType},
+    yasl_type::{Typed, YaslScalarType, YaslType},
 };
 /// Scope used in var init
 /// For example `let a = 5 + call();`
@@ -55,26 +78,201 @@ pub enum YaslExprLineScope {
     Ident(YaslIdent),
     Unary(YaslExprUnary),
     Field(YaslExprField),
+    Index(YaslExprIndex),
+    BuiltinVar(YaslExprBuiltinVar),
+    Paren(YaslExprParen),
+    Ternary(YaslExprTernary),
 }
 
 impl YaslExprLineScope {
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
+    pub fn span(&self) -> Span {
+        use YaslExprLineScope::*;
+        match self {
+            Lit(l) => l.span(),
+            Binary(b) => b.span(),
+            Call(c) => c.span(),
+            Cast(c) => c.span(),
+            Ident(i) => i.span(),
+            Unary(u) => u.span(),
+            Field(f) => f.span(),
+            Index(i) => i.span(),
+            BuiltinVar(b) => b.span(),
+            Paren(p) => p.span(),
+            Ternary(t) => t.span(),
+        }
+    }
+
+    /// Identifier at the root of a place expression, `a` in `a.x[0]`
+    pub fn root_ident(&self) -> Option<&YaslIdent> {
+        use YaslExprLineScope::*;
+        match self {
+            Ident(i) => Some(i),
+            Field(f) => f.base().root_ident(),
+            Index(i) => i.base().root_ident(),
+            Paren(p) => p.expr().root_ident(),
+            Unary(u) if u.is_deref() => u.expr().root_ident(),
+            _ => None,
+        }
+    }
+
+    /// Type doesn't change with `infer_type`
+    pub fn has_fixed_type(&self) -> bool {
+        use YaslExprLineScope::*;
+        match self {
+            Lit(l) => l.is_suffixed(),
+            Binary(b) if binary::is_arithmetic(b.op()) => {
+                b.left().has_fixed_type() || b.right().has_fixed_type()
+            }
+            Binary(b) if binary::is_shift(b.op()) => b.left().has_fixed_type(),
+            Unary(u) => u.expr().has_fixed_type(),
+            Paren(p) => p.expr().has_fixed_type(),
+            Ternary(t) => t.then_expr().has_fixed_type() || t.else_expr().has_fixed_type(),
+            _ => true,
+        }
+    }
+
+    /// Expression can be assigned to
+    pub fn is_place(&self) -> bool {
+        use YaslExprLineScope::*;
+        match self {
+            Ident(_) => true,
+            Field(f) => f.base().is_place(),
+            Index(i) => i.base().is_place(),
+            Paren(p) => p.expr().is_place(),
+            Unary(u) if u.is_deref() => u.expr().is_place(),
+            _ => false,
+        }
+    }
+
+    /// Value is the same for every invocation in a workgroup.
+    /// Locals are assumed to be non-uniform
+    pub fn is_uniform(&self) -> bool {
+        use YaslExprLineScope::*;
+        match self {
+            Lit(_) => true,
+            BuiltinVar(b) => b.var().uniform,
+            Binary(b) => b.left().is_uniform() && b.right().is_uniform(),
+            Unary(u) => u.expr().is_uniform(),
+            Cast(c) => c.expr().is_uniform(),
+            Field(f) => f.base().is_uniform(),
+            Index(i) => i.base().is_uniform() && i.index().is_uniform(),
+            Paren(p) => p.expr().is_uniform(),
+            Ternary(t) => {
+                t.cond().is_uniform() && t.then_expr().is_uniform() && t.else_expr().is_uniform()
+            }
+            Ident(_) | Call(_) => false,
+        }
+    }
+
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
         use YaslExprLineScope::*;
         match self {
             Ident(i) => {
-                if let Some(ty) = idents.get(&i.to_string()) {
-                    i.set_type(ty.clone());
+                if let Some(binding) = idents.get(&i.key()) {
+                    i.resolve(binding);
                 }
             }
-            Call(c) => {
-                let c = c.get_mut_ident();
-                if let Some(ty) = idents.get(&c.to_string()) {
-                    c.set_type(ty.clone());
-                }
+            Call(c) => c.attempt_type_anotation(idents),
+            Binary(b) => {
+                b.left_mut().attempt_type_anotation(idents);
+                b.right_mut().attempt_type_anotation(idents);
+                b.infer_operand_types();
+            }
+            Unary(u) => u.expr_mut().attempt_type_anotation(idents),
+            Cast(c) => c.expr_mut().attempt_type_anotation(idents),
+            Field(f) => f.attempt_type_anotation(idents),
+            Index(i) => {
+                i.base_mut().attempt_type_anotation(idents);
+                i.index_mut().attempt_type_anotation(idents);
+            }
+            Paren(p) => p.expr_mut().attempt_type_anotation(idents),
+            Ternary(t) => {
+                t.cond_mut().attempt_type_anotation(idents);
+                t.cond_mut()
+                    .infer_type(&YaslType::ScalarType(YaslScalarType::Bool));
+                t.then_expr_mut().attempt_type_anotation(idents);
+                t.else_expr_mut().attempt_type_anotation(idents);
+                t.infer_branch_types();
+            }
+            Lit(_) | BuiltinVar(_) => {}
+        }
+    }
+
+    /// Gives unsuffixed literals the type expected by the context, `x + 1` with `x: u32` is `x + 1u`
+    pub fn infer_type(&mut self, ty: &YaslType) {
+        use YaslExprLineScope::*;
+        // GLSL functions and variables of unknown type take it from context too
+        match self {
+            Call(c) => return c.infer_type(ty),
+            Ident(i) if i.native().is_some() && i.get_type().is_none() => {
+                return i.set_type(ty.clone())
+            }
+            _ => {}
+        }
+        let scalar = match ty.scalar() {
+            Some(s) => s,
+            None => return,
+        };
+        match self {
+            Lit(l) => l.infer_type(scalar),
+            Binary(b) if binary::is_arithmetic(b.op()) => {
+                b.left_mut().infer_type(ty);
+                b.right_mut().infer_type(ty);
+            }
+            Binary(b) if binary::is_shift(b.op()) => b.left_mut().infer_type(ty),
+            Unary(u) => u.expr_mut().infer_type(ty),
+            Paren(p) => p.expr_mut().infer_type(ty),
+            Ternary(t) => {
+                t.then_expr_mut().infer_type(ty);
+                t.else_expr_mut().infer_type(ty);
             }
             _ => {}
         }
     }
+
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        if let Some(YaslType::Array(_, None)) = self.get_type() {
+            return Err(Error::new(
+                self.span(),
+                "Runtime sized array can only be indexed or have its len() taken, it has no value of its own",
+            ));
+        }
+        self.validate_indexed(ctx)
+    }
+
+    /// Validates an expression that is indexed or measured, which can be a runtime sized array
+    pub fn validate_indexed(&self, ctx: &ValidationContext) -> Result<()> {
+        use YaslExprLineScope::*;
+        match self {
+            BuiltinVar(b) => ctx.check_stage(b.var().name, b.var().stages, b.span()),
+            Call(c) => c.validate(ctx),
+            Binary(b) => {
+                b.left().validate(ctx)?;
+                b.right().validate(ctx)?;
+                b.validate()
+            }
+            Unary(u) => {
+                u.expr().validate(ctx)?;
+                u.validate(ctx)
+            }
+            Cast(c) => c.validate(ctx),
+            Field(f) => f.base().validate(ctx),
+            Index(i) => {
+                i.base().validate_indexed(ctx)?;
+                i.index().validate(ctx)
+            }
+            Paren(p) => p.expr().validate(ctx),
+            Ternary(t) => {
+                t.cond().validate(ctx)?;
+                let ctx = ctx.branch(t.cond().is_uniform());
+                t.then_expr().validate(&ctx)?;
+                t.else_expr().validate(&ctx)?;
+                t.validate()
+            }
+            Lit(l) => l.validate(),
+            Ident(_) => Ok(()),
+        }
+    }
 }
 
 impl Typed for YaslExprLineScope {
@@ -85,26 +283,47 @@ impl Typed for YaslExprLineScope {
             Binary(b) => b.get_type(),
             Ident(i) => i.get_type(),
             Call(c) => c.get_type(),
-            _ => None,
+            Cast(c) => c.get_type(),
+            Unary(u) => u.get_type(),
+            Index(i) => i.get_type(),
+            BuiltinVar(b) => b.get_type(),
+            Field(f) => f.get_type(),
+            Paren(p) => p.get_type(),
+            Ternary(t) => t.get_type(),
         }
     }
 }
 
-impl From<&YaslExprLineScope> for Glsl {
-    fn from(expr: &YaslExprLineScope) -> Glsl {
+impl TryFrom<&YaslExprLineScope> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprLineScope) -> Result<ir::Expr> {
         use YaslExprLineScope::*;
 
-        Glsl::Expr(match expr {
-            Lit(l) => Glsl::from(l).to_string(),
-            Binary(b) => Glsl::from(b).to_string(),
-            Call(c) => Glsl::from(c).to_string(),
-            Cast(c) => Glsl::from(c).to_string(),
-            Ident(i) => Glsl::from(i).to_string(),
-            Unary(u) => Glsl::from(u).to_string(),
-            Field(f) => Glsl::from(f).to_string(),
-        })
+        match expr {
+            Lit(l) => Ok(l.into()),
+            Binary(b) => b.try_into(),
+            Call(c) => c.try_into(),
+            Cast(c) => c.try_into(),
+            Ident(i) => i.try_into(),
+            Unary(u) => u.try_into(),
+            Field(f) => f.try_into(),
+            Index(i) => i.try_into(),
+            BuiltinVar(b) => Ok(b.into()),
+            Paren(p) => p.try_into(),
+            Ternary(t) => t.try_into(),
+        }
     }
 }
+
+/// Every lowered expression is typed, only GLSL functions and variables can be of unknown type
+pub fn lowered_type(ty: Option<YaslType>, span: Span) -> Result<YaslType> {
+    ty.ok_or_else(|| {
+        Error::new(
+            span,
+            "Cannot infer type of expression, give GLSL functions and variables a type through context, like `let v: f32 = glsl::f(x);`",
+        )
+    })
+}
 impl TryFrom<Expr> for YaslExprLineScope {
     type Error = Error;
     fn try_from(expr: Expr) -> Result<Self> {
@@ -113,10 +332,38 @@ impl TryFrom<Expr> for YaslExprLineScope {
             Expr::Lit(l) => Ok(Lit(l.try_into()?)),
             Expr::Binary(b) => Ok(Binary(b.try_into()?)),
             Expr::Call(c) => Ok(Call(c.try_into()?)),
+            Expr::MethodCall(c) => Ok(Call(c.try_into()?)),
             Expr::Cast(c) => Ok(Cast(c.try_into()?)),
-            Expr::Path(p) => Ok(Ident(p.try_into()?)),
-            Expr::Unary(u) => Ok(Unary(u.try_into()?)),
+            Expr::Path(p) => {
+                let builtin = p
+                    .path
+                    .get_ident()
+                    .and_then(|i| builtins::var(&i.to_string()));
+                if let Some(var) = builtin {
+                    Ok(BuiltinVar(YaslExprBuiltinVar::new(var, p.span())))
+                } else {
+                    Ok(Ident(p.try_into()?))
+                }
+            }
+            Expr::Index(i) => Ok(Index(i.try_into()?)),
+            Expr::Unary(u) => match YaslExprLit::negative(&u) {
+                Some(l) => Ok(Lit(l)),
+                None => Ok(Unary(u.try_into()?)),
+            },
             Expr::Field(f) => Ok(Field(f.try_into()?)),
+            Expr::Paren(p) => Ok(Paren(p.try_into()?)),
+            Expr::If(_) | Expr::Block(_) | Expr::Match(_) if !tail::is_inline(&expr) => Err(Error::new(
+                expr.span(),
+                "Blocks with statements can only be used as a let initializer, an assignment or a return value",
+            )),
+            Expr::If(i) => Ok(Ternary(i.try_into()?)),
+            Expr::Block(b) => {
+                let span = b.block.brace_token.span;
+                match b.block.stmts.into_iter().next() {
+                    Some(syn::Stmt::Expr(e)) => Ok(Paren(YaslExprParen::new(span, e)?)),
+                    _ => Err(Error::new(span, "Expected value of block")),
+                }
+            }
             _ => Err(Error::new(
                 expr.span(),
                 format!("Unsuported Action (Line Scope);\n {:#?}", expr),
@@ -161,7 +408,7 @@ impl TryFrom<Expr> for YaslExprLineScope {
 //     }
 // }
 
-/// Scope Used when returning in function
+// Scope Used when returning in function
 // #[derive(Debug)]
 // pub struct YaslExprReturnScope(YaslExprLineScope);
 
@@ -192,49 +439,124 @@ pub enum YaslExprFunctionScope {
     AssignOp(YaslExprAssignOp),
     Return(YaslExprReturn),
     If(YaslExprIf),
+    Match(YaslExprMatch),
     Block(YaslBlock),
 }
 
 impl YaslExprFunctionScope {
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
         use YaslExprFunctionScope::*;
 
         match self {
+            Call(c) => {
+                c.attempt_type_anotation(idents);
+                // Value of a call used as a statement is discarded
+                c.infer_type(&YaslType::Void);
+            }
+            Assign(a) => a.attempt_type_anotation(idents),
+            AssignOp(a) => a.attempt_type_anotation(idents),
+            Return(r) => r.attempt_type_anotation(idents),
+            If(i) => i.attempt_type_anotation(idents),
+            Match(m) => m.attempt_type_anotation(idents),
             Block(b) => b.attempt_type_anotation(idents),
-            _ => {}
+        }
+    }
+
+    /// Type of the value assigned in branches produced by lowering a block used as a value
+    pub fn assigned_type(&self) -> Option<YaslType> {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Assign(a) => a.right().get_type(),
+            If(i) => i.assigned_type(),
+            Match(m) => m.assigned_type(),
+            Block(b) => b.assigned_type(),
+            Call(_) | AssignOp(_) | Return(_) => None,
+        }
+    }
+
+    /// Like `assigned_type`, but skips branches whose type comes from context like unsuffixed literals,
+    /// so `if c { 1 } else { 2u32 }` is u32
+    pub fn fixed_assigned_type(&self) -> Option<YaslType> {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Assign(a) if a.right().has_fixed_type() => a.right().get_type(),
+            If(i) => i.fixed_assigned_type(),
+            Match(m) => m.fixed_assigned_type(),
+            Block(b) => b.fixed_assigned_type(),
+            Assign(_) | Call(_) | AssignOp(_) | Return(_) => None,
+        }
+    }
+
+    /// Every path through the statement ends with `return`
+    pub fn always_returns(&self) -> bool {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Return(_) => true,
+            If(i) => i.always_returns(),
+            Match(m) => m.always_returns(),
+            Block(b) => b.always_returns(),
+            Call(_) | Assign(_) | AssignOp(_) => false,
+        }
+    }
+
+    /// Some invocations can return while others go on, `non_uniform` when the statement is in a non-uniform branch
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Return(_) => non_uniform,
+            If(i) => i.returns_non_uniformly(non_uniform),
+            Match(m) => m.returns_non_uniformly(non_uniform),
+            Block(b) => b.returns_non_uniformly(non_uniform),
+            Call(_) | Assign(_) | AssignOp(_) => false,
+        }
+    }
+
+    /// Types the place assigned in branches, once the type of the local they initialize is known
+    pub fn set_assigned_type(&mut self, target: &YaslIdent, ty: &YaslType) {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Assign(a) => a.set_assigned_type(target, ty),
+            If(i) => i.set_assigned_type(target, ty),
+            Match(m) => m.set_assigned_type(target, ty),
+            Block(b) => b.set_assigned_type(target, ty),
+            Call(_) | AssignOp(_) | Return(_) => {}
+        }
+    }
+
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        use YaslExprFunctionScope::*;
+
+        match self {
+            Call(c) => c.validate(ctx),
+            Assign(a) => a.validate(ctx),
+            AssignOp(a) => a.validate(ctx),
+            Return(r) => r.validate(ctx),
+            If(i) => i.validate(ctx),
+            Match(m) => m.validate(ctx),
+            Block(b) => b.validate(ctx),
         }
     }
 }
 
-impl From<&YaslExprFunctionScope> for Glsl {
-    fn from(expr: &YaslExprFunctionScope) -> Glsl {
+impl TryFrom<&YaslExprFunctionScope> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprFunctionScope) -> Result<ir::Stmt> {
         use YaslExprFunctionScope::*;
 
-        let glsl: Glsl = match expr {
-            Call(c) => Glsl::Line(GlslLine {
-                span: Some(c.span()),
-                ends_with_semi: true,
-                glsl_string: Glsl::from(c).to_string(),
-            }),
-            Assign(a) => Glsl::Line(GlslLine {
-                span: Some(a.span()),
-                ends_with_semi: true,
-                glsl_string: Glsl::from(a).to_string(),
-            }),
-            AssignOp(a) => Glsl::Line(GlslLine {
-                span: Some(a.span()),
-                ends_with_semi: true,
-                glsl_string: Glsl::from(a).to_string(),
-            }),
-            Return(r) => Glsl::Line(GlslLine {
-                span: Some(r.span()),
-                ends_with_semi: true,
-                glsl_string: Glsl::from(r).to_string(),
-            }),
-            If(i) => i.into(),
-            Block(b) => b.into(),
-        };
-        glsl
+        match expr {
+            Call(c) => Ok(ir::Stmt::Expr(c.try_into()?)),
+            Assign(a) => a.try_into(),
+            AssignOp(a) => a.try_into(),
+            Return(r) => r.try_into(),
+            If(i) => i.try_into(),
+            Match(m) => m.try_into(),
+            Block(b) => Ok(ir::Stmt::Block(b.try_into()?)),
+        }
     }
 }
 
@@ -242,12 +564,31 @@ impl TryFrom<Expr> for YaslExprFunctionScope {
     type Error = Error;
     fn try_from(expr: Expr) -> Result<Self> {
         use YaslExprFunctionScope::*;
+
+        // Values of blocks are assigned or returned in every branch
+        let expr = match expr {
+            Expr::Assign(a) if !tail::is_inline(&a.right) => {
+                let left = *a.left;
+                tail::map_tails(*a.right, &|e| tail::assign_expr(&left, e))?
+            }
+            Expr::AssignOp(a) if !tail::is_inline(&a.right) => {
+                let (left, op) = (*a.left, a.op);
+                tail::map_tails(*a.right, &|e| tail::assign_op_expr(&left, op, e))?
+            }
+            Expr::Return(syn::ExprReturn {
+                expr: Some(value), ..
+            }) if !tail::is_inline(&value) => tail::map_tails(*value, &tail::return_expr)?,
+            expr => expr,
+        };
+
         match expr {
             Expr::Call(c) => Ok(Call(c.try_into()?)),
+            Expr::MethodCall(c) => Ok(Call(c.try_into()?)),
             Expr::Assign(a) => Ok(Assign(a.try_into()?)),
             Expr::AssignOp(a) => Ok(AssignOp(a.try_into()?)),
             Expr::Return(r) => Ok(Return(r.try_into()?)),
             Expr::If(i) => Ok(If(i.try_into()?)),
+            Expr::Match(m) => Ok(Match(m.try_into()?)),
             Expr::Block(b) => Ok(Block(b.try_into()?)),
             _ => Err(Error::new(
                 expr.span(),
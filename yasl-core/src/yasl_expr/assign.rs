@@ -1,41 +1,78 @@
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
 
 use proc_macro2::Span;
-use syn::{spanned::Spanned, Error, ExprAssign, Result};
+use syn::{Error, ExprAssign, Result};
 
-use crate::glsl::Glsl;
+use crate::ir;
+use crate::validation::ValidationContext;
 use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::{Typed, YaslType};
 
 use super::YaslExprLineScope;
 
 #[derive(Debug)]
 pub struct YaslExprAssign {
-    left: YaslIdent,
+    left: YaslExprLineScope,
     right: YaslExprLineScope,
 }
 impl YaslExprAssign {
     pub fn span(&self) -> Span {
         self.left.span()
     }
+    pub fn right(&self) -> &YaslExprLineScope {
+        &self.right
+    }
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        self.left.attempt_type_anotation(idents);
+        self.right.attempt_type_anotation(idents);
+        // GLSL variables like `glsl::gl_FragDepth` take the type of the value assigned to them
+        match (self.left.get_type(), self.right.get_type()) {
+            (Some(ty), _) => self.right.infer_type(&ty),
+            (None, Some(ty)) => self.left.infer_type(&ty),
+            (None, None) => {}
+        }
+    }
+    /// See `YaslExprFunctionScope::set_assigned_type`
+    pub fn set_assigned_type(&mut self, target: &YaslIdent, ty: &YaslType) {
+        if let YaslExprLineScope::Ident(i) = &mut self.left {
+            if i.to_string() == target.to_string() {
+                i.set_type(ty.clone());
+                self.right.infer_type(ty);
+            }
+        }
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        self.left.validate(ctx)?;
+        ctx.check_mutable(&self.left)?;
+        self.right.validate(ctx)?;
+        match self.left.get_type() {
+            Some(ty) => ctx.check_value(&ty, &self.right),
+            None => Ok(()),
+        }
+    }
 }
 
-impl From<&YaslExprAssign> for Glsl {
-    fn from(expr: &YaslExprAssign) -> Glsl {
-        Glsl::Expr(format!(
-            "{} = {}",
-            Glsl::from(&expr.left),
-            Glsl::from(&expr.right),
-        ))
+impl TryFrom<&YaslExprAssign> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprAssign) -> Result<ir::Stmt> {
+        Ok(ir::Stmt::Assign {
+            place: (&expr.left).try_into()?,
+            op: None,
+            value: (&expr.right).try_into()?,
+        })
     }
 }
 
 impl TryFrom<ExprAssign> for YaslExprAssign {
     type Error = Error;
     fn try_from(c: ExprAssign) -> Result<Self> {
-        let left = match *c.left {
-            syn::Expr::Path(p) => p.try_into()?,
-            _ => return Err(Error::new(c.left.span(), "Expeted Ident")),
-        };
+        let left: YaslExprLineScope = (*c.left).try_into()?;
+        if !left.is_place() {
+            return Err(Error::new(left.span(), "Expected variable, field or index"));
+        }
         let right: YaslExprLineScope = (*c.right).try_into()?;
 
         Ok(Self { left, right })
@@ -1,18 +1,23 @@
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
 
 use proc_macro2::Span;
 use syn::{spanned::Spanned, BinOp, Error, ExprAssignOp, Result};
 
 use quote::quote;
 
-use crate::glsl::Glsl;
+use crate::ir;
+use crate::validation::{operand_type, ValidationContext};
 use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::Typed;
 
-use super::YaslExprLineScope;
+use super::{binary, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprAssignOp {
-    left: YaslIdent,
+    left: YaslExprLineScope,
     op: BinOp,
     right: YaslExprLineScope,
 }
@@ -21,28 +26,56 @@ impl YaslExprAssignOp {
     pub fn span(&self) -> Span {
         self.left.span()
     }
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        self.left.attempt_type_anotation(idents);
+        self.right.attempt_type_anotation(idents);
+        if let Some(ty) = self.left.get_type() {
+            self.right.infer_type(&ty);
+        }
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        self.left.validate(ctx)?;
+        ctx.check_mutable(&self.left)?;
+        self.right.validate(ctx)?;
+
+        // `a op= b` has to keep the type of `a`
+        let op = binary::assigned_op(&self.op)
+            .ok_or_else(|| Error::new(self.op.span(), "Unsupported assignment operator"))?;
+        let (l, r) = (operand_type(&self.left)?, operand_type(&self.right)?);
+        let ty = binary::result_type(&op, &l, &r).map_err(|e| Error::new(self.op.span(), e))?;
+        if ty != l {
+            return Err(Error::new(
+                self.op.span(),
+                format!(
+                    "Result of `{}` is {}, it can't be assigned to {}",
+                    quote!(#op),
+                    ty.name(),
+                    l.name()
+                ),
+            ));
+        }
+        Ok(())
+    }
 }
 
-impl From<&YaslExprAssignOp> for Glsl {
-    fn from(expr: &YaslExprAssignOp) -> Glsl {
-        let op = expr.op;
-        let op = quote!(#op).to_string();
-        Glsl::Expr(format!(
-            "{} {} {}",
-            Glsl::from(&expr.left),
-            op,
-            Glsl::from(&expr.right)
-        ))
+impl TryFrom<&YaslExprAssignOp> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprAssignOp) -> Result<ir::Stmt> {
+        Ok(ir::Stmt::Assign {
+            place: (&expr.left).try_into()?,
+            op: binary::assigned_op(&expr.op).map(|op| (&op).into()),
+            value: (&expr.right).try_into()?,
+        })
     }
 }
 
 impl TryFrom<ExprAssignOp> for YaslExprAssignOp {
     type Error = Error;
     fn try_from(c: ExprAssignOp) -> Result<Self> {
-        let left = match *c.left {
-            syn::Expr::Path(p) => p.try_into()?,
-            _ => return Err(Error::new(c.left.span(), "Expeted Ident")),
-        };
+        let left: YaslExprLineScope = (*c.left).try_into()?;
+        if !left.is_place() {
+            return Err(Error::new(left.span(), "Expected variable, field or index"));
+        }
         let op = c.op;
         let right: YaslExprLineScope = (*c.right).try_into()?;
 
@@ -1,17 +1,19 @@
 use std::convert::{TryFrom, TryInto};
 
-use syn::{BinOp, Error, Result};
+use proc_macro2::Span;
+use syn::{spanned::Spanned, BinOp, Error, Result};
 
 use syn::ExprBinary;
 
 use quote::quote;
 
 use crate::{
-    glsl::Glsl,
-    yasl_type::{Typed, YaslType},
+    ir,
+    validation::operand_type,
+    yasl_type::{Typed, YaslScalarType, YaslType},
 };
 
-use super::YaslExprLineScope;
+use super::{lowered_type, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprBinary {
@@ -20,20 +22,236 @@ pub struct YaslExprBinary {
     right: Box<YaslExprLineScope>,
 }
 
+impl YaslExprBinary {
+    pub fn span(&self) -> Span {
+        self.left.span()
+    }
+    pub fn op(&self) -> &BinOp {
+        &self.op
+    }
+    pub fn left(&self) -> &YaslExprLineScope {
+        &self.left
+    }
+    pub fn right(&self) -> &YaslExprLineScope {
+        &self.right
+    }
+    pub fn left_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.left
+    }
+    pub fn right_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.right
+    }
+}
+
+impl YaslExprBinary {
+    /// Unsuffixed literal takes the type of the other operand, `1 + x` with `x: u32` is `1u + x`
+    pub fn infer_operand_types(&mut self) {
+        if is_shift(&self.op) {
+            return;
+        }
+        if let Some(ty) = self.left.get_type() {
+            self.right.infer_type(&ty);
+        }
+        if let Some(ty) = self.right.get_type() {
+            self.left.infer_type(&ty);
+        }
+    }
+}
+
+/// Operators that produce a value of the operand type
+pub fn is_arithmetic(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Add(_)
+            | BinOp::Sub(_)
+            | BinOp::Mul(_)
+            | BinOp::Div(_)
+            | BinOp::Rem(_)
+            | BinOp::BitAnd(_)
+            | BinOp::BitOr(_)
+            | BinOp::BitXor(_)
+    )
+}
+
+/// Shift amount doesn't have to match type of the shifted value
+pub fn is_shift(op: &BinOp) -> bool {
+    matches!(op, BinOp::Shl(_) | BinOp::Shr(_))
+}
+
+impl YaslExprBinary {
+    pub fn validate(&self) -> Result<()> {
+        let (l, r) = (operand_type(&self.left)?, operand_type(&self.right)?);
+        result_type(&self.op, &l, &r).map_err(|e| Error::new(self.op.span(), e))?;
+        Ok(())
+    }
+}
+
+fn op_name(op: &BinOp) -> String {
+    quote!(#op).to_string()
+}
+
+/// Type of `left op right`, operands are never converted implicitly.
+/// Vectors can be combined componentwise with vectors of the same type, or with a scalar of their component type
+pub fn result_type(
+    op: &BinOp,
+    left: &YaslType,
+    right: &YaslType,
+) -> std::result::Result<YaslType, String> {
+    use YaslScalarType::*;
+
+    let mismatch = || {
+        format!(
+            "Cannot apply `{}` to {} and {}, cast one of the operands with `as`",
+            op_name(op),
+            left.name(),
+            right.name()
+        )
+    };
+    let boolean = YaslType::ScalarType(Bool);
+
+    match op {
+        BinOp::And(_) | BinOp::Or(_) => {
+            if *left == boolean && *right == boolean {
+                Ok(boolean)
+            } else {
+                Err(format!(
+                    "`{}` only accepts bool operands, found {} and {}",
+                    op_name(op),
+                    left.name(),
+                    right.name()
+                ))
+            }
+        }
+        BinOp::Eq(_) | BinOp::Ne(_) if left == right => Ok(boolean),
+        BinOp::Eq(_) | BinOp::Ne(_) => Err(mismatch()),
+        BinOp::Lt(_) | BinOp::Le(_) | BinOp::Gt(_) | BinOp::Ge(_) => match (left, right) {
+            (YaslType::ScalarType(a), YaslType::ScalarType(b)) if a == b && *a != Bool => {
+                Ok(boolean)
+            }
+            (YaslType::Vec(_), YaslType::Vec(_)) => Err(format!(
+                "`{}` is not supported on vectors, use lessThan(), greaterThan() and similar builtins",
+                op_name(op)
+            )),
+            _ => Err(mismatch()),
+        },
+        _ if matches!(left, YaslType::Enum(_)) || matches!(right, YaslType::Enum(_)) => Err(
+            format!("`{}` is not supported on enums", op_name(op)),
+        ),
+        BinOp::Shl(_) | BinOp::Shr(_) => {
+            let integer = |ty: &YaslType| matches!(ty.scalar(), Some(Int | UInt));
+            let same_shape = match (left, right) {
+                (YaslType::Vec(a), YaslType::Vec(b)) => {
+                    std::mem::discriminant(a) == std::mem::discriminant(b)
+                }
+                (YaslType::Vec(_) | YaslType::ScalarType(_), YaslType::ScalarType(_)) => true,
+                _ => false,
+            };
+            if integer(left) && integer(right) && same_shape {
+                Ok(left.clone())
+            } else {
+                Err(format!(
+                    "`{}` is only supported on integers, found {} and {}",
+                    op_name(op),
+                    left.name(),
+                    right.name()
+                ))
+            }
+        }
+        op if is_arithmetic(op) => {
+            let ty = match (left, right) {
+                (YaslType::ScalarType(a), YaslType::ScalarType(b)) if a == b => left,
+                (YaslType::Vec(a), YaslType::Vec(b)) if a == b => left,
+                (YaslType::Vec(v), YaslType::ScalarType(s)) if v.component() == s => left,
+                (YaslType::ScalarType(s), YaslType::Vec(v)) if v.component() == s => right,
+                _ => return Err(mismatch()),
+            };
+            let integer_only = matches!(
+                op,
+                BinOp::Rem(_) | BinOp::BitAnd(_) | BinOp::BitOr(_) | BinOp::BitXor(_)
+            );
+            match ty.scalar() {
+                Some(Int | UInt) => Ok(ty.clone()),
+                Some(Float32 | Float64) if !integer_only => Ok(ty.clone()),
+                Some(Float32 | Float64) if matches!(op, BinOp::Rem(_)) => {
+                    Err("`%` is only supported on integers, use mod()".into())
+                }
+                Some(Bool) if integer_only => Err(format!(
+                    "`{}` is not supported on bool, use && or ||",
+                    op_name(op)
+                )),
+                _ => Err(format!(
+                    "`{}` is not supported on {}",
+                    op_name(op),
+                    ty.name()
+                )),
+            }
+        }
+        _ => Err(format!("Operator `{}` is not supported", op_name(op))),
+    }
+}
+
+/// Operator of a compound assignment, `+=` is `+`
+pub fn assigned_op(op: &BinOp) -> Option<BinOp> {
+    use syn::token;
+    let span = op.span();
+    Some(match op {
+        BinOp::AddEq(_) => BinOp::Add(token::Add(span)),
+        BinOp::SubEq(_) => BinOp::Sub(token::Sub(span)),
+        BinOp::MulEq(_) => BinOp::Mul(token::Star(span)),
+        BinOp::DivEq(_) => BinOp::Div(token::Div(span)),
+        BinOp::RemEq(_) => BinOp::Rem(token::Rem(span)),
+        BinOp::BitAndEq(_) => BinOp::BitAnd(token::And(span)),
+        BinOp::BitOrEq(_) => BinOp::BitOr(token::Or(span)),
+        BinOp::BitXorEq(_) => BinOp::BitXor(token::Caret(span)),
+        BinOp::ShlEq(_) => BinOp::Shl(token::Shl(span)),
+        BinOp::ShrEq(_) => BinOp::Shr(token::Shr(span)),
+        _ => return None,
+    })
+}
+
+/// Unknown when the operation is invalid, validation reports why
 impl Typed for YaslExprBinary {
     fn get_type(&self) -> Option<YaslType> {
-        self.left.get_type()
+        result_type(&self.op, &self.left.get_type()?, &self.right.get_type()?).ok()
     }
 }
 
-impl From<&YaslExprBinary> for Glsl {
-    fn from(expr: &YaslExprBinary) -> Glsl {
-        let left: Glsl = (&*expr.left).into();
-        let op = &expr.op;
-        let op = quote!(#op).to_string();
-        let right: Glsl = (&*expr.right).into();
+/// Compound assignments are converted with `assigned_op` first
+impl From<&BinOp> for ir::BinOp {
+    fn from(op: &BinOp) -> ir::BinOp {
+        match op {
+            BinOp::Add(_) => ir::BinOp::Add,
+            BinOp::Sub(_) => ir::BinOp::Sub,
+            BinOp::Mul(_) => ir::BinOp::Mul,
+            BinOp::Div(_) => ir::BinOp::Div,
+            BinOp::Rem(_) => ir::BinOp::Rem,
+            BinOp::And(_) => ir::BinOp::And,
+            BinOp::Or(_) => ir::BinOp::Or,
+            BinOp::BitXor(_) => ir::BinOp::BitXor,
+            BinOp::BitAnd(_) => ir::BinOp::BitAnd,
+            BinOp::BitOr(_) => ir::BinOp::BitOr,
+            BinOp::Shl(_) => ir::BinOp::Shl,
+            BinOp::Shr(_) => ir::BinOp::Shr,
+            BinOp::Eq(_) => ir::BinOp::Eq,
+            BinOp::Lt(_) => ir::BinOp::Lt,
+            BinOp::Le(_) => ir::BinOp::Le,
+            BinOp::Ne(_) => ir::BinOp::Ne,
+            BinOp::Ge(_) => ir::BinOp::Ge,
+            BinOp::Gt(_) => ir::BinOp::Gt,
+            op => unreachable!("{} is not a binary operator", op_name(op)),
+        }
+    }
+}
 
-        Glsl::Expr(format!("{} {} {}", left, op, right))
+impl TryFrom<&YaslExprBinary> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprBinary) -> Result<ir::Expr> {
+        Ok(ir::Expr::binary(
+            (&expr.op).into(),
+            (&*expr.left).try_into()?,
+            (&*expr.right).try_into()?,
+            lowered_type(expr.get_type(), expr.span())?,
+        ))
     }
 }
 
@@ -0,0 +1,35 @@
+use proc_macro2::Span;
+
+use crate::builtins::BuiltinVar;
+use crate::ir;
+use crate::yasl_type::{Typed, YaslType};
+
+#[derive(Debug)]
+pub struct YaslExprBuiltinVar {
+    var: &'static BuiltinVar,
+    span: Span,
+}
+impl YaslExprBuiltinVar {
+    pub fn new(var: &'static BuiltinVar, span: Span) -> Self {
+        Self { var, span }
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn var(&self) -> &'static BuiltinVar {
+        self.var
+    }
+}
+
+impl Typed for YaslExprBuiltinVar {
+    fn get_type(&self) -> Option<YaslType> {
+        Some(self.var.ty.clone())
+    }
+}
+
+impl From<&YaslExprBuiltinVar> for ir::Expr {
+    fn from(expr: &YaslExprBuiltinVar) -> ir::Expr {
+        let kind = ir::ExprKind::BuiltinVar(expr.var.name.into());
+        ir::Expr::new(kind, expr.var.ty.clone(), expr.span())
+    }
+}
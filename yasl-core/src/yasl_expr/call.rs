@@ -1,67 +1,522 @@
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 
 use proc_macro2::Span;
 use syn::{punctuated::Punctuated, spanned::Spanned, Error, Result};
 
-use syn::{Expr, ExprCall};
+use syn::{Expr, ExprCall, ExprMethodCall};
 
-use crate::glsl::Glsl;
+use crate::builtins::{self, BuiltinFn, BuiltinParam, BuiltinReturn};
+use crate::cpu;
+use crate::ir;
+use crate::validation::ValidationContext;
 use crate::{
     yasl_ident::YaslIdent,
-    yasl_type::{Typed, YaslType},
+    yasl_item::ParamKind,
+    yasl_type::{Typed, YaslScalarType, YaslType},
 };
 
-use super::YaslExprLineScope;
+use super::{lowered_type, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprCall {
     ident: YaslIdent,
     args: Punctuated<YaslExprLineScope, syn::token::Comma>,
+    /// `&mut` in front of each argument
+    mut_refs: Vec<Option<Span>>,
+    builtin: Option<&'static BuiltinFn>,
+    /// Called as a method on the first argument, `v.length()`
+    method: bool,
+    /// Type of a GLSL function only GLSL knows, taken from where it's called, see `infer_type`
+    native_ty: Option<YaslType>,
 }
 impl YaslExprCall {
+    pub fn builtin(&self) -> Option<&'static BuiltinFn> {
+        self.builtin
+    }
+    pub fn args(&self) -> impl Iterator<Item = &YaslExprLineScope> {
+        self.args.iter()
+    }
     pub fn span(&self) -> Span {
         self.ident.span()
     }
-    pub fn get_mut_ident(&mut self) -> &mut YaslIdent {
-        &mut self.ident
+    pub fn name(&self) -> String {
+        self.ident.name()
+    }
+    /// User functions declared above take precedence over builtins, `fn length(..)` hides `length()` and `v.length()`
+    fn resolve_builtin(&mut self, idents: &HashMap<String, YaslIdent>) {
+        if idents
+            .get(&self.ident.key())
+            .is_some_and(|i| i.params().is_some())
+        {
+            self.builtin = None;
+        }
+    }
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        for a in self.args.iter_mut() {
+            a.attempt_type_anotation(idents);
+        }
+        self.resolve_builtin(idents);
+        if let Some(binding) = idents.get(&self.ident.key()) {
+            self.ident.resolve(binding);
+        }
+        self.infer_arg_types();
+    }
+    /// GLSL functions of unknown type take the type of where they're called, like `let x: f32 = glsl::fract(v);`
+    pub fn infer_type(&mut self, ty: &YaslType) {
+        if self.ident.native().is_some() && self.get_type().is_none() {
+            self.native_ty = Some(ty.clone());
+        }
+    }
+    /// Builtin that is called, also when it's written with its GLSL name like `glsl::atomicAdd`
+    fn signature(&self) -> Option<&'static BuiltinFn> {
+        self.builtin.or_else(|| {
+            self.ident
+                .native()
+                .and_then(|name| builtins::glsl_function(&name))
+        })
+    }
+    /// Unsuffixed literal arguments take the type of the parameter,
+    /// arguments of builtins take the type of each other, `atomic_add(counters[0], 1)` is `atomicAdd(counters[0], 1u)`
+    fn infer_arg_types(&mut self) {
+        if let (None, Some(params)) = (self.builtin, self.ident.params()) {
+            for (a, ty) in self.args.iter_mut().zip(params) {
+                a.infer_type(ty);
+            }
+        } else if let Some(ty) = self.ident.native().and_then(|n| YaslType::constructed(&n)) {
+            for a in self.args.iter_mut() {
+                a.infer_type(&ty);
+            }
+        } else if self.signature().is_some() {
+            for i in 0..self.args.len() {
+                if let Some(ty) = self.args[i].get_type() {
+                    for a in self.args.iter_mut() {
+                        a.infer_type(&ty);
+                    }
+                }
+            }
+        }
     }
 }
 
-impl Typed for YaslExprCall {
-    fn get_type(&self) -> Option<YaslType> {
-        self.ident.get_type()
+impl YaslExprCall {
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        for (i, a) in self.args.iter().enumerate() {
+            match self.builtin {
+                Some(b) if matches!(b.params.get(i), Some(BuiltinParam::Array)) => {
+                    a.validate_indexed(ctx)?
+                }
+                _ => a.validate(ctx)?,
+            }
+        }
+        if ctx.shared {
+            self.validate_cpu()?;
+        }
+
+        let builtin = if let Some(b) = self.builtin {
+            b
+        } else {
+            if ctx.uniform_functions.contains(&self.ident.to_string()) {
+                self.check_uniform(ctx)?;
+            }
+            return self.validate_params(ctx);
+        };
+
+        // Array receivers are checked against the signature
+        let on_numbers = !matches!(builtin.params.first(), Some(BuiltinParam::Array));
+        let receiver = self.args.first().and_then(|a| a.get_type());
+        if let (true, true, Some(ty)) = (self.method, on_numbers, receiver) {
+            if !matches!(
+                ty.scalar(),
+                Some(
+                    YaslScalarType::Int
+                        | YaslScalarType::UInt
+                        | YaslScalarType::Float32
+                        | YaslScalarType::Float64
+                )
+            ) || matches!(ty, YaslType::Enum(_))
+            {
+                return Err(Error::new(
+                    self.span(),
+                    format!(
+                        "No method {}() on {}, builtins can only be called on numbers and vectors, and len() on arrays",
+                        self.ident.name(),
+                        ty.name()
+                    ),
+                ));
+            }
+        }
+
+        if let Some(span) = self.mut_refs.iter().flatten().next() {
+            return Err(Error::new(
+                *span,
+                format!("{}() doesn't take &mut arguments", builtin.name),
+            ));
+        }
+
+        if builtin.atomic {
+            ctx.check_atomic_target(builtin.name, self.args.first(), self.span())?;
+        }
+
+        self.check_signature(builtin)?;
+
+        if let Some(stages) = builtin.stages {
+            ctx.check_stage(builtin.name, stages, self.span())?;
+        }
+
+        if builtin.uniform_only {
+            self.check_uniform(ctx)?;
+        }
+
+        Ok(())
     }
 }
 
-impl From<&YaslExprCall> for Glsl {
-    fn from(expr: &YaslExprCall) -> Glsl {
-        let ident = Glsl::from(&expr.ident);
+impl YaslExprCall {
+    /// Calls to barriers, and to functions that use them, can't be skipped by some invocations
+    fn check_uniform(&self, ctx: &ValidationContext) -> Result<()> {
+        ctx.calls_uniform_only.set(true);
+        if !ctx.non_uniform {
+            return Ok(());
+        }
+        let what = match self.builtin {
+            Some(b) => format!("{}()", b.name),
+            None => format!("{}() calls barrier(), so it", self.ident.name()),
+        };
+        Err(Error::new(
+            self.span(),
+            format!(
+                "{} has to be called from uniform control flow, it can't be used inside of branches that depend on per-invocation values, or after some invocations returned",
+                what
+            ),
+        ))
+    }
+    /// Arguments of a builtin have to fit its parameters, `dot(a, b)` takes two vectors of one type
+    fn check_signature(&self, builtin: &BuiltinFn) -> Result<()> {
+        let params = builtin.params;
+        if self.method && params.is_empty() {
+            return Err(Error::new(
+                self.span(),
+                format!(
+                    "{}() takes no arguments, it can't be called as a method",
+                    builtin.name
+                ),
+            ));
+        }
+        if params.len() != self.args.len() {
+            let message = if self.method {
+                format!(
+                    "Method {}() takes {} arguments besides the receiver, found {}",
+                    self.ident.name(),
+                    params.len() - 1,
+                    self.args.len() - 1
+                )
+            } else {
+                format!(
+                    "{}() takes {} arguments, found {}",
+                    builtin.name,
+                    params.len(),
+                    self.args.len()
+                )
+            };
+            return Err(Error::new(self.span(), message));
+        }
 
-        let mut args: Vec<String> = Vec::new();
+        let types: Vec<_> = self.args.iter().map(|a| a.get_type()).collect();
+        match builtin.mismatched_arg(&types) {
+            Some((i, message)) => Err(Error::new(self.args[i].span(), message)),
+            None => Ok(()),
+        }
+    }
+    /// Shared functions are compiled as Rust too, so they can only call what `cpu` implements
+    fn validate_cpu(&self) -> Result<()> {
+        let name = self.ident.name();
+        let available = match (self.method, self.args.first().and_then(|a| a.get_type())) {
+            (true, Some(ty)) => cpu::has_method(&ty, &name),
+            // Receivers of unknown type are results of `glsl::` calls, which are rejected themselves
+            (true, None) => true,
+            (false, _) => match (self.builtin, self.ident.native()) {
+                (Some(b), _) => cpu::has_function(b.name),
+                (None, Some(native)) => cpu::has_function(&native),
+                (None, None) => true,
+            },
+        };
+        if available {
+            return Ok(());
+        }
+        let what = match self.args.first().and_then(|a| a.get_type()) {
+            Some(ty) if self.method => format!("Method {}() of {}", name, ty.yasl_name()),
+            _ => format!("Function {}()", self.ident.native().unwrap_or(name)),
+        };
+        Err(Error::new(
+            self.span(),
+            format!(
+                "{} is not available in shared functions, they are compiled as Rust against yasl_core::cpu",
+                what
+            ),
+        ))
+    }
+    /// `&mut` arguments have to match `&mut` parameters, values have to match parameter types
+    fn validate_params(&self, ctx: &ValidationContext) -> Result<()> {
+        if self.ident.native().is_none() && self.ident.get_type().is_none() {
+            return Err(Error::new(
+                self.span(),
+                format!(
+                    "Cannot find function `{}`, functions can only call functions declared above them",
+                    self.ident.name()
+                ),
+            ));
+        }
+        let params = match ctx.functions.get(&self.ident.to_string()) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        if params.len() != self.args.len() {
+            return Err(Error::new(
+                self.span(),
+                format!(
+                    "{}() takes {} arguments, found {}",
+                    self.ident.name(),
+                    params.len(),
+                    self.args.len()
+                ),
+            ));
+        }
 
-        for a in expr.args.iter() {
-            args.push(Glsl::from(a).into());
+        for ((arg, mut_ref), (kind, ty)) in self.args.iter().zip(self.mut_refs.iter()).zip(params) {
+            ctx.check_value(ty, arg)?;
+            match (kind, mut_ref) {
+                (ParamKind::In, Some(span)) => {
+                    return Err(Error::new(*span, "Parameter is not &mut, pass it by value"))
+                }
+                (ParamKind::In, None) => {}
+                (_, None) => return Err(Error::new(arg.span(), "Expected &mut argument")),
+                (_, Some(_)) if !arg.is_place() => {
+                    return Err(Error::new(arg.span(), "Expected variable, field or index"))
+                }
+                (_, Some(_)) => ctx.check_mutable(arg)?,
+            }
         }
+        Ok(())
+    }
+}
+
+impl Typed for YaslExprCall {
+    fn get_type(&self) -> Option<YaslType> {
+        match self.signature() {
+            Some(b) => match b.ret {
+                BuiltinReturn::Void => Some(YaslType::Void),
+                BuiltinReturn::UInt => Some(YaslType::ScalarType(YaslScalarType::UInt)),
+                BuiltinReturn::FirstArg => self.args.first()?.get_type(),
+                BuiltinReturn::Component => self
+                    .args
+                    .first()?
+                    .get_type()?
+                    .scalar()
+                    .map(|s| YaslType::ScalarType(s.clone())),
+            },
+            // Other GLSL functions have unknown type, unless they are constructors like `vec3`
+            None => match self.ident.native() {
+                Some(name) => YaslType::constructed(&name).or_else(|| self.native_ty.clone()),
+                None => self.ident.get_type(),
+            },
+        }
+    }
+}
 
-        Glsl::Expr(format!("{}({})", ident, args.join(",")))
+impl TryFrom<&YaslExprCall> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprCall) -> Result<ir::Expr> {
+        let callee = match (expr.builtin, expr.ident.native()) {
+            (Some(b), _) => ir::Callee::Builtin(b.name.into()),
+            (None, Some(name)) => ir::Callee::Native(name),
+            (None, None) => ir::Callee::Function((&expr.ident).into()),
+        };
+        let kind = ir::ExprKind::Call {
+            callee,
+            args: expr
+                .args
+                .iter()
+                .map(ir::Expr::try_from)
+                .collect::<Result<_>>()?,
+        };
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
     }
 }
 
 impl TryFrom<ExprCall> for YaslExprCall {
     type Error = Error;
     fn try_from(c: ExprCall) -> Result<Self> {
-        let ident = if let Expr::Path(p) = *c.func {
-            p.try_into()?
+        let (ident, builtin) = if let Expr::Path(p) = *c.func {
+            let builtin = p
+                .path
+                .get_ident()
+                .and_then(|i| builtins::function(&i.to_string()));
+            (p.try_into()?, builtin)
         } else {
             return Err(Error::new(c.func.span(), "Expected Ident"));
         };
 
         let mut args = Punctuated::new();
+        let mut mut_refs = Vec::new();
 
         for e in c.args.into_iter() {
-            args.push(e.try_into()?);
+            let (e, mut_ref) = parse_arg(e)?;
+            args.push(e);
+            mut_refs.push(mut_ref);
         }
 
-        Ok(Self { ident, args })
+        Ok(Self {
+            ident,
+            args,
+            mut_refs,
+            builtin,
+            method: false,
+            native_ty: None,
+        })
+    }
+}
+
+/// `a.f(b)` is `f(a, b)`
+impl TryFrom<ExprMethodCall> for YaslExprCall {
+    type Error = Error;
+    fn try_from(c: ExprMethodCall) -> Result<Self> {
+        if let Some(turbofish) = c.turbofish {
+            return Err(Error::new(
+                turbofish.span(),
+                "Generic arguments are not supported",
+            ));
+        }
+        if let Expr::Reference(r) = &*c.receiver {
+            return Err(Error::new(
+                r.and_token.span,
+                "Receiver can't be a reference",
+            ));
+        }
+
+        let builtin = builtins::method(&c.method.to_string());
+        let mut args = Punctuated::new();
+        let mut mut_refs = vec![None];
+        args.push((*c.receiver).try_into()?);
+
+        for e in c.args.into_iter() {
+            let (e, mut_ref) = parse_arg(e)?;
+            args.push(e);
+            mut_refs.push(mut_ref);
+        }
+
+        Ok(Self {
+            ident: c.method.into(),
+            args,
+            mut_refs,
+            builtin,
+            method: true,
+            native_ty: None,
+        })
+    }
+}
+
+/// Argument, with span of `&mut` in front of it
+fn parse_arg(e: Expr) -> Result<(YaslExprLineScope, Option<Span>)> {
+    let (e, mut_ref) = match e {
+        Expr::Reference(r) if r.mutability.is_some() => (*r.expr, Some(r.and_token.span)),
+        Expr::Reference(r) => {
+            return Err(Error::new(
+                r.and_token.span,
+                "Only &mut references are supported",
+            ))
+        }
+        e => (e, None),
+    };
+    Ok((e.try_into()?, mut_ref))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// First error of a vertex shader with `body` in main, None when it compiles
+    fn error(body: &str) -> Option<String> {
+        let source = format!("fn main() {{ let v = vec3(1.0, 2.0, 3.0); {} }}", body);
+        compile(&source, &CompileOptions::default())
+            .err()
+            .map(|d| d.errors[0].message.clone())
+    }
+
+    #[test]
+    fn builtin_arguments_fit_parameters() {
+        assert_eq!(
+            error("let n = v.normalize().dot(v).clamp(0.0, 1.0).powf(2.0);"),
+            None
+        );
+        assert_eq!(
+            error("let c = clamp(v, 0.0, 1.0).lerp(v, 0.5).cross(v);"),
+            None
+        );
+        assert_eq!(error("let m = max(1u32, 2u32) + abs(-1) as u32;"), None);
+
+        let errors = [
+            ("let d = dot(v);", "dot() takes 2 arguments, found 1"),
+            (
+                "let l = v.length(1.0);",
+                "Method length() takes 0 arguments besides the receiver, found 1",
+            ),
+            (
+                "let c = vec2(1.0, 2.0).cross(vec2(1.0, 2.0));",
+                "cross() expects vec3 or dvec3, found vec2",
+            ),
+            (
+                "let l = 1.length();",
+                "length() expects float or double scalar or vector, found int",
+            ),
+            ("let p = 2.0.powf(2);", "pow() expects float, found int"),
+            (
+                "let c = clamp(1.0, 0, 1);",
+                "clamp() expects float, found int",
+            ),
+            (
+                "let a = abs(1u32);",
+                "abs() expects float, double or int scalar or vector, found uint",
+            ),
+            (
+                "let s = 1.0f64.sin();",
+                "sin() expects float scalar or vector, found double",
+            ),
+        ];
+        for (body, message) in errors.iter() {
+            assert_eq!(error(body).as_deref(), Some(*message), "{}", body);
+        }
+    }
+
+    #[test]
+    fn runtime_sized_arrays_are_only_indexed_or_measured() {
+        let compile_main = |body: &str| {
+            let source = format!(
+                "layout<buffer,0> counters: [u32];\nfn f(a: u32) -> u32 {{ a }}\nfn main() {{ {} }}",
+                body
+            );
+            compile(&source, &CompileOptions::default())
+                .map(|o| o.stages[0].glsl.clone())
+                .map_err(|d| d.errors[0].message.clone())
+        };
+
+        let glsl = compile_main("let n = counters.len() + counters[0];").unwrap();
+        assert!(glsl.contains("uint n = uint(counters.length()) + counters[0];"));
+
+        let message = "Runtime sized array can only be indexed or have its len() taken, it has no value of its own";
+        for body in [
+            "let q = counters;",
+            "let y = f(counters);",
+            "counters = counters;",
+        ]
+        .iter()
+        {
+            assert_eq!(compile_main(body), Err(message.to_string()), "{}", body);
+        }
+        assert_eq!(
+            compile_main("let n = 1.len();"),
+            Err("len() expects array, found int".to_string())
+        );
     }
 }
@@ -1,13 +1,15 @@
 use std::convert::{TryFrom, TryInto};
 
+use proc_macro2::Span;
 use syn::{Error, Result};
 
 use syn::ExprCast;
 
-use crate::glsl::Glsl;
-use crate::yasl_type::YaslType;
+use crate::ir;
+use crate::validation::ValidationContext;
+use crate::yasl_type::{Typed, YaslScalarType, YaslType};
 
-use super::YaslExprLineScope;
+use super::{lowered_type, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprCast {
@@ -15,13 +17,61 @@ pub struct YaslExprCast {
     ty: Box<YaslType>,
 }
 
-impl From<&YaslExprCast> for Glsl {
-    fn from(expr: &YaslExprCast) -> Glsl {
-        Glsl::Expr(format!(
-            "{}({})",
-            Glsl::from(&*expr.ty),
-            Glsl::from(&*expr.expr)
-        ))
+impl YaslExprCast {
+    pub fn span(&self) -> Span {
+        self.expr.span()
+    }
+    pub fn ty(&self) -> &YaslType {
+        &self.ty
+    }
+    pub fn expr(&self) -> &YaslExprLineScope {
+        &self.expr
+    }
+    pub fn expr_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.expr
+    }
+    /// Like in Rust, enums can be cast to integers, but nothing can be cast to an enum
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        self.expr.validate(ctx)?;
+        match (self.expr.get_type(), &*self.ty) {
+            (Some(YaslType::Enum(from)), YaslType::Enum(to)) if &from == to => Ok(()),
+            (from, YaslType::Enum(to)) => Err(Error::new(
+                self.span(),
+                format!(
+                    "Cannot cast {} to enum {}",
+                    from.map_or_else(|| "value".into(), |ty| ty.name()),
+                    to
+                ),
+            )),
+            (
+                Some(YaslType::Enum(_)),
+                YaslType::ScalarType(YaslScalarType::Int | YaslScalarType::UInt),
+            ) => Ok(()),
+            (Some(YaslType::Enum(from)), to) => Err(Error::new(
+                self.span(),
+                format!(
+                    "Cannot cast enum {} to {}, it can only be cast to i32 or u32",
+                    from,
+                    to.name()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Typed for YaslExprCast {
+    fn get_type(&self) -> Option<YaslType> {
+        Some((*self.ty).clone())
+    }
+}
+
+impl TryFrom<&YaslExprCast> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprCast) -> Result<ir::Expr> {
+        let kind = ir::ExprKind::Cast(Box::new((&*expr.expr).try_into()?));
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
     }
 }
 
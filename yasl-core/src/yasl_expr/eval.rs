@@ -0,0 +1,419 @@
+use proc_macro2::Span;
+use quote::quote;
+use syn::{spanned::Spanned, BinOp, Error, Lit, Result, UnOp};
+
+use crate::builtins::BuiltinFn;
+use crate::glsl::Glsl;
+use crate::yasl_const::{ConstValue, Consts, Folded};
+use crate::yasl_type::{YaslScalarType, YaslType};
+
+use super::{binary, YaslExprLineScope};
+
+fn mismatch(span: Span) -> Error {
+    Error::new(span, "Mismatched types in constant expression")
+}
+
+fn overflow(span: Span) -> Error {
+    Error::new(span, "Constant expression overflows")
+}
+
+/// Unsuffixed literals have the expected type when it fits them
+fn lit(lit: &Lit, expected: Option<&YaslScalarType>) -> Result<Option<Folded>> {
+    let out_of_range =
+        |ty: &str| Error::new(lit.span(), format!("Literal out of range for {}", ty));
+    let untyped = match lit {
+        Lit::Int(i) => i.suffix().is_empty(),
+        Lit::Float(f) => f.suffix().is_empty(),
+        _ => false,
+    };
+    let value = match lit {
+        Lit::Int(i) => match (i.suffix(), expected) {
+            ("", Some(YaslScalarType::UInt)) | ("u32", _) => {
+                ConstValue::UInt(i.base10_parse().map_err(|_| out_of_range("u32"))?)
+            }
+            ("", _) | ("i32", _) => {
+                ConstValue::Int(i.base10_parse().map_err(|_| out_of_range("i32"))?)
+            }
+            _ => return Ok(None),
+        },
+        Lit::Float(f) => match (f.suffix(), expected) {
+            ("", Some(YaslScalarType::Float64)) | ("f64", _) => {
+                ConstValue::Float64(f.base10_parse()?)
+            }
+            ("", _) | ("f32", _) => ConstValue::Float32(f.base10_parse()?),
+            _ => return Ok(None),
+        },
+        Lit::Bool(b) => ConstValue::Bool(b.value),
+        _ => return Ok(None),
+    };
+    Ok(Some(Folded { value, untyped }))
+}
+
+fn unary(op: &UnOp, v: ConstValue, span: Span) -> Result<ConstValue> {
+    use ConstValue::*;
+    Ok(match (op, v) {
+        (UnOp::Neg(_), Int(v)) => Int(v.checked_neg().ok_or_else(|| overflow(span))?),
+        (UnOp::Neg(_), Float32(v)) => Float32(-v),
+        (UnOp::Neg(_), Float64(v)) => Float64(-v),
+        (UnOp::Not(_), Bool(v)) => Bool(!v),
+        (UnOp::Not(_), Int(v)) => Int(!v),
+        (UnOp::Not(_), UInt(v)) => UInt(!v),
+        (op, v) => {
+            return Err(Error::new(
+                span,
+                format!(
+                    "`{}` is not supported on {}",
+                    quote!(#op),
+                    Glsl::from(&v.scalar_type())
+                ),
+            ))
+        }
+    })
+}
+
+macro_rules! int_op {
+    ($op: expr, $a: expr, $b: expr, $span: expr) => {{
+        let (a, b, span) = ($a, $b, $span);
+        match $op {
+            BinOp::Add(_) => a.checked_add(b),
+            BinOp::Sub(_) => a.checked_sub(b),
+            BinOp::Mul(_) => a.checked_mul(b),
+            BinOp::Div(_) | BinOp::Rem(_) if b == 0 => {
+                return Err(Error::new(span, "Division by zero in constant expression"));
+            }
+            BinOp::Div(_) => a.checked_div(b),
+            BinOp::Rem(_) => a.checked_rem(b),
+            BinOp::BitAnd(_) => Some(a & b),
+            BinOp::BitOr(_) => Some(a | b),
+            BinOp::BitXor(_) => Some(a ^ b),
+            BinOp::Shl(_) => a.checked_shl(b as u32),
+            BinOp::Shr(_) => a.checked_shr(b as u32),
+            _ => return Err(mismatch(span)),
+        }
+        .ok_or_else(|| overflow(span))?
+    }};
+}
+
+macro_rules! float_op {
+    ($op: expr, $a: expr, $b: expr, $span: expr) => {{
+        let (a, b) = ($a, $b);
+        match $op {
+            BinOp::Add(_) => a + b,
+            BinOp::Sub(_) => a - b,
+            BinOp::Mul(_) => a * b,
+            BinOp::Div(_) => a / b,
+            BinOp::Rem(_) => a % b,
+            _ => return Err(mismatch($span)),
+        }
+    }};
+}
+
+macro_rules! compare {
+    ($op: expr, $a: expr, $b: expr) => {{
+        let (a, b) = ($a, $b);
+        match $op {
+            BinOp::Eq(_) => Some(a == b),
+            BinOp::Ne(_) => Some(a != b),
+            BinOp::Lt(_) => Some(a < b),
+            BinOp::Le(_) => Some(a <= b),
+            BinOp::Gt(_) => Some(a > b),
+            BinOp::Ge(_) => Some(a >= b),
+            _ => None,
+        }
+    }};
+}
+
+fn binary(op: &BinOp, a: ConstValue, b: ConstValue, span: Span) -> Result<ConstValue> {
+    use ConstValue::*;
+
+    // Same operator rules as for runtime values
+    let operator_error = |a: ConstValue, b: ConstValue| {
+        let (a, b) = (
+            YaslType::ScalarType(a.scalar_type()),
+            YaslType::ScalarType(b.scalar_type()),
+        );
+        match binary::result_type(op, &a, &b) {
+            Err(e) => Error::new(span, e),
+            Ok(_) => mismatch(span),
+        }
+    };
+
+    // Shift amount doesn't have to match type of the shifted value
+    match op {
+        BinOp::Shl(_) | BinOp::Shr(_) => {}
+        _ if a.scalar_type() != b.scalar_type() => return Err(operator_error(a, b)),
+        _ => {}
+    }
+    if let Err(e) = binary::result_type(
+        op,
+        &YaslType::ScalarType(a.scalar_type()),
+        &YaslType::ScalarType(b.scalar_type()),
+    ) {
+        return Err(Error::new(span, e));
+    }
+
+    if let (Bool(a), Bool(b)) = (a, b) {
+        return Ok(Bool(match op {
+            BinOp::And(_) => a && b,
+            BinOp::Or(_) => a || b,
+            BinOp::BitAnd(_) => a & b,
+            BinOp::BitOr(_) => a | b,
+            BinOp::BitXor(_) | BinOp::Ne(_) => a != b,
+            BinOp::Eq(_) => a == b,
+            _ => return Err(mismatch(span)),
+        }));
+    }
+
+    let (a, b) = match op {
+        BinOp::Shl(_) | BinOp::Shr(_) => (a, b.cast(&a.scalar_type(), span)?),
+        _ => (a, b),
+    };
+
+    let cmp = match (a, b) {
+        (Int(a), Int(b)) => compare!(op, a, b),
+        (UInt(a), UInt(b)) => compare!(op, a, b),
+        (Float32(a), Float32(b)) => compare!(op, a, b),
+        (Float64(a), Float64(b)) => compare!(op, a, b),
+        _ => None,
+    };
+    if let Some(cmp) = cmp {
+        return Ok(Bool(cmp));
+    }
+
+    Ok(match (a, b) {
+        (Int(a), Int(b)) => Int(int_op!(op, a, b, span)),
+        (UInt(a), UInt(b)) => UInt(int_op!(op, a, b, span)),
+        (Float32(a), Float32(b)) => Float32(float_op!(op, a, b, span)),
+        (Float64(a), Float64(b)) => Float64(float_op!(op, a, b, span)),
+        _ => return Err(mismatch(span)),
+    })
+}
+
+/// Builtin math functions that can be evaluated at compile time
+fn call(builtin: &BuiltinFn, args: &[ConstValue], span: Span) -> Result<Option<ConstValue>> {
+    use ConstValue::*;
+
+    fn float(args: &[ConstValue], f: impl Fn(f64) -> f64) -> Option<ConstValue> {
+        match args {
+            [Float32(v)] => Some(Float32(f(*v as f64) as f32)),
+            [Float64(v)] => Some(Float64(f(*v))),
+            _ => None,
+        }
+    }
+
+    // Arity is checked during validation, calls that don't fit are left to it
+    if args.len() != builtin.params.len() {
+        return Ok(None);
+    }
+    let types: Vec<_> = args
+        .iter()
+        .map(|a| Some(YaslType::ScalarType(a.scalar_type())))
+        .collect();
+    if let Some((_, message)) = builtin.mismatched_arg(&types) {
+        return Err(Error::new(span, message));
+    }
+    let name = builtin.name;
+
+    let pick = |a: ConstValue, b: ConstValue, max: bool| -> Result<ConstValue> {
+        let greater = binary(&BinOp::Gt(Default::default()), a, b, span)? == Bool(true);
+        Ok(if greater == max { a } else { b })
+    };
+
+    let v = match (name, args) {
+        ("abs", [Int(v)]) => Some(Int(v.checked_abs().ok_or_else(|| overflow(span))?)),
+        ("abs", _) => float(args, f64::abs),
+        ("min", [a, b]) => Some(pick(*a, *b, false)?),
+        ("max", [a, b]) => Some(pick(*a, *b, true)?),
+        ("clamp", [v, lo, hi]) => Some(pick(pick(*v, *lo, true)?, *hi, false)?),
+        ("pow", [a, b]) => match (a, b) {
+            (Float32(a), Float32(b)) => Some(Float32(a.powf(*b))),
+            (Float64(a), Float64(b)) => Some(Float64(a.powf(*b))),
+            _ => None,
+        },
+        ("sqrt", _) => float(args, f64::sqrt),
+        ("floor", _) => float(args, f64::floor),
+        ("ceil", _) => float(args, f64::ceil),
+        ("round", _) => float(args, f64::round),
+        ("sin", _) => float(args, f64::sin),
+        ("cos", _) => float(args, f64::cos),
+        ("tan", _) => float(args, f64::tan),
+        ("exp", _) => float(args, f64::exp),
+        ("log", _) => float(args, f64::ln),
+        _ => return Ok(None),
+    };
+
+    v.map(Some).ok_or_else(|| {
+        Error::new(
+            span,
+            format!("Unexpected arguments of {}() in constant expression", name),
+        )
+    })
+}
+
+impl YaslExprLineScope {
+    /// Value of the expression when it only depends on literals and constants
+    pub fn eval(&self, consts: &Consts) -> Result<Option<ConstValue>> {
+        Ok(self.fold(consts, None)?.map(|f| f.value))
+    }
+
+    /// Like `eval`, unsuffixed literals get the expected type when they can have it
+    pub fn fold(
+        &self,
+        consts: &Consts,
+        expected: Option<&YaslScalarType>,
+    ) -> Result<Option<Folded>> {
+        use YaslExprLineScope::*;
+
+        macro_rules! some {
+            ($e: expr) => {
+                match $e? {
+                    Some(v) => v,
+                    None => return Ok(None),
+                }
+            };
+        }
+        macro_rules! fold {
+            ($e: expr, $expected: expr) => {
+                some!($e.fold(consts, $expected))
+            };
+        }
+        let typed = |value| {
+            Ok(Some(Folded {
+                value,
+                untyped: false,
+            }))
+        };
+
+        match self {
+            Lit(l) => lit(l.lit(), expected),
+            // Variants keep their enum type, they only become integers through `as`
+            Ident(i) if i.is_variant() => Ok(None),
+            Ident(i) => Ok(consts.get(&i.name()).map(|value| Folded {
+                value,
+                untyped: false,
+            })),
+            Unary(u) => {
+                let v = fold!(u.expr(), expected);
+                Ok(Some(Folded {
+                    value: unary(u.op(), v.value, u.span())?,
+                    untyped: v.untyped,
+                }))
+            }
+            Binary(b) => {
+                let op = b.op();
+                let (values, untyped) = match op {
+                    // Shift amount doesn't take type of the shifted value
+                    BinOp::Shl(_) | BinOp::Shr(_) => {
+                        let v = fold!(b.left(), expected);
+                        let amount = fold!(b.right(), None);
+                        (vec![v.value, amount.value], v.untyped)
+                    }
+                    // Operands of comparisons don't have the type of the result
+                    BinOp::Eq(_)
+                    | BinOp::Ne(_)
+                    | BinOp::Lt(_)
+                    | BinOp::Le(_)
+                    | BinOp::Gt(_)
+                    | BinOp::Ge(_) => {
+                        some!(Self::fold_all(&[b.left(), b.right()], consts, None))
+                    }
+                    _ => some!(Self::fold_all(&[b.left(), b.right()], consts, expected)),
+                };
+                Ok(Some(Folded {
+                    value: binary(op, values[0], values[1], b.span())?,
+                    untyped,
+                }))
+            }
+            Cast(c) => {
+                let v = match c.expr() {
+                    Ident(i) if i.is_variant() => match (consts.get(&i.name()), c.ty()) {
+                        (
+                            Some(v),
+                            YaslType::ScalarType(YaslScalarType::Int | YaslScalarType::UInt),
+                        ) => v,
+                        _ => return Ok(None),
+                    },
+                    e => fold!(e, None).value,
+                };
+                match c.ty() {
+                    YaslType::ScalarType(s) => typed(v.cast(s, c.span())?),
+                    _ => Ok(None),
+                }
+            }
+            Call(c) => {
+                // User functions are never evaluated, also when they have the name of a builtin
+                let builtin = match c.builtin() {
+                    Some(b) if !consts.is_function(&c.name()) => b,
+                    _ => return Ok(None),
+                };
+                let args: Vec<_> = c.args().collect();
+                let (args, untyped) = some!(Self::fold_all(&args, consts, expected));
+                Ok(call(builtin, &args, c.span())?.map(|value| Folded { value, untyped }))
+            }
+            Paren(p) => p.expr().fold(consts, expected),
+            Ternary(t) => {
+                let cond = fold!(t.cond(), None).value;
+                // Branch that isn't taken still has to have the type of the other one
+                let (values, untyped) = some!(Self::fold_all(
+                    &[t.then_expr(), t.else_expr()],
+                    consts,
+                    expected
+                ));
+                let (then_ty, else_ty) = (values[0].scalar_type(), values[1].scalar_type());
+                if then_ty != else_ty {
+                    return Err(Error::new(
+                        t.else_expr().span(),
+                        format!(
+                            "`if` and `else` have different types, {} and {}",
+                            Glsl::from(&then_ty),
+                            Glsl::from(&else_ty)
+                        ),
+                    ));
+                }
+                let value = match cond {
+                    ConstValue::Bool(true) => values[0],
+                    ConstValue::Bool(false) => values[1],
+                    _ => return Ok(None),
+                };
+                Ok(Some(Folded { value, untyped }))
+            }
+            Field(_) | Index(_) | BuiltinVar(_) => Ok(None),
+        }
+    }
+
+    /// Values of expressions that have to be of one type, untyped ones take the type of a typed one
+    fn fold_all(
+        exprs: &[&Self],
+        consts: &Consts,
+        expected: Option<&YaslScalarType>,
+    ) -> Result<Option<(Vec<ConstValue>, bool)>> {
+        let mut values = Vec::new();
+        for e in exprs.iter() {
+            match e.fold(consts, expected)? {
+                Some(v) => values.push(v),
+                None => return Ok(None),
+            }
+        }
+
+        let typed = values
+            .iter()
+            .find(|v| !v.untyped)
+            .map(|v| v.value.scalar_type());
+        if let Some(ty) = typed {
+            for (v, e) in values.iter_mut().zip(exprs.iter()) {
+                if v.untyped && v.value.scalar_type() != ty {
+                    match e.fold(consts, Some(&ty))? {
+                        Some(f) => *v = f,
+                        None => return Ok(None),
+                    }
+                }
+            }
+        }
+
+        let untyped = values.iter().all(|v| v.untyped);
+        Ok(Some((
+            values.into_iter().map(|v| v.value).collect(),
+            untyped,
+        )))
+    }
+}
@@ -1,15 +1,19 @@
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
 
 use proc_macro2::Span;
 use syn::{spanned::Spanned, Error, ExprIf, Result};
 
-use crate::glsl::Glsl;
-use crate::glsl::GlslFragment;
-use crate::glsl::GlslLine;
+use crate::ir;
 
 use super::YaslExprFunctionScope;
 use super::YaslExprLineScope;
+use crate::validation::ValidationContext;
 use crate::yasl_block::YaslBlock;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::{YaslScalarType, YaslType};
 
 #[derive(Debug)]
 pub struct YaslExprIf {
@@ -22,34 +26,75 @@ impl YaslExprIf {
     pub fn span(&self) -> Span {
         self.if_token.span()
     }
-}
-
-impl From<&YaslExprIf> for Glsl {
-    fn from(expr: &YaslExprIf) -> Glsl {
-        let mut elements = Vec::new();
-
-        elements.push(Glsl::Line(GlslLine {
-            span: Some(expr.if_token.span()),
-            ends_with_semi: false,
-            glsl_string: format!("if({})", Glsl::from(&*expr.cond),),
-        }));
-
-        elements.push(Glsl::from(&*expr.then_branch));
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        self.cond.attempt_type_anotation(idents);
+        self.cond
+            .infer_type(&YaslType::ScalarType(YaslScalarType::Bool));
+        self.then_branch.attempt_type_anotation(idents);
+        if let Some((_, e)) = &mut self.else_branch {
+            e.attempt_type_anotation(idents);
+        }
+    }
+    pub fn assigned_type(&self) -> Option<YaslType> {
+        self.then_branch
+            .assigned_type()
+            .or_else(|| self.else_branch.as_ref()?.1.assigned_type())
+    }
+    pub fn fixed_assigned_type(&self) -> Option<YaslType> {
+        self.then_branch
+            .fixed_assigned_type()
+            .or_else(|| self.else_branch.as_ref()?.1.fixed_assigned_type())
+    }
+    /// Both branches return, `if` without `else` can always be skipped
+    pub fn always_returns(&self) -> bool {
+        self.then_branch.always_returns()
+            && self
+                .else_branch
+                .as_ref()
+                .is_some_and(|(_, e)| e.always_returns())
+    }
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        let non_uniform = non_uniform || !self.cond.is_uniform();
+        self.then_branch.returns_non_uniformly(non_uniform)
+            || self
+                .else_branch
+                .as_ref()
+                .is_some_and(|(_, e)| e.returns_non_uniformly(non_uniform))
+    }
+    pub fn set_assigned_type(&mut self, target: &YaslIdent, ty: &YaslType) {
+        self.then_branch.set_assigned_type(target, ty);
+        if let Some((_, e)) = &mut self.else_branch {
+            e.set_assigned_type(target, ty);
+        }
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        self.cond.validate(ctx)?;
 
-        if let Some(expr) = &expr.else_branch {
-            let el = Glsl::Line(GlslLine {
-                span: Some(expr.0.span()),
-                ends_with_semi: false,
-                glsl_string: "else".into(),
-            });
-            let block = (&*expr.1).into();
-            let fragment = GlslFragment {
-                elements: vec![el, block],
-            };
-            elements.push(Glsl::Fragment(fragment));
+        let ctx = ctx.branch(self.cond.is_uniform());
+        self.then_branch.validate(&ctx)?;
+        if let Some((_, e)) = &self.else_branch {
+            e.validate(&ctx)?;
         }
+        Ok(())
+    }
+}
 
-        Glsl::Fragment(GlslFragment { elements })
+impl TryFrom<&YaslExprIf> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprIf) -> Result<ir::Stmt> {
+        let otherwise = match &expr.else_branch {
+            Some((token, e)) => Some(ir::Else {
+                body: Box::new((&**e).try_into()?),
+                span: token.span(),
+            }),
+            None => None,
+        };
+        Ok(ir::Stmt::If {
+            cond: (&*expr.cond).try_into()?,
+            then: (&*expr.then_branch).try_into()?,
+            otherwise,
+            span: expr.span(),
+        })
     }
 }
 
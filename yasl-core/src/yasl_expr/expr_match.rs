@@ -0,0 +1,476 @@
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Error, ExprMatch, Pat, RangeLimits, Result};
+
+use crate::ir;
+use crate::validation::ValidationContext;
+use crate::yasl_const::{ConstValue, Consts};
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::{Typed, YaslScalarType, YaslType};
+
+use super::{YaslExprFunctionScope, YaslExprLineScope};
+
+/// Single pattern of a match arm
+#[derive(Debug)]
+enum YaslPat {
+    Value(Span, i64),
+    /// Inclusive range
+    Range(Span, i64, i64),
+    Wild(Span),
+}
+
+impl YaslPat {
+    fn span(&self) -> Span {
+        match self {
+            YaslPat::Value(s, _) | YaslPat::Range(s, _, _) | YaslPat::Wild(s) => *s,
+        }
+    }
+
+    fn int(expr: &syn::Expr) -> Result<i64> {
+        // Range of the value is checked against type of the scrutinee later
+        if let syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(i),
+            ..
+        }) = expr
+        {
+            return i.base10_parse();
+        }
+        match Consts::default().eval(expr)? {
+            Some(ConstValue::Int(v)) => Ok(v as i64),
+            Some(ConstValue::UInt(v)) => Ok(v as i64),
+            _ => Err(Error::new(
+                expr.span(),
+                "Expected integer literal or constant",
+            )),
+        }
+    }
+
+    fn parse(pat: Pat, out: &mut Vec<YaslPat>) -> Result<()> {
+        let span = pat.span();
+        match pat {
+            Pat::Lit(l) => out.push(YaslPat::Value(span, Self::int(&l.expr)?)),
+            Pat::Range(r) => {
+                let lo = Self::int(&r.lo)?;
+                let hi = Self::int(&r.hi)?;
+                let hi = match r.limits {
+                    RangeLimits::Closed(_) => hi,
+                    RangeLimits::HalfOpen(_) => hi - 1,
+                };
+                if lo > hi {
+                    return Err(Error::new(span, "Range is empty"));
+                }
+                out.push(YaslPat::Range(span, lo, hi));
+            }
+            Pat::Or(o) => {
+                for p in o.cases.into_iter() {
+                    Self::parse(p, out)?;
+                }
+            }
+            Pat::Wild(_) => out.push(YaslPat::Wild(span)),
+            _ => {
+                return Err(Error::new(
+                    span,
+                    "Expected literal, range, constant or _ pattern",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Condition used when match is lowered to if-chain
+    fn condition(&self, scrutinee: &ir::Expr, scalar: &YaslScalarType) -> ir::Expr {
+        let boolean = YaslType::ScalarType(YaslScalarType::Bool);
+        let compare = |op, v| {
+            let value = ir::Expr::literal(literal(v, scalar), self.span());
+            ir::Expr::binary(op, scrutinee.clone(), value, boolean.clone())
+        };
+        match *self {
+            YaslPat::Value(_, v) => compare(ir::BinOp::Eq, v),
+            YaslPat::Range(_, lo, hi) => ir::Expr::binary(
+                ir::BinOp::And,
+                compare(ir::BinOp::Ge, lo),
+                compare(ir::BinOp::Le, hi),
+                boolean.clone(),
+            ),
+            YaslPat::Wild(span) => ir::Expr::literal(ir::Literal::Bool(true), span),
+        }
+    }
+}
+
+/// Pattern value as a literal of the scrutinee type, range is checked during validation
+fn literal(v: i64, scalar: &YaslScalarType) -> ir::Literal {
+    match scalar {
+        YaslScalarType::UInt => ir::Literal::UInt(v as u32),
+        _ => ir::Literal::Int(v as i32),
+    }
+}
+
+#[derive(Debug)]
+struct YaslMatchArm {
+    pats: Vec<YaslPat>,
+    body: YaslExprFunctionScope,
+}
+
+impl YaslMatchArm {
+    fn is_wild(&self) -> bool {
+        self.pats.iter().any(|p| matches!(p, YaslPat::Wild(_)))
+    }
+}
+
+/// `match` on integers, lowered to `switch` or to if-chain when ranges are used
+#[derive(Debug)]
+pub struct YaslExprMatch {
+    match_token: syn::token::Match,
+    expr: Box<YaslExprLineScope>,
+    arms: Vec<YaslMatchArm>,
+}
+
+impl YaslExprMatch {
+    pub fn span(&self) -> Span {
+        self.match_token.span
+    }
+
+    fn scalar(&self) -> Option<YaslScalarType> {
+        match self.expr.get_type() {
+            Some(YaslType::ScalarType(s)) => Some(s),
+            Some(YaslType::Enum(_)) => Some(YaslScalarType::Int),
+            _ => None,
+        }
+    }
+
+    fn has_ranges(&self) -> bool {
+        self.arms
+            .iter()
+            .flat_map(|a| a.pats.iter())
+            .any(|p| matches!(p, YaslPat::Range(_, _, _)))
+    }
+
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        self.expr.attempt_type_anotation(idents);
+        for arm in self.arms.iter_mut() {
+            arm.body.attempt_type_anotation(idents);
+        }
+    }
+
+    pub fn assigned_type(&self) -> Option<YaslType> {
+        self.arms.iter().find_map(|a| a.body.assigned_type())
+    }
+
+    pub fn fixed_assigned_type(&self) -> Option<YaslType> {
+        self.arms.iter().find_map(|a| a.body.fixed_assigned_type())
+    }
+
+    pub fn set_assigned_type(&mut self, target: &YaslIdent, ty: &YaslType) {
+        for arm in self.arms.iter_mut() {
+            arm.body.set_assigned_type(target, ty);
+        }
+    }
+
+    /// Every arm returns, match is exhaustive
+    pub fn always_returns(&self) -> bool {
+        self.arms.iter().all(|a| a.body.always_returns())
+    }
+
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        let non_uniform = non_uniform || !self.expr.is_uniform();
+        self.arms
+            .iter()
+            .any(|a| a.body.returns_non_uniformly(non_uniform))
+    }
+
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        self.expr.validate(ctx)?;
+
+        let (min, max) = match self.scalar() {
+            Some(YaslScalarType::Int) => (i32::MIN as i64, i32::MAX as i64),
+            Some(YaslScalarType::UInt) => (0, u32::MAX as i64),
+            _ => {
+                return Err(Error::new(
+                    self.expr.span(),
+                    "match is only supported on i32, u32 and enums",
+                ))
+            }
+        };
+
+        let mut covered: Vec<(i64, i64)> = Vec::new();
+        let mut matched: Vec<&YaslPat> = Vec::new();
+        let mut wild = false;
+
+        for arm in self.arms.iter() {
+            if wild {
+                return Err(Error::new(arm.pats[0].span(), "Unreachable match arm"));
+            }
+            for pat in arm.pats.iter() {
+                let (lo, hi) = match *pat {
+                    YaslPat::Value(_, v) => (v, v),
+                    YaslPat::Range(_, lo, hi) => (lo, hi),
+                    YaslPat::Wild(_) => {
+                        wild = true;
+                        continue;
+                    }
+                };
+                if lo < min || hi > max {
+                    return Err(Error::new(pat.span(), "Pattern is out of range"));
+                }
+                // Every value is matched by one pattern, so a switch never has duplicate labels
+                if let Some((first, (a, b))) = matched
+                    .iter()
+                    .zip(covered.iter())
+                    .find(|(_, (a, b))| *a <= hi && lo <= *b)
+                {
+                    let message = if lo == hi {
+                        format!("{} is already matched", lo)
+                    } else {
+                        format!("{}..={} is already matched", lo.max(*a), hi.min(*b))
+                    };
+                    let mut err = Error::new(pat.span(), message);
+                    err.combine(Error::new(first.span(), "First matched here"));
+                    return Err(err);
+                }
+                matched.push(pat);
+                covered.push((lo, hi));
+            }
+        }
+
+        // Enums only have to cover their variants
+        if let (false, Some(YaslType::Enum(name))) = (wild, self.expr.get_type()) {
+            let variants = ctx.enums.get(&name).cloned().unwrap_or_default();
+            for (variant, v) in variants {
+                let v = v as i64;
+                if !covered.iter().any(|(lo, hi)| *lo <= v && v <= *hi) {
+                    return Err(Error::new(
+                        self.span(),
+                        format!("Non-exhaustive match, {}::{} is not covered", name, variant),
+                    ));
+                }
+            }
+        } else if !wild {
+            covered.sort();
+            let mut next = min;
+            for (lo, hi) in covered {
+                if lo > next {
+                    break;
+                }
+                next = next.max(hi + 1);
+            }
+            if next <= max {
+                return Err(Error::new(
+                    self.span(),
+                    format!(
+                        "Non-exhaustive match, {} is not covered, add `_ =>` arm",
+                        next
+                    ),
+                ));
+            }
+        }
+
+        let ctx = ctx.branch(self.expr.is_uniform());
+        for arm in self.arms.iter() {
+            arm.body.validate(&ctx)?;
+        }
+        Ok(())
+    }
+
+    fn switch(&self, scalar: &YaslScalarType) -> Result<ir::Stmt> {
+        let mut cases = Vec::new();
+        for arm in self.arms.iter() {
+            cases.push(ir::Case {
+                labels: arm
+                    .pats
+                    .iter()
+                    .map(|pat| ir::Label {
+                        value: match *pat {
+                            YaslPat::Value(_, v) => Some(literal(v, scalar)),
+                            _ => None,
+                        },
+                        span: pat.span(),
+                    })
+                    .collect(),
+                body: Box::new((&arm.body).try_into()?),
+            });
+        }
+
+        Ok(ir::Stmt::Switch {
+            value: (&*self.expr).try_into()?,
+            cases,
+            span: self.span(),
+        })
+    }
+
+    fn if_chain(&self, scalar: &YaslScalarType) -> Result<ir::Stmt> {
+        // Scrutinee is evaluated once
+        let ty = YaslType::ScalarType(scalar.clone());
+        let symbol = ir::Symbol {
+            name: "match".into(),
+            shadow: 0,
+        };
+        let scrutinee = ir::Expr::new(ir::ExprKind::Var(symbol.clone()), ty.clone(), self.span());
+
+        // Built from the last arm, each arm is the else branch of the one before
+        let mut chain: Option<(Span, ir::Stmt)> = None;
+        for (i, arm) in self.arms.iter().enumerate().rev() {
+            let span = arm.pats[0].span();
+            let body: ir::Stmt = (&arm.body).try_into()?;
+            let stmt = if arm.is_wild() && i > 0 {
+                body
+            } else {
+                let cond = arm
+                    .pats
+                    .iter()
+                    .map(|p| p.condition(&scrutinee, scalar))
+                    .reduce(|a, b| {
+                        let ty = a.ty.clone();
+                        ir::Expr::binary(ir::BinOp::Or, a, b, ty)
+                    })
+                    .expect("match arm has a pattern");
+                ir::Stmt::If {
+                    cond,
+                    then: into_block(body, span),
+                    otherwise: chain.map(|(span, e)| ir::Else {
+                        body: Box::new(e),
+                        span,
+                    }),
+                    span,
+                }
+            };
+            chain = Some((span, stmt));
+        }
+
+        let mut stmts = vec![ir::Stmt::Local {
+            symbol,
+            ty,
+            constant: false,
+            value: Some((&*self.expr).try_into()?),
+            span: self.span(),
+        }];
+        stmts.extend(chain.map(|(_, stmt)| stmt));
+        Ok(ir::Stmt::Block(ir::Block {
+            stmts,
+            span: self.span(),
+        }))
+    }
+}
+
+fn into_block(stmt: ir::Stmt, span: Span) -> ir::Block {
+    match stmt {
+        ir::Stmt::Block(b) => b,
+        stmt => ir::Block {
+            stmts: vec![stmt],
+            span,
+        },
+    }
+}
+
+impl TryFrom<&YaslExprMatch> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprMatch) -> Result<ir::Stmt> {
+        let scalar = expr.scalar().unwrap_or(YaslScalarType::Int);
+        if expr.has_ranges() {
+            expr.if_chain(&scalar)
+        } else {
+            expr.switch(&scalar)
+        }
+    }
+}
+
+impl TryFrom<ExprMatch> for YaslExprMatch {
+    type Error = Error;
+    fn try_from(m: ExprMatch) -> Result<Self> {
+        let mut arms = Vec::new();
+        for arm in m.arms.into_iter() {
+            if let Some((if_token, _)) = arm.guard {
+                return Err(Error::new(if_token.span, "Match guards are not supported"));
+            }
+            let mut pats = Vec::new();
+            YaslPat::parse(arm.pat, &mut pats)?;
+            arms.push(YaslMatchArm {
+                pats,
+                body: (*arm.body).try_into()?,
+            });
+        }
+
+        Ok(Self {
+            match_token: m.match_token,
+            expr: Box::new((*m.expr).try_into()?),
+            arms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// Errors of a vertex shader with `let y = <matched>;` in main, `x` is an i32 and `e` an enum
+    fn errors(matched: &str) -> Vec<String> {
+        let source = format!(
+            "enum E {{ A, B = 4, C }}\nfn main() {{ let x = 3; let e = E::A; let y = {}; }}",
+            matched
+        );
+        match compile(&source, &CompileOptions::default()) {
+            Ok(_) => Vec::new(),
+            Err(d) => d.errors.into_iter().map(|e| e.message).collect(),
+        }
+    }
+
+    #[test]
+    fn exhaustive_matches() {
+        assert!(errors("match x { 0 => 1, 1..=5 => 2, _ => 3 }").is_empty());
+        assert!(errors("match e { E::A => 1, E::B | E::C => 2 }").is_empty());
+        assert!(errors("match x as u32 { 0..=4294967295 => 1 }").is_empty());
+        assert!(errors("match x { -2147483648..=-1 => 0, 0..=2147483647 => 1 }").is_empty());
+    }
+
+    #[test]
+    fn non_exhaustive_matches() {
+        assert_eq!(
+            errors("match e { E::A => 1, E::C => 2 }"),
+            ["Non-exhaustive match, E::B is not covered"]
+        );
+        assert_eq!(
+            errors("match x { 0 => 1, 1..=5 => 2 }"),
+            ["Non-exhaustive match, -2147483648 is not covered, add `_ =>` arm"]
+        );
+        assert_eq!(
+            errors("match x { -2147483648..=5 => 1, 7..=2147483647 => 2 }"),
+            ["Non-exhaustive match, 6 is not covered, add `_ =>` arm"]
+        );
+    }
+
+    #[test]
+    fn overlapping_patterns() {
+        assert_eq!(
+            errors("match x { 0..=5 => 1, 3 => 2, _ => 0 }"),
+            ["3 is already matched", "First matched here"]
+        );
+        assert_eq!(
+            errors("match x { 0..=5 => 1, 4..=9 => 2, _ => 0 }"),
+            ["4..=5 is already matched", "First matched here"]
+        );
+        assert_eq!(
+            errors("match e { E::A | E::A => 1, _ => 0 }"),
+            ["0 is already matched", "First matched here"]
+        );
+        assert_eq!(
+            errors("match x { _ => 1, 2 => 0 }"),
+            ["Unreachable match arm"]
+        );
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!(
+            errors("match x as u32 { -1 => 1, _ => 0 }"),
+            ["Pattern is out of range"]
+        );
+        assert_eq!(errors("match x { 5..=1 => 1, _ => 0 }"), ["Range is empty"]);
+        assert_eq!(
+            errors("match 1.0 { _ => 0 }"),
+            ["match is only supported on i32, u32 and enums"]
+        );
+    }
+}
@@ -1,12 +1,21 @@
-use std::convert::{TryFrom, TryInto};
+use std::{collections::HashMap, convert::TryFrom};
 
 use proc_macro2::Span;
 use syn::{spanned::Spanned, Error, ExprReturn, Result};
 
-use crate::glsl::Glsl;
+use crate::ir;
+use crate::validation::ValidationContext;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::{Typed, YaslType};
 
 use super::YaslExprLineScope;
 
+/// Binding typed with the return type of the enclosing function, inserted during type anotation.
+/// It's a keyword, so it can't be hidden by locals
+pub fn return_target(span: Span) -> syn::Ident {
+    syn::Ident::new("return", span)
+}
+
 #[derive(Debug)]
 pub struct YaslExprReturn {
     return_token: syn::token::Return,
@@ -16,17 +25,41 @@ impl YaslExprReturn {
     pub fn span(&self) -> Span {
         self.return_token.span()
     }
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        if let Some(e) = &mut self.expr {
+            e.attempt_type_anotation(idents);
+            let target = YaslIdent::from(return_target(self.return_token.span));
+            if let Some(ty) = idents.get(&target.key()).and_then(|i| i.get_type()) {
+                e.infer_type(&ty);
+            }
+        }
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        match (&self.expr, &ctx.returns) {
+            (None, YaslType::Void) => Ok(()),
+            (None, ty) => Err(Error::new(
+                self.span(),
+                format!("Expected return value of type {}", ty.name()),
+            )),
+            (Some(e), YaslType::Void) => Err(Error::new(
+                e.span(),
+                "Function doesn't return a value, declare the return type with `->`",
+            )),
+            (Some(e), ty) => {
+                e.validate(ctx)?;
+                ctx.check_value(ty, e)
+            }
+        }
+    }
 }
 
-impl From<&YaslExprReturn> for Glsl {
-    fn from(expr: &YaslExprReturn) -> Glsl {
-        let glsl_expr = if let Some(expr) = &expr.expr {
-            format!("{}", Glsl::from(&**expr))
-        } else {
-            String::new()
-        };
-
-        Glsl::Expr(format!("return {}", glsl_expr))
+impl TryFrom<&YaslExprReturn> for ir::Stmt {
+    type Error = Error;
+    fn try_from(expr: &YaslExprReturn) -> Result<ir::Stmt> {
+        Ok(ir::Stmt::Return {
+            value: expr.expr.as_deref().map(ir::Expr::try_from).transpose()?,
+            span: expr.span(),
+        })
     }
 }
 
@@ -1,30 +1,70 @@
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 
 use proc_macro2::Span;
 use syn::{spanned::Spanned, Error, ExprField, Result};
 
-use crate::glsl::Glsl;
+use crate::ir;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::{Typed, YaslType, YaslVecType};
 
-use super::YaslExprLineScope;
+use super::{lowered_type, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprField {
     base: Box<YaslExprLineScope>,
     member: syn::Ident,
+    ty: Option<YaslType>,
 }
 impl YaslExprField {
     pub fn span(&self) -> Span {
         self.member.span()
     }
+    pub fn base(&self) -> &YaslExprLineScope {
+        &self.base
+    }
+    pub fn base_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.base
+    }
+    /// Struct members are looked up as `Struct::member`, vectors are swizzled
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        self.base.attempt_type_anotation(idents);
+
+        let member = self.member.to_string();
+        self.ty = match self.base.get_type() {
+            Some(YaslType::Struct(name)) => idents
+                .get(&format!("{}::{}", name, member))
+                .and_then(|i| i.get_type()),
+            Some(YaslType::Vec(v)) => {
+                let s = v.component().clone();
+                match member.len() {
+                    1 => Some(YaslType::ScalarType(s)),
+                    2 => Some(YaslType::Vec(YaslVecType::Vec2(s))),
+                    3 => Some(YaslType::Vec(YaslVecType::Vec3(s))),
+                    4 => Some(YaslType::Vec(YaslVecType::Vec4(s))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+    }
 }
 
-impl From<&YaslExprField> for Glsl {
-    fn from(expr: &YaslExprField) -> Glsl {
-        Glsl::Expr(format!(
-            "{}.{}",
-            Glsl::from(&*expr.base),
-            expr.member.to_string()
-        ))
+impl Typed for YaslExprField {
+    fn get_type(&self) -> Option<YaslType> {
+        self.ty.clone()
+    }
+}
+
+impl TryFrom<&YaslExprField> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprField) -> Result<ir::Expr> {
+        let kind = ir::ExprKind::Field {
+            base: Box::new((&*expr.base).try_into()?),
+            member: expr.member.to_string(),
+        };
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
     }
 }
 
@@ -34,10 +74,14 @@ impl TryFrom<ExprField> for YaslExprField {
         let base = Box::new((*f.base).try_into()?);
 
         let member = match f.member {
-            syn::Member::Named(i) => i.into(),
+            syn::Member::Named(i) => i,
             syn::Member::Unnamed(i) => Err(Error::new(i.span(), "Expected Ident"))?,
         };
 
-        Ok(Self { base, member })
+        Ok(Self {
+            base,
+            member,
+            ty: None,
+        })
     }
 }
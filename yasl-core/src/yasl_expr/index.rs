@@ -0,0 +1,63 @@
+use std::convert::{TryFrom, TryInto};
+
+use proc_macro2::Span;
+use syn::{Error, ExprIndex, Result};
+
+use crate::ir;
+use crate::yasl_type::{Typed, YaslType};
+
+use super::{lowered_type, YaslExprLineScope};
+
+#[derive(Debug)]
+pub struct YaslExprIndex {
+    base: Box<YaslExprLineScope>,
+    index: Box<YaslExprLineScope>,
+}
+impl YaslExprIndex {
+    pub fn span(&self) -> Span {
+        self.base.span()
+    }
+    pub fn base(&self) -> &YaslExprLineScope {
+        &self.base
+    }
+    pub fn base_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.base
+    }
+    pub fn index(&self) -> &YaslExprLineScope {
+        &self.index
+    }
+    pub fn index_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.index
+    }
+}
+
+impl Typed for YaslExprIndex {
+    fn get_type(&self) -> Option<YaslType> {
+        match self.base.get_type()? {
+            YaslType::Array(ty, _) => Some(*ty),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&YaslExprIndex> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprIndex) -> Result<ir::Expr> {
+        let kind = ir::ExprKind::Index {
+            base: Box::new((&*expr.base).try_into()?),
+            index: Box::new((&*expr.index).try_into()?),
+        };
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
+    }
+}
+
+impl TryFrom<ExprIndex> for YaslExprIndex {
+    type Error = Error;
+    fn try_from(i: ExprIndex) -> Result<Self> {
+        Ok(Self {
+            base: Box::new((*i.expr).try_into()?),
+            index: Box::new((*i.index).try_into()?),
+        })
+    }
+}
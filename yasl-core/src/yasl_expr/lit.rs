@@ -1,38 +1,116 @@
 use std::convert::TryFrom;
 
-use syn::{Error, Result};
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Error, Result};
 
 use syn::ExprLit;
 
-use quote::quote;
-
 use crate::{
-    glsl::Glsl,
+    ir,
     yasl_type::{Typed, YaslScalarType, YaslType},
 };
 
 #[derive(Debug)]
 pub struct YaslExprLit {
     lit: syn::Lit,
+    ty: YaslScalarType,
+    /// Literals without suffix take their type from context, like `let x: u32 = 0`
+    suffixed: bool,
 }
 
-impl Typed for YaslExprLit {
-    fn get_type(&self) -> Option<YaslType> {
-        use syn::Lit;
-        println!("{:#?}", self.lit);
-        match self.lit {
-            Lit::Float(_) => Some(YaslType::ScalarType(YaslScalarType::Float32)),
-            Lit::Int(_) => Some(YaslType::ScalarType(YaslScalarType::Int)),
-            Lit::Bool(_) => Some(YaslType::ScalarType(YaslScalarType::Bool)),
+impl YaslExprLit {
+    pub fn span(&self) -> Span {
+        self.lit.span()
+    }
+    pub fn lit(&self) -> &syn::Lit {
+        &self.lit
+    }
+
+    /// Negated integer as a single literal, 2147483648 alone doesn't fit in i32
+    pub fn negative(u: &syn::ExprUnary) -> Option<Self> {
+        match (&u.op, &*u.expr) {
+            (
+                syn::UnOp::Neg(_),
+                syn::Expr::Lit(ExprLit {
+                    lit: syn::Lit::Int(i),
+                    ..
+                }),
+            ) if matches!(i.suffix(), "" | "i32") => Some(Self {
+                lit: syn::Lit::Int(syn::LitInt::new(&format!("-{}", i), i.span())),
+                ty: YaslScalarType::Int,
+                suffixed: !i.suffix().is_empty(),
+            }),
             _ => None,
         }
     }
+
+    pub fn is_suffixed(&self) -> bool {
+        self.suffixed
+    }
+
+    /// Type expected by the context, ignored when it doesn't fit the literal
+    pub fn infer_type(&mut self, ty: &YaslScalarType) {
+        use YaslScalarType::*;
+        if self.suffixed {
+            return;
+        }
+        match (&self.lit, ty) {
+            (syn::Lit::Int(_), Int | UInt) | (syn::Lit::Float(_), Float32 | Float64) => {
+                self.ty = ty.clone()
+            }
+            _ => {}
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let out_of_range =
+            |ty: &str| Error::new(self.span(), format!("Literal out of range for {}", ty));
+        match (&self.lit, &self.ty) {
+            (syn::Lit::Int(i), YaslScalarType::Int) => {
+                i.base10_parse::<i32>().map_err(|_| out_of_range("i32"))?;
+            }
+            (syn::Lit::Int(i), YaslScalarType::UInt) => {
+                i.base10_parse::<u32>().map_err(|_| out_of_range("u32"))?;
+            }
+            (syn::Lit::Float(f), YaslScalarType::Float32)
+                if !f.base10_parse::<f32>()?.is_finite() =>
+            {
+                return Err(out_of_range("f32"));
+            }
+            (syn::Lit::Float(f), YaslScalarType::Float64)
+                if !f.base10_parse::<f64>()?.is_finite() =>
+            {
+                return Err(out_of_range("f64"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Typed for YaslExprLit {
+    fn get_type(&self) -> Option<YaslType> {
+        Some(YaslType::ScalarType(self.ty.clone()))
+    }
 }
 
-impl From<&YaslExprLit> for Glsl {
-    fn from(expr: &YaslExprLit) -> Glsl {
-        let lit = &expr.lit;
-        Glsl::Expr(quote!(#lit).to_string())
+/// Range of the value is checked during validation
+impl From<&YaslExprLit> for ir::Expr {
+    fn from(expr: &YaslExprLit) -> ir::Expr {
+        use ir::Literal;
+        let lit = match (&expr.lit, &expr.ty) {
+            (syn::Lit::Bool(b), _) => Literal::Bool(b.value),
+            (syn::Lit::Int(i), YaslScalarType::UInt) => {
+                Literal::UInt(i.base10_parse().unwrap_or_default())
+            }
+            (syn::Lit::Int(i), _) => Literal::Int(i.base10_parse().unwrap_or_default()),
+            (syn::Lit::Float(f), YaslScalarType::Float64) => {
+                Literal::Double(f.base10_parse().unwrap_or_default())
+            }
+            (syn::Lit::Float(f), _) => Literal::Float(f.base10_parse().unwrap_or_default()),
+            _ => unreachable!("only int, float and bool literals are parsed"),
+        };
+        ir::Expr::literal(lit, expr.span())
     }
 }
 
@@ -40,6 +118,86 @@ impl TryFrom<ExprLit> for YaslExprLit {
     type Error = Error;
     fn try_from(l: ExprLit) -> Result<Self> {
         let lit = l.lit;
-        Ok(Self { lit })
+        let (ty, suffixed) = match &lit {
+            syn::Lit::Int(i) => match i.suffix() {
+                "" => (YaslScalarType::Int, false),
+                "i32" => (YaslScalarType::Int, true),
+                "u32" => (YaslScalarType::UInt, true),
+                s => return Err(unsupported_suffix(lit.span(), s)),
+            },
+            syn::Lit::Float(f) => match f.suffix() {
+                "" => (YaslScalarType::Float32, false),
+                "f32" => (YaslScalarType::Float32, true),
+                "f64" => (YaslScalarType::Float64, true),
+                s => return Err(unsupported_suffix(lit.span(), s)),
+            },
+            syn::Lit::Bool(_) => (YaslScalarType::Bool, true),
+            _ => {
+                return Err(Error::new(
+                    lit.span(),
+                    "Only int, float and bool literals are supported",
+                ))
+            }
+        };
+        Ok(Self { lit, ty, suffixed })
+    }
+}
+
+fn unsupported_suffix(span: Span, suffix: &str) -> Error {
+    Error::new(
+        span,
+        format!(
+            "Literal suffix {} is not supported, use i32, u32, f32 or f64",
+            suffix
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// GLSL of `stmt` in main, or the first error
+    fn compile_stmt(stmt: &str) -> Result<String, String> {
+        let source = format!("fn main() {{ {} }}", stmt);
+        let output = compile(&source, &CompileOptions::default())
+            .map_err(|d| d.errors[0].message.clone())?;
+        let glsl = &output.stages[0].glsl;
+        let line = glsl.lines().find(|l| l.contains(" a = ")).unwrap_or("");
+        Ok(line.trim().to_string())
+    }
+
+    #[test]
+    fn literals_at_the_limits() {
+        let valid = [
+            ("let a = -2147483648;", "int a = -2147483648;"),
+            ("let a = 2147483647;", "int a = 2147483647;"),
+            ("let a = 4294967295u32;", "uint a = 4294967295u;"),
+            ("let a: u32 = 0;", "uint a = 0u;"),
+            ("let a = 3.4e38;", "float a = 3.4e38;"),
+            ("let a = 1e39f64;", "double a = 1e39lf;"),
+        ];
+        for (stmt, glsl) in valid.iter() {
+            assert_eq!(compile_stmt(stmt), Ok(glsl.to_string()), "{}", stmt);
+        }
+    }
+
+    #[test]
+    fn literals_out_of_range() {
+        let errors = [
+            ("let a = 2147483648;", "Literal out of range for i32"),
+            ("let a = -2147483649;", "Literal out of range for i32"),
+            ("let a: u32 = 4294967296;", "Literal out of range for u32"),
+            ("let a: u32 = -1;", "Literal out of range for u32"),
+            ("let a = 1e39;", "Literal out of range for f32"),
+            ("let a = 1e309f64;", "Literal out of range for f64"),
+            (
+                "let a = 1u8;",
+                "Literal suffix u8 is not supported, use i32, u32, f32 or f64",
+            ),
+        ];
+        for (stmt, message) in errors.iter() {
+            assert_eq!(compile_stmt(stmt), Err(message.to_string()), "{}", stmt);
+        }
     }
 }
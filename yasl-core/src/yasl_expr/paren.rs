@@ -0,0 +1,55 @@
+use std::convert::{TryFrom, TryInto};
+
+use proc_macro2::Span;
+use syn::{Error, Expr, Result};
+
+use crate::ir;
+use crate::yasl_type::{Typed, YaslType};
+
+use super::YaslExprLineScope;
+
+/// `(a + b)`, also used for blocks without statements like `{ a + b }`
+#[derive(Debug)]
+pub struct YaslExprParen {
+    span: Span,
+    expr: Box<YaslExprLineScope>,
+}
+
+impl YaslExprParen {
+    pub fn new(span: Span, expr: Expr) -> Result<Self> {
+        Ok(Self {
+            span,
+            expr: Box::new(expr.try_into()?),
+        })
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn expr(&self) -> &YaslExprLineScope {
+        &self.expr
+    }
+    pub fn expr_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.expr
+    }
+}
+
+impl Typed for YaslExprParen {
+    fn get_type(&self) -> Option<YaslType> {
+        self.expr.get_type()
+    }
+}
+
+/// Grouping is implicit in the IR, backends add parentheses where precedence requires them
+impl TryFrom<&YaslExprParen> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprParen) -> Result<ir::Expr> {
+        (&*expr.expr).try_into()
+    }
+}
+
+impl TryFrom<syn::ExprParen> for YaslExprParen {
+    type Error = Error;
+    fn try_from(p: syn::ExprParen) -> Result<Self> {
+        Self::new(p.paren_token.span, *p.expr)
+    }
+}
@@ -0,0 +1,114 @@
+//! Lowering of blocks and if-else chains that produce values
+use syn::{spanned::Spanned, Block, Error, Expr, Result, Stmt, Token};
+
+/// Value that can be written as a single GLSL expression,
+/// if-else chain or block without statements before the tail
+pub fn is_inline(expr: &Expr) -> bool {
+    fn block_is_inline(b: &Block) -> bool {
+        matches!(b.stmts.as_slice(), [Stmt::Expr(e)] if is_inline(e))
+    }
+    match expr {
+        Expr::If(i) => {
+            block_is_inline(&i.then_branch)
+                && i.else_branch.as_ref().map(|(_, e)| is_inline(e)) == Some(true)
+        }
+        Expr::Block(b) => block_is_inline(&b.block),
+        Expr::Paren(p) => is_inline(&p.expr),
+        Expr::Match(_) => false,
+        _ => true,
+    }
+}
+
+/// If without a final else whose branches end with statements, like `if c { return 1.0; }`,
+/// produces no value and stays a statement
+pub fn is_statement_if(expr: &Expr) -> bool {
+    match expr {
+        Expr::If(i) => {
+            !matches!(i.then_branch.stmts.last(), Some(Stmt::Expr(e)) if !matches!(e, Expr::Return(_)))
+                && i.else_branch
+                    .as_ref()
+                    .is_none_or(|(_, e)| is_statement_if(e))
+        }
+        _ => false,
+    }
+}
+
+/// Replaces every value produced by if-else branches and blocks with `f(value)`
+/// For example `if c { a } else { b }` becomes `if c { x = a; } else { x = b; }`
+pub fn map_tails(expr: Expr, f: &dyn Fn(Expr) -> Expr) -> Result<Expr> {
+    Ok(match expr {
+        Expr::If(mut i) => {
+            let else_branch = match i.else_branch.take() {
+                Some((else_token, e)) => (else_token, Box::new(map_tails(*e, f)?)),
+                None => {
+                    return Err(Error::new(
+                        i.if_token.span,
+                        "if used as a value needs an else branch",
+                    ))
+                }
+            };
+            i.then_branch = map_block_tail(i.then_branch, f)?;
+            i.else_branch = Some(else_branch);
+            Expr::If(i)
+        }
+        Expr::Block(mut b) => {
+            b.block = map_block_tail(b.block, f)?;
+            Expr::Block(b)
+        }
+        Expr::Match(mut m) => {
+            for arm in m.arms.iter_mut() {
+                let body = std::mem::replace(&mut *arm.body, Expr::Verbatim(Default::default()));
+                *arm.body = map_tails(body, f)?;
+            }
+            Expr::Match(m)
+        }
+        Expr::Paren(p) => map_tails(*p.expr, f)?,
+        e => f(e),
+    })
+}
+
+/// Branches ending with a statement, like `return`, are left as they are
+fn map_block_tail(mut block: Block, f: &dyn Fn(Expr) -> Expr) -> Result<Block> {
+    if let Some(Stmt::Expr(e)) = block.stmts.last() {
+        let span = e.span();
+        if let Some(Stmt::Expr(e)) = block.stmts.pop() {
+            block
+                .stmts
+                .push(Stmt::Semi(map_tails(e, f)?, Token![;](span)));
+        }
+    }
+    Ok(block)
+}
+
+/// `return value`, already returning expressions are left untouched
+pub fn return_expr(e: Expr) -> Expr {
+    if let Expr::Return(_) = e {
+        return e;
+    }
+    let span = e.span();
+    Expr::Return(syn::ExprReturn {
+        attrs: Vec::new(),
+        return_token: Token![return](span),
+        expr: Some(Box::new(e)),
+    })
+}
+
+/// `place = value`
+pub fn assign_expr(place: &Expr, e: Expr) -> Expr {
+    Expr::Assign(syn::ExprAssign {
+        attrs: Vec::new(),
+        left: Box::new(place.clone()),
+        eq_token: Token![=](e.span()),
+        right: Box::new(e),
+    })
+}
+
+/// `place op= value`
+pub fn assign_op_expr(place: &Expr, op: syn::BinOp, e: Expr) -> Expr {
+    Expr::AssignOp(syn::ExprAssignOp {
+        attrs: Vec::new(),
+        left: Box::new(place.clone()),
+        op,
+        right: Box::new(e),
+    })
+}
@@ -0,0 +1,118 @@
+use std::convert::{TryFrom, TryInto};
+
+use proc_macro2::Span;
+use syn::{Error, Expr, ExprIf, Result, Stmt};
+
+use crate::ir;
+use crate::yasl_type::{Typed, YaslType};
+
+use super::{lowered_type, YaslExprLineScope};
+
+/// `if c { a } else { b }` used as a value, lowered to `c ? a : b`
+#[derive(Debug)]
+pub struct YaslExprTernary {
+    if_token: syn::token::If,
+    cond: Box<YaslExprLineScope>,
+    then_expr: Box<YaslExprLineScope>,
+    else_expr: Box<YaslExprLineScope>,
+}
+
+impl YaslExprTernary {
+    pub fn span(&self) -> Span {
+        self.if_token.span
+    }
+    pub fn cond(&self) -> &YaslExprLineScope {
+        &self.cond
+    }
+    pub fn then_expr(&self) -> &YaslExprLineScope {
+        &self.then_expr
+    }
+    pub fn else_expr(&self) -> &YaslExprLineScope {
+        &self.else_expr
+    }
+    pub fn cond_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.cond
+    }
+    pub fn then_expr_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.then_expr
+    }
+    pub fn else_expr_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.else_expr
+    }
+}
+
+impl YaslExprTernary {
+    /// Unsuffixed literal in one branch takes the type of the other, like operands of binary operators
+    pub fn infer_branch_types(&mut self) {
+        if let Some(ty) = self.then_expr.get_type() {
+            self.else_expr.infer_type(&ty);
+        }
+        if let Some(ty) = self.else_expr.get_type() {
+            self.then_expr.infer_type(&ty);
+        }
+    }
+    /// Both branches produce a value of the same type
+    pub fn validate(&self) -> Result<()> {
+        match (self.then_expr.get_type(), self.else_expr.get_type()) {
+            (Some(a), Some(b)) if a != b => Err(Error::new(
+                self.else_expr.span(),
+                format!(
+                    "`if` and `else` have different types, {} and {}",
+                    a.name(),
+                    b.name()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Typed for YaslExprTernary {
+    fn get_type(&self) -> Option<YaslType> {
+        self.then_expr.get_type()
+    }
+}
+
+impl TryFrom<&YaslExprTernary> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprTernary) -> Result<ir::Expr> {
+        let kind = ir::ExprKind::Select {
+            cond: Box::new((&*expr.cond).try_into()?),
+            then: Box::new((&*expr.then_expr).try_into()?),
+            otherwise: Box::new((&*expr.else_expr).try_into()?),
+        };
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
+    }
+}
+
+impl TryFrom<ExprIf> for YaslExprTernary {
+    type Error = Error;
+    fn try_from(i: ExprIf) -> Result<Self> {
+        let span = i.if_token.span;
+        let then_expr = match i.then_branch.stmts.into_iter().next() {
+            Some(Stmt::Expr(e)) => e,
+            _ => return Err(Error::new(span, "Expected value of if branch")),
+        };
+        let else_expr: Expr = match i.else_branch {
+            // Final `else { b }` doesn't need parentheses
+            Some((_, e)) => match *e {
+                Expr::Block(mut b) if b.block.stmts.len() == 1 => match b.block.stmts.pop() {
+                    Some(Stmt::Expr(e)) => e,
+                    _ => return Err(Error::new(span, "Expected value of else branch")),
+                },
+                e => e,
+            },
+            None => {
+                return Err(Error::new(span, "if used as a value needs an else branch"));
+            }
+        };
+
+        Ok(Self {
+            if_token: i.if_token,
+            cond: Box::new((*i.cond).try_into()?),
+            then_expr: Box::new(then_expr.try_into()?),
+            else_expr: Box::new(else_expr.try_into()?),
+        })
+    }
+}
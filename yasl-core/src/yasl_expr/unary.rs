@@ -1,13 +1,15 @@
 use std::convert::{TryFrom, TryInto};
 
 use proc_macro2::Span;
-use syn::{spanned::Spanned, Error, ExprUnary, Result};
+use syn::{spanned::Spanned, Error, ExprUnary, Result, UnOp};
 
 use quote::quote;
 
-use crate::glsl::Glsl;
+use crate::ir;
+use crate::validation::{operand_type, ValidationContext};
+use crate::yasl_type::{Typed, YaslScalarType, YaslType};
 
-use super::YaslExprLineScope;
+use super::{lowered_type, YaslExprLineScope};
 
 #[derive(Debug)]
 pub struct YaslExprUnary {
@@ -18,14 +20,76 @@ impl YaslExprUnary {
     pub fn span(&self) -> Span {
         self.op.span()
     }
+    pub fn op(&self) -> &syn::UnOp {
+        &self.op
+    }
+    pub fn expr(&self) -> &YaslExprLineScope {
+        &self.expr
+    }
+    pub fn expr_mut(&mut self) -> &mut YaslExprLineScope {
+        &mut self.expr
+    }
+    /// `*param`, reads or writes what a `&mut` parameter refers to
+    pub fn is_deref(&self) -> bool {
+        matches!(self.op, UnOp::Deref(_))
+    }
 }
 
-impl From<&YaslExprUnary> for Glsl {
-    fn from(expr: &YaslExprUnary) -> Glsl {
-        let op = expr.op;
+impl YaslExprUnary {
+    /// `-` on signed and float values, `!` on bools and integers, where it's bitwise,
+    /// `*` on `&mut` parameters
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        use YaslScalarType::*;
+        let op = self.op;
         let op = quote!(#op).to_string();
+        if self.is_deref() {
+            return match &*self.expr {
+                YaslExprLineScope::Ident(i) if ctx.references.contains(&i.to_string()) => Ok(()),
+                e => Err(Error::new(
+                    e.span(),
+                    "Only &mut parameters can be dereferenced",
+                )),
+            };
+        }
+        let ty = operand_type(&self.expr)?;
+        let allowed = match (&self.op, ty.scalar()) {
+            (_, _) if matches!(ty, YaslType::Enum(_)) => false,
+            (UnOp::Neg(_), Some(Int | Float32 | Float64)) => true,
+            (UnOp::Not(_), Some(Bool | Int | UInt)) => true,
+            _ => false,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::new(
+                self.span(),
+                format!("`{}` is not supported on {}", op, ty.name()),
+            ))
+        }
+    }
+}
 
-        Glsl::Expr(format!("{}{}", op, Glsl::from(&*expr.expr)))
+impl Typed for YaslExprUnary {
+    fn get_type(&self) -> Option<YaslType> {
+        self.expr.get_type()
+    }
+}
+
+impl TryFrom<&YaslExprUnary> for ir::Expr {
+    type Error = Error;
+    fn try_from(expr: &YaslExprUnary) -> Result<ir::Expr> {
+        let op = match expr.op {
+            UnOp::Neg(_) => ir::UnOp::Neg,
+            UnOp::Not(_) => ir::UnOp::Not,
+            // `&mut` parameters are `inout` in GLSL, they're used like any other variable
+            UnOp::Deref(_) => return (&*expr.expr).try_into(),
+        };
+        let kind = ir::ExprKind::Unary {
+            op,
+            expr: Box::new((&*expr.expr).try_into()?),
+        };
+        let ty = lowered_type(expr.get_type(), expr.span())?;
+        Ok(ir::Expr::new(kind, ty, expr.span()))
     }
 }
 
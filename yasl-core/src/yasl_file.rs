@@ -1,13 +1,22 @@
 use std::{collections::HashMap, convert::TryInto};
 
+use proc_macro2::Span;
 use syn::parse::ParseStream;
-use syn::Result;
+use syn::{Error, Result};
 
-use crate::glsl::{Glsl, GlslFragment};
+use crate::glsl::Glsl;
+use crate::ir;
+use crate::outline::Declaration;
 use crate::{
+    options::CompileOptions,
+    reflection::Reflection,
+    reflection::{MemberReflection, StructReflection},
+    validation::ValidationContext,
+    yasl_const::Consts,
     yasl_ident::YaslIdent,
-    yasl_item::YaslItem,
-    yasl_type::{Typed, YaslType},
+    yasl_item::{LayoutKind, YaslItem, YaslItemLayout, YaslItemShared},
+    yasl_program::ShaderStage,
+    yasl_type::{std430, std430::StructFields},
 };
 
 #[derive(Debug)]
@@ -15,39 +24,252 @@ pub struct YaslFile {
     items: Vec<YaslItem>,
 }
 
-impl YaslFile {}
-
-impl From<YaslFile> for Glsl {
-    fn from(mut file: YaslFile) -> Glsl {
-        let mut elements = Vec::new();
-
-        let mut global_idents: HashMap<String, YaslType> = HashMap::new();
-        for i in file.items.iter_mut() {
+impl YaslFile {
+    /// Items shared between multiple shader stages are placed in front of stage items
+    pub fn prepend_shared(&mut self, shared: &[syn::Item]) -> Result<()> {
+        let mut items = Vec::new();
+        for i in shared.iter() {
+            items.push(i.clone().try_into()?);
+        }
+        items.append(&mut self.items);
+        self.items = items;
+        Ok(())
+    }
+    /// Items only see items declared above them
+    pub fn attempt_type_anotation(&mut self) {
+        let mut global_idents: HashMap<String, YaslIdent> = HashMap::new();
+        for i in self.items.iter_mut() {
             for ident in i.update_idents() {
-                global_idents.insert(ident.to_string(), ident.get_type().unwrap().clone());
+                global_idents.insert(ident.key(), ident);
+            }
+            if let YaslItem::Struct(s) = i {
+                for field in s.field_idents() {
+                    global_idents.insert(format!("{}::{}", s.name(), field.name()), field);
+                }
             }
 
             i.attempt_type_anotation(&global_idents);
+        }
+    }
+    pub fn validate(&self, stage: ShaderStage, options: &CompileOptions) -> Result<()> {
+        let mut ctx = self.context(stage, options)?;
+        for i in self.items.iter() {
+            ctx.calls_uniform_only.set(false);
+            i.validate(&ctx)?;
+            // Functions are declared before use, so callers are validated after this
+            if let (YaslItem::Fn(f), true) = (i, ctx.calls_uniform_only.get()) {
+                ctx.uniform_functions.insert(f.get_ident().to_string());
+            }
+        }
+
+        let has_main = self.items.iter().any(|i| match i {
+            YaslItem::Fn(f) => f.is_entry_point(),
+            _ => false,
+        });
+        if !has_main {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("{} stage is missing fn main()", stage.name()),
+            ));
+        }
 
-            elements.push(i.into());
+        Ok(())
+    }
+
+    /// Functions compiled both as Rust and as shader code, they can't depend on any stage
+    pub fn validate_shared(&self, options: &CompileOptions) -> Result<()> {
+        let mut ctx = self.context(ShaderStage::Vertex, options)?;
+        ctx.shared = true;
+        for i in self.items.iter() {
+            match i {
+                YaslItem::Fn(f) => f.validate_shared()?,
+                _ => {
+                    return Err(Error::new(
+                        Span::call_site(),
+                        "Only functions can be shared",
+                    ))
+                }
+            }
+            i.validate(&ctx)?;
         }
+        Ok(())
+    }
 
-        Glsl::Fragment(GlslFragment { elements })
+    /// Declarations of every item, visible to function bodies
+    fn context<'a>(
+        &self,
+        stage: ShaderStage,
+        options: &'a CompileOptions,
+    ) -> Result<ValidationContext<'a>> {
+        let mut ctx = ValidationContext::new(stage, options);
+        ctx.structs = self.structs();
+        ctx.enums = self
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                YaslItem::Enum(e) => Some((e.name(), e.values())),
+                _ => None,
+            })
+            .collect();
+
+        let mut push_constants = self
+            .layouts()
+            .into_iter()
+            .filter(|l| *l.kind() == LayoutKind::PushConstant);
+        if let (Some(_), Some(second)) = (push_constants.next(), push_constants.next()) {
+            return Err(Error::new(
+                second.ident().span(),
+                "Only one push constant block is allowed per stage",
+            ));
+        }
+
+        let mut specialization_ids: HashMap<u32, Span> = HashMap::new();
+        for i in self.items.iter() {
+            match i {
+                YaslItem::Const(c) => {
+                    if let Some((span, id)) = c.specialization_id() {
+                        if let Some(first) = specialization_ids.insert(id, span) {
+                            let mut err = Error::new(
+                                span,
+                                format!("Specialization constant id {} is already used", id),
+                            );
+                            err.combine(Error::new(first, "First used here"));
+                            return Err(err);
+                        }
+                    }
+                    ctx.declare(&c.get_ident(), Some("constant"));
+                }
+                YaslItem::Shared(s) => {
+                    ctx.atomic_targets.insert(s.get_ident().to_string());
+                }
+                YaslItem::Layout(l) => match l.kind() {
+                    LayoutKind::Buffer => {
+                        ctx.atomic_targets.insert(l.ident().to_string());
+                    }
+                    LayoutKind::Input => ctx.declare(l.ident(), Some("input")),
+                    LayoutKind::PushConstant => ctx.declare(l.ident(), Some("push constant")),
+                    LayoutKind::Output => {}
+                },
+                YaslItem::Static(s) if !s.is_mutable() => {
+                    ctx.declare(&s.get_ident(), Some("static"))
+                }
+                YaslItem::Enum(e) => {
+                    for v in e.variants() {
+                        ctx.declare(&v, Some("enum variant"));
+                    }
+                }
+                YaslItem::Fn(f) => {
+                    ctx.functions.insert(f.get_ident().to_string(), f.params());
+                }
+                _ => {}
+            }
+        }
+        Ok(ctx)
+    }
+    pub fn structs(&self) -> StructFields {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                YaslItem::Struct(s) => Some((s.name(), s.fields())),
+                _ => None,
+            })
+            .collect()
+    }
+    pub fn reflect(&self) -> Reflection {
+        let structs = self.structs();
+        let mut reflection = Reflection::default();
+        for i in self.items.iter() {
+            match i {
+                YaslItem::Layout(l) => match l.kind() {
+                    LayoutKind::Input => reflection.inputs.push(l.reflect()),
+                    LayoutKind::Output => reflection.outputs.push(l.reflect()),
+                    LayoutKind::Buffer => reflection.buffers.push(l.reflect_buffer()),
+                    LayoutKind::PushConstant => {
+                        reflection.push_constant = l.reflect_push_constant(&structs)
+                    }
+                },
+                YaslItem::Struct(s) => {
+                    if let Some((members, size)) = std430::struct_layout(&s.fields(), &structs) {
+                        reflection.structs.push(StructReflection {
+                            name: s.name(),
+                            size,
+                            members: members
+                                .into_iter()
+                                .map(|m| MemberReflection {
+                                    rust_ty: m.ty.rust_type(),
+                                    ty: Glsl::from(&m.ty).to_string(),
+                                    name: m.name,
+                                    offset: m.offset,
+                                    size: m.size,
+                                })
+                                .collect(),
+                        });
+                    }
+                }
+                YaslItem::Enum(e) => reflection.enums.push(e.reflect()),
+                YaslItem::Fn(f) if f.is_entry_point() => {
+                    reflection.workgroup_size = f.workgroup_size();
+                }
+                YaslItem::Const(c) => {
+                    if let Some(s) = c.reflect_specialization() {
+                        reflection.specialization_constants.push(s);
+                    }
+                }
+                _ => {}
+            }
+        }
+        reflection
+    }
+    pub fn declarations(&self) -> Vec<Declaration> {
+        self.items.iter().flat_map(|i| i.declarations()).collect()
+    }
+    pub fn layouts(&self) -> Vec<&YaslItemLayout> {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                YaslItem::Layout(l) => Some(l),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl YaslFile {
+    /// Lowers every item of the validated file, in declaration order
+    pub fn lower(&self, stage: ShaderStage) -> Result<ir::Module> {
+        let mut module = ir::Module::new(stage);
+        for i in self.items.iter() {
+            module.items.push(match i {
+                YaslItem::Struct(s) => ir::Item::Struct(s.into()),
+                YaslItem::Enum(e) => ir::Item::Enum(e.into()),
+                YaslItem::Const(c) => ir::Item::Constant(c.try_into()?),
+                YaslItem::Layout(l) => ir::Item::Layout(l.into()),
+                YaslItem::Shared(s) => ir::Item::Shared(s.into()),
+                YaslItem::Static(s) => ir::Item::Global(s.try_into()?),
+                YaslItem::Fn(f) => ir::Item::Function(f.try_into()?),
+            });
+        }
+        Ok(module)
     }
 }
 
 syn::custom_keyword!(layout);
+syn::custom_keyword!(shared);
 
 impl YaslFile {
-    pub fn parse(ps: ParseStream) -> Result<Self> {
+    /// Constant expressions are folded while parsing, so items see constants declared above them
+    pub fn parse(ps: ParseStream, mut consts: Consts) -> Result<Self> {
         let mut items = Vec::new();
         while !ps.is_empty() {
             if ps.peek(layout) {
-                use crate::yasl_item::YaslItemLayout;
-                let layout = ps.parse::<YaslItemLayout>()?;
+                let layout = YaslItemLayout::parse(ps, &consts)?;
                 items.push(layout.into());
+            } else if ps.peek(shared) {
+                let shared = YaslItemShared::parse(ps, &consts)?;
+                items.push(shared.into());
             } else {
-                let item: syn::Item = ps.parse()?;
+                let mut item: syn::Item = ps.parse()?;
+                consts.fold_item(&mut item)?;
                 items.push(item.try_into()?);
             }
         }
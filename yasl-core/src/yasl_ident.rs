@@ -1,5 +1,7 @@
 use crate::{
     glsl::Glsl,
+    ir, naming,
+    yasl_expr::lowered_type,
     yasl_type::{Typed, YaslType},
 };
 use std::convert::{TryFrom, TryInto};
@@ -13,11 +15,70 @@ use syn::{ExprPath, PatType, Path};
 pub struct YaslIdent {
     prefix: String,
     ident: Ident,
+    /// Enum of a variant, like `Blend` in `Blend::Alpha`
+    owner: Option<String>,
+    /// Number of bindings with the same name this one shadows, see `shadow`
+    shadows: usize,
     ty: Option<YaslType>,
+    /// Parameter types of a function, unsuffixed literal arguments take their type
+    params: Option<Vec<YaslType>>,
+}
+/// GLSL name, see `naming::glsl_name`
+impl std::fmt::Display for YaslIdent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.native() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", naming::glsl_name(&self.name(), self.shadows)),
+        }
+    }
 }
 impl YaslIdent {
-    pub fn to_string(&self) -> String {
-        format!("{}{}", self.prefix, self.ident.to_string())
+    /// Variant of an enum, emitted as `Enum_Variant`
+    pub fn variant(owner: &Ident, ident: Ident) -> Self {
+        Self {
+            prefix: format!("yasl_{}_", owner),
+            ident,
+            owner: Some(owner.to_string()),
+            shadows: 0,
+            ty: None,
+            params: None,
+        }
+    }
+    /// `Enum::Variant`, as opposed to a local or a constant
+    pub fn is_variant(&self) -> bool {
+        self.owner.is_some()
+    }
+    /// Name used to look the ident up in scope
+    pub fn key(&self) -> String {
+        format!("{}{}", self.prefix, self.ident)
+    }
+    /// Renames a binding that hides `prev`, `let a = 1; let a = a + 1;` declares `a` and `a_1`
+    pub fn shadow(&mut self, prev: &YaslIdent) {
+        self.shadows = self.shadows.max(prev.shadows + 1);
+    }
+    /// Reference takes the GLSL name and type of the binding it refers to, but keeps its span
+    pub fn resolve(&mut self, binding: &YaslIdent) {
+        self.prefix = binding.prefix.clone();
+        self.ident = Ident::new(&binding.ident.to_string(), self.ident.span());
+        self.owner = binding.owner.clone();
+        self.shadows = binding.shadows;
+        self.ty = binding.ty.clone();
+        self.params = binding.params.clone();
+    }
+    /// Name as written in YASL source
+    pub fn name(&self) -> String {
+        match &self.owner {
+            Some(owner) => format!("{}::{}", owner, self.ident),
+            None => self.ident.to_string(),
+        }
+    }
+    /// GLSL name written in source, like `vec3` or `glsl::gl_Position`, these aren't renamed
+    pub fn native(&self) -> Option<String> {
+        if self.prefix.starts_with("yasl_") {
+            None
+        } else {
+            Some(self.key())
+        }
     }
     pub fn span(&self) -> Span {
         self.ident.span()
@@ -25,6 +86,12 @@ impl YaslIdent {
     pub fn set_type(&mut self, ty: YaslType) {
         self.ty = Some(ty);
     }
+    pub fn set_params(&mut self, params: Vec<YaslType>) {
+        self.params = Some(params);
+    }
+    pub fn params(&self) -> Option<&[YaslType]> {
+        self.params.as_deref()
+    }
 }
 
 impl Typed for YaslIdent {
@@ -39,6 +106,27 @@ impl From<&YaslIdent> for Glsl {
     }
 }
 
+impl From<&YaslIdent> for ir::Symbol {
+    fn from(ident: &YaslIdent) -> ir::Symbol {
+        ir::Symbol {
+            name: ident.name(),
+            shadow: ident.shadows,
+        }
+    }
+}
+
+impl TryFrom<&YaslIdent> for ir::Expr {
+    type Error = Error;
+    fn try_from(ident: &YaslIdent) -> Result<ir::Expr> {
+        let kind = match ident.native() {
+            Some(name) => ir::ExprKind::Native(name),
+            None => ir::ExprKind::Var(ident.into()),
+        };
+        let ty = lowered_type(ident.get_type(), ident.span())?;
+        Ok(ir::Expr::new(kind, ty, ident.span()))
+    }
+}
+
 impl From<Ident> for YaslIdent {
     fn from(ident: Ident) -> Self {
         // TODO MOVE TIHS TO KEYWORDS MODULE
@@ -50,7 +138,10 @@ impl From<Ident> for YaslIdent {
         Self {
             prefix,
             ident,
+            owner: None,
+            shadows: 0,
             ty: None,
+            params: None,
         }
     }
 }
@@ -64,9 +155,15 @@ impl TryFrom<Path> for YaslIdent {
             if p.segments.len() == 2 {
                 let mut iter = p.segments.into_iter();
 
-                let prefix = iter.next().unwrap().ident.to_string();
+                let first = iter.next().unwrap().ident;
+                let ident = iter.next().unwrap().ident;
+
+                // Enums follow Rust naming, just like structs
+                if first.to_string().starts_with(char::is_uppercase) {
+                    return Ok(Self::variant(&first, ident));
+                }
 
-                let prefix = match prefix.as_str() {
+                let prefix = match first.to_string().as_str() {
                     "glsl" => "",
                     "f32" => "",
                     "f64" => "d",
@@ -75,19 +172,20 @@ impl TryFrom<Path> for YaslIdent {
                     "u32" => "u",
                     _ => {
                         return Err(Error::new(
-                            prefix.span(),
+                            first.span(),
                             "Only 'glsl,f32,f64,bool,i32,u32' prefix is allowed",
                         ))
                     }
                 }
                 .into();
 
-                let ident = iter.next().unwrap().ident;
-
                 Self {
                     prefix,
                     ident,
+                    owner: None,
+                    shadows: 0,
                     ty: None,
+                    params: None,
                 }
             } else {
                 return Err(Error::new(p.span(), "Expected Ident"));
@@ -112,7 +210,7 @@ impl TryFrom<PatType> for YaslIdent {
         if let syn::Pat::Ident(i) = *t.pat {
             Ok(i.ident.into())
         } else {
-            return Err(Error::new(t.pat.span(), "Expected Ident"));
+            Err(Error::new(t.pat.span(), "Expected Ident"))
         }
     }
 }
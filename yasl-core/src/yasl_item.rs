@@ -4,25 +4,40 @@ use std::{
 };
 use syn::{spanned::Spanned, Error, Item, Result};
 
-use crate::{
-    glsl::Glsl,
-    yasl_ident::YaslIdent,
-    yasl_type::{Typed, YaslType},
-};
+use crate::outline::{Declaration, DeclarationKind};
+use crate::yasl_type::Typed;
+use crate::{validation::ValidationContext, yasl_ident::YaslIdent};
 
 mod static_it;
 use static_it::YaslItemStatic;
 
+mod const_it;
+pub use const_it::{parse_specialization, YaslItemConst};
+
 mod func;
+pub use func::ParamKind;
 use func::YaslItemFn;
 
+mod shared;
+pub use shared::YaslItemShared;
+
+mod struct_it;
+pub use struct_it::YaslItemStruct;
+
+mod enum_it;
+pub use enum_it::YaslItemEnum;
+
 mod layout;
-pub use layout::YaslItemLayout;
+pub use layout::{Interpolation, LayoutKind, Precision, Sampling, YaslItemLayout};
 
 #[derive(Debug)]
 pub enum YaslItem {
     Static(YaslItemStatic),
+    Const(YaslItemConst),
     Layout(YaslItemLayout),
+    Shared(YaslItemShared),
+    Struct(YaslItemStruct),
+    Enum(YaslItemEnum),
     Fn(YaslItemFn),
 }
 
@@ -30,34 +45,84 @@ impl YaslItem {
     pub fn update_idents(&mut self) -> Vec<YaslIdent> {
         match self {
             YaslItem::Static(i) => vec![i.get_ident()],
+            YaslItem::Const(c) => vec![c.get_ident()],
             YaslItem::Fn(f) => vec![f.get_ident()],
-            _ => Vec::new(),
+            YaslItem::Layout(l) => vec![l.get_ident()],
+            YaslItem::Shared(s) => vec![s.get_ident()],
+            YaslItem::Struct(_) => Vec::new(),
+            YaslItem::Enum(e) => e.variants(),
         }
     }
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
+    /// Declarations with their spans, enums declare their variants too
+    pub fn declarations(&self) -> Vec<Declaration> {
+        let typed = |ident: YaslIdent, kind: DeclarationKind, keyword: &str| {
+            let ty = ident.get_type().map_or_else(String::new, |t| t.yasl_name());
+            Declaration {
+                name: ident.name(),
+                kind,
+                span: ident.span(),
+                ty: ident.get_type(),
+                detail: format!("{}{}: {}", keyword, ident.name(), ty),
+            }
+        };
         match self {
-            YaslItem::Fn(f) => f.attempt_type_anotation(idents),
-            _ => {}
+            YaslItem::Static(s) => vec![typed(s.get_ident(), DeclarationKind::Static, "static ")],
+            YaslItem::Const(c) => vec![typed(c.get_ident(), DeclarationKind::Const, "const ")],
+            YaslItem::Shared(s) => vec![typed(s.get_ident(), DeclarationKind::Shared, "shared ")],
+            YaslItem::Layout(l) => {
+                let keyword = match l.kind() {
+                    LayoutKind::PushConstant => "layout<push_constant> ".to_string(),
+                    kind => format!("layout<{}, {}> ", kind.name(), l.pos()),
+                };
+                vec![typed(l.get_ident(), DeclarationKind::Layout, &keyword)]
+            }
+            YaslItem::Fn(f) => vec![Declaration {
+                name: f.get_ident().name(),
+                kind: DeclarationKind::Function,
+                ty: None,
+                span: f.get_ident().span(),
+                detail: f.signature(),
+            }],
+            YaslItem::Struct(s) => vec![Declaration {
+                name: s.name(),
+                kind: DeclarationKind::Struct,
+                ty: None,
+                span: s.span(),
+                detail: format!("struct {}", s.name()),
+            }],
+            YaslItem::Enum(e) => {
+                let mut out = vec![Declaration {
+                    name: e.name(),
+                    kind: DeclarationKind::Enum,
+                    ty: None,
+                    span: e.span(),
+                    detail: format!("enum {}", e.name()),
+                }];
+                out.extend(e.variants().into_iter().map(|v| Declaration {
+                    name: v.name(),
+                    kind: DeclarationKind::EnumVariant,
+                    span: v.span(),
+                    ty: v.get_type(),
+                    detail: v.name(),
+                }));
+                out
+            }
         }
     }
-}
-
-impl From<&YaslItem> for Glsl {
-    fn from(item: &YaslItem) -> Glsl {
-        match item {
-            YaslItem::Static(s) => s.into(),
-            YaslItem::Layout(l) => l.into(),
-            YaslItem::Fn(f) => f.into(),
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        match self {
+            YaslItem::Layout(l) => l.validate(ctx),
+            YaslItem::Shared(s) => s.validate(ctx),
+            YaslItem::Struct(s) => s.validate(ctx),
+            YaslItem::Enum(e) => e.validate(),
+            YaslItem::Fn(f) => f.validate(ctx),
+            YaslItem::Static(s) => s.validate(ctx),
+            YaslItem::Const(c) => c.validate(ctx),
         }
     }
-}
-
-impl From<&mut YaslItem> for Glsl {
-    fn from(item: &mut YaslItem) -> Glsl {
-        match item {
-            YaslItem::Static(ref s) => s.into(),
-            YaslItem::Layout(ref l) => l.into(),
-            YaslItem::Fn(ref f) => f.into(),
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        if let YaslItem::Fn(f) = self {
+            f.attempt_type_anotation(idents);
         }
     }
 }
@@ -67,7 +132,10 @@ impl TryFrom<Item> for YaslItem {
     fn try_from(item: Item) -> Result<Self> {
         Ok(match item {
             Item::Static(s) => Self::Static(s.try_into()?),
+            Item::Const(c) => Self::Const(c.try_into()?),
             Item::Fn(f) => Self::Fn(f.try_into()?),
+            Item::Struct(s) => Self::Struct(s.try_into()?),
+            Item::Enum(e) => Self::Enum(e.try_into()?),
             _ => return Err(Error::new(item.span(), "Unsuported Item")),
         })
     }
@@ -78,3 +146,9 @@ impl From<YaslItemLayout> for YaslItem {
         YaslItem::Layout(layout)
     }
 }
+
+impl From<YaslItemShared> for YaslItem {
+    fn from(shared: YaslItemShared) -> Self {
+        YaslItem::Shared(shared)
+    }
+}
@@ -0,0 +1,128 @@
+use proc_macro2::Span;
+use std::convert::{TryFrom, TryInto};
+use syn::{spanned::Spanned, Attribute, Error, ItemConst, Lit, Meta, NestedMeta, Result};
+
+use crate::glsl::Glsl;
+use crate::ir;
+use crate::reflection::SpecializationReflection;
+use crate::validation::ValidationContext;
+use crate::yasl_const::Consts;
+use crate::yasl_expr::YaslExprLineScope;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::YaslType;
+
+/// Attributes that don't affect shader code, doc comments and lints like `#[allow(dead_code)]`
+fn is_ignored(attr: &Attribute) -> bool {
+    ["doc", "allow", "warn", "deny", "forbid"]
+        .iter()
+        .any(|name| attr.path.is_ident(name))
+}
+
+/// `#[specialization(id = N)]`
+pub fn parse_specialization(attrs: &[Attribute]) -> Result<Option<(Span, u32)>> {
+    let mut out = None;
+    for attr in attrs.iter() {
+        if is_ignored(attr) {
+            continue;
+        }
+        if !attr.path.is_ident("specialization") {
+            return Err(Error::new(attr.span(), "Unknown attribute"));
+        }
+        if out.is_some() {
+            return Err(Error::new(attr.span(), "Duplicated specialization"));
+        }
+
+        let expected = || Error::new(attr.span(), "Expected #[specialization(id = N)]");
+        let list = match attr.parse_meta()? {
+            Meta::List(l) if l.nested.len() == 1 => l,
+            _ => return Err(expected()),
+        };
+        let id = match &list.nested[0] {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("id") => match &nv.lit {
+                Lit::Int(i) => i.base10_parse()?,
+                _ => return Err(expected()),
+            },
+            _ => return Err(expected()),
+        };
+        out = Some((attr.span(), id));
+    }
+    Ok(out)
+}
+
+/// Compile time constant, its value is already folded into every use
+/// Specialization constants are the exception, they can be overridden when creating a pipeline
+#[derive(Debug)]
+pub struct YaslItemConst {
+    specialization: Option<(Span, u32)>,
+    ident: YaslIdent,
+    ty: YaslType,
+    expr: YaslExprLineScope,
+}
+
+impl YaslItemConst {
+    pub fn get_ident(&self) -> YaslIdent {
+        let mut ident = self.ident.clone();
+        ident.set_type(self.ty.clone());
+        ident
+    }
+    pub fn specialization_id(&self) -> Option<(Span, u32)> {
+        self.specialization
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        ctx.check_type(&self.ty, self.ident.span())
+    }
+    pub fn reflect_specialization(&self) -> Option<SpecializationReflection> {
+        let (_, id) = self.specialization?;
+        Some(SpecializationReflection {
+            name: self.ident.name(),
+            id,
+            ty: Glsl::from(&self.ty).to_string(),
+            default: self.expr.eval(&Consts::default()).ok()??,
+        })
+    }
+}
+
+impl TryFrom<&YaslItemConst> for ir::Constant {
+    type Error = Error;
+    fn try_from(item: &YaslItemConst) -> Result<ir::Constant> {
+        Ok(ir::Constant {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            value: (&item.expr).try_into()?,
+            specialization: item.specialization.map(|(_, id)| id),
+            span: item.ident.span(),
+        })
+    }
+}
+
+/// Constant declared inside of a function
+impl TryFrom<&YaslItemConst> for ir::Stmt {
+    type Error = Error;
+    fn try_from(item: &YaslItemConst) -> Result<ir::Stmt> {
+        Ok(ir::Stmt::Local {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            constant: true,
+            value: Some((&item.expr).try_into()?),
+            span: item.ident.span(),
+        })
+    }
+}
+
+impl TryFrom<ItemConst> for YaslItemConst {
+    type Error = Error;
+    fn try_from(item: ItemConst) -> Result<Self> {
+        if item.vis != syn::Visibility::Inherited {
+            return Err(Error::new(
+                item.span(),
+                "Visibility Keywords are not supported",
+            ));
+        }
+        Ok(Self {
+            specialization: parse_specialization(&item.attrs)?,
+            ident: item.ident.into(),
+            ty: (*item.ty).try_into()?,
+            expr: (*item.expr).try_into()?,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Error, ItemEnum, Result};
+
+use crate::ir;
+use crate::reflection::EnumReflection;
+use crate::yasl_const::{ConstValue, Consts};
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::YaslType;
+
+/// Fieldless enum, every variant becomes a `const int`
+/// Discriminants are already folded into literals by `Consts::fold_item`
+#[derive(Debug)]
+pub struct YaslItemEnum {
+    ident: syn::Ident,
+    variants: Vec<(syn::Ident, i32)>,
+}
+
+impl YaslItemEnum {
+    pub fn name(&self) -> String {
+        self.ident.to_string()
+    }
+    pub fn span(&self) -> proc_macro2::Span {
+        self.ident.span()
+    }
+    /// Variant idents typed with the enum, like `Blend::Alpha`
+    pub fn variants(&self) -> Vec<YaslIdent> {
+        self.variants
+            .iter()
+            .map(|(v, _)| {
+                let mut ident = YaslIdent::variant(&self.ident, v.clone());
+                ident.set_type(YaslType::Enum(self.name()));
+                ident
+            })
+            .collect()
+    }
+    /// Value of every variant, in order of declaration
+    pub fn values(&self) -> Vec<(String, i32)> {
+        self.variants
+            .iter()
+            .map(|(i, v)| (i.to_string(), *v))
+            .collect()
+    }
+    pub fn validate(&self) -> Result<()> {
+        let mut values: HashMap<i32, Span> = HashMap::new();
+        for (ident, value) in self.variants.iter() {
+            if let Some(first) = values.insert(*value, ident.span()) {
+                let mut err = Error::new(
+                    ident.span(),
+                    format!("Discriminant {} is already used", value),
+                );
+                err.combine(Error::new(first, "First used here"));
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+    pub fn reflect(&self) -> EnumReflection {
+        EnumReflection {
+            name: self.name(),
+            variants: self.values(),
+        }
+    }
+}
+
+impl From<&YaslItemEnum> for ir::Enum {
+    fn from(item: &YaslItemEnum) -> ir::Enum {
+        let variants = item
+            .variants()
+            .iter()
+            .zip(item.variants.iter())
+            .map(|(ident, (_, value))| ir::Variant {
+                symbol: ident.into(),
+                value: *value,
+                span: ident.span(),
+            })
+            .collect();
+
+        ir::Enum {
+            name: item.name(),
+            variants,
+            span: item.span(),
+        }
+    }
+}
+
+impl TryFrom<ItemEnum> for YaslItemEnum {
+    type Error = Error;
+    fn try_from(item: ItemEnum) -> Result<Self> {
+        if item.vis != syn::Visibility::Inherited {
+            return Err(Error::new(
+                item.span(),
+                "Visibility Keywords are not supported",
+            ));
+        }
+        if !item.generics.params.is_empty() {
+            return Err(Error::new(
+                item.generics.span(),
+                "Generics are not supported",
+            ));
+        }
+
+        let mut variants = Vec::new();
+        for v in item.variants.into_iter() {
+            let value = match &v.discriminant {
+                Some((_, expr)) => Consts::default().eval(expr)?,
+                None => None,
+            };
+            match value {
+                Some(ConstValue::Int(value)) => variants.push((v.ident, value)),
+                _ => return Err(Error::new(v.ident.span(), "Expected discriminant literal")),
+            }
+        }
+
+        Ok(Self {
+            ident: item.ident,
+            variants,
+        })
+    }
+}
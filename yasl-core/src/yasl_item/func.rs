@@ -1,18 +1,119 @@
+use proc_macro2::Span;
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
 };
-use syn::{spanned::Spanned, Error, FnArg, ItemFn, Result};
+use syn::{punctuated::Punctuated, spanned::Spanned, Token};
+use syn::{Attribute, Error, FnArg, ItemFn, LitInt, Result, Stmt};
 
-use crate::glsl::{Glsl, GlslFragment, GlslLine};
+use crate::ir;
+use crate::validation::ValidationContext;
 use crate::yasl_block::YaslBlock;
+use crate::yasl_expr::{return_target, tail};
 use crate::yasl_ident::YaslIdent;
+use crate::yasl_program::ShaderStage;
 use crate::yasl_type::{Typed, YaslType};
 
+/// `#[workgroup_size(x, y, z)]`, missing dimensions default to 1
+fn parse_workgroup_size(attrs: &[Attribute]) -> Result<Option<(Span, [u32; 3])>> {
+    let mut out = None;
+    for attr in attrs.iter() {
+        if !attr.path.is_ident("workgroup_size") {
+            continue;
+        }
+        if out.is_some() {
+            return Err(Error::new(attr.span(), "Duplicated workgroup_size"));
+        }
+
+        let dims = attr.parse_args_with(Punctuated::<LitInt, Token![,]>::parse_terminated)?;
+        if dims.is_empty() || dims.len() > 3 {
+            return Err(Error::new(
+                attr.span(),
+                "Expected 1 to 3 dimensions, for example #[workgroup_size(8, 8, 1)]",
+            ));
+        }
+
+        let mut size = [1; 3];
+        for (i, d) in dims.iter().enumerate() {
+            size[i] = d.base10_parse()?;
+            if size[i] == 0 {
+                return Err(Error::new(d.span(), "Workgroup size can't be 0"));
+            }
+        }
+        out = Some((attr.span(), size));
+    }
+    Ok(out)
+}
+
+/// How an argument is passed, `&mut T` is `inout` and `#[out] &mut T` is `out`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ParamKind {
+    In,
+    InOut,
+    Out,
+}
+
+#[derive(Debug)]
+struct YaslFnArg {
+    ident: YaslIdent,
+    ty: YaslType,
+    kind: ParamKind,
+    /// `mut a: f32`, a copy that can be assigned to
+    mutable: bool,
+}
+
+impl TryFrom<syn::PatType> for YaslFnArg {
+    type Error = Error;
+    fn try_from(t: syn::PatType) -> Result<Self> {
+        let (ident, mutable) = if let syn::Pat::Ident(i) = *t.pat {
+            (i.ident, i.mutability.is_some())
+        } else {
+            return Err(Error::new(t.pat.span(), "Expected Ident"));
+        };
+
+        let mut out = false;
+        for attr in t.attrs.iter() {
+            if attr.path.is_ident("out") && attr.tokens.is_empty() && !out {
+                out = true;
+            } else {
+                return Err(Error::new(attr.span(), "Expected #[out]"));
+            }
+        }
+
+        let (ty, kind) = match *t.ty {
+            syn::Type::Reference(r) => {
+                if r.mutability.is_none() {
+                    return Err(Error::new(
+                        r.and_token.span,
+                        "Only &mut references are supported",
+                    ));
+                }
+                let kind = if out {
+                    ParamKind::Out
+                } else {
+                    ParamKind::InOut
+                };
+                ((*r.elem).try_into()?, kind)
+            }
+            ty if out => return Err(Error::new(ty.span(), "#[out] parameters have to be &mut")),
+            ty => (ty.try_into()?, ParamKind::In),
+        };
+
+        Ok(Self {
+            ident: ident.into(),
+            ty,
+            kind,
+            mutable,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct YaslItemFn {
+    workgroup_size: Option<(Span, [u32; 3])>,
     ident: YaslIdent,
-    args: Vec<(YaslIdent, YaslType)>,
+    args: Vec<YaslFnArg>,
     output: YaslType,
     block: Box<YaslBlock>,
 }
@@ -21,10 +122,111 @@ impl YaslItemFn {
     pub fn get_ident(&self) -> YaslIdent {
         let mut ident = self.ident.clone();
         ident.set_type(self.output.clone());
+        ident.set_params(self.args.iter().map(|a| a.ty.clone()).collect());
         ident
     }
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
-        self.block.attempt_type_anotation(idents);
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        let mut idents = idents.clone();
+        for a in self.args.iter() {
+            let mut ident = a.ident.clone();
+            ident.set_type(a.ty.clone());
+            idents.insert(ident.key(), ident);
+        }
+        let mut ret = YaslIdent::from(return_target(self.ident.span()));
+        ret.set_type(self.output.clone());
+        idents.insert(ret.key(), ret);
+        self.block.attempt_type_anotation(&idents);
+    }
+    pub fn is_entry_point(&self) -> bool {
+        self.ident.name() == "main"
+    }
+    pub fn workgroup_size(&self) -> Option<[u32; 3]> {
+        self.workgroup_size.map(|(_, s)| s)
+    }
+    /// Written like in source, `fn add(a: f32, b: &mut f32) -> f32`
+    pub fn signature(&self) -> String {
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|a| {
+                let reference = match a.kind {
+                    ParamKind::In => "",
+                    ParamKind::InOut => "&mut ",
+                    ParamKind::Out => "#[out] &mut ",
+                };
+                format!("{}: {}{}", a.ident.name(), reference, a.ty.yasl_name())
+            })
+            .collect();
+        match self.output {
+            YaslType::Void => format!("fn {}({})", self.ident.name(), args.join(", ")),
+            ref ty => format!(
+                "fn {}({}) -> {}",
+                self.ident.name(),
+                args.join(", "),
+                ty.yasl_name()
+            ),
+        }
+    }
+    pub fn params(&self) -> Vec<(ParamKind, YaslType)> {
+        self.args.iter().map(|a| (a.kind, a.ty.clone())).collect()
+    }
+    /// Shared functions only take and return scalars and vectors by value, like plain Rust math
+    pub fn validate_shared(&self) -> Result<()> {
+        let math = |ty: &YaslType| matches!(ty, YaslType::ScalarType(_) | YaslType::Vec(_));
+        for a in self.args.iter() {
+            if a.kind != ParamKind::In || !math(&a.ty) {
+                return Err(Error::new(
+                    a.ident.span(),
+                    "Shared functions only take scalars and vectors by value",
+                ));
+            }
+        }
+        if !math(&self.output) && self.output != YaslType::Void {
+            return Err(Error::new(
+                self.ident.span(),
+                "Shared functions can only return scalars and vectors",
+            ));
+        }
+        Ok(())
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        if let Some((span, _)) = self.workgroup_size {
+            if !self.is_entry_point() || ctx.stage != ShaderStage::Compute {
+                return Err(Error::new(
+                    span,
+                    "workgroup_size is only allowed on main function of compute stage",
+                ));
+            }
+        } else if self.is_entry_point() && ctx.stage == ShaderStage::Compute {
+            return Err(Error::new(
+                self.ident.span(),
+                "Compute entry point requires #[workgroup_size(x, y, z)]",
+            ));
+        }
+
+        let mut ctx = ctx.clone();
+        for a in self.args.iter() {
+            ctx.check_type(&a.ty, a.ident.span())?;
+            let mutable = a.kind != ParamKind::In || a.mutable;
+            ctx.declare(&a.ident, if mutable { None } else { Some("argument") });
+            if a.kind != ParamKind::In {
+                ctx.references.insert(a.ident.to_string());
+            }
+        }
+        ctx.check_type(&self.output, self.ident.span())?;
+        ctx.returns = self.output.clone();
+        if self.output != YaslType::Void && !self.block.always_returns() {
+            return Err(Error::new(
+                self.block.close_span(),
+                format!(
+                    "{}() doesn't return a value on every path, expected {}",
+                    self.ident.name(),
+                    self.output.name()
+                ),
+            ));
+        }
+
+        self.block.validate(&ctx)
     }
 }
 
@@ -34,38 +236,25 @@ impl Typed for YaslItemFn {
     }
 }
 
-impl From<&YaslItemFn> for Glsl {
-    fn from(item: &YaslItemFn) -> Glsl {
-        let mut args = Vec::new();
-
-        for a in item.args.iter() {
-            args.push(format!("{} {}", Glsl::from(&a.1), Glsl::from(&a.0)));
-        }
-
-        let args_glsl = args.join(",");
-
-        let mut elements = Vec::new();
-
-        elements.push(Glsl::Line(GlslLine {
-            span: Some(item.ident.span()),
-            ends_with_semi: false,
-            glsl_string: format!(
-                "{} {}({})",
-                Glsl::from(&item.output),
-                Glsl::from(&item.ident),
-                args_glsl
-            ),
-        }));
-
-        elements.push((&*item.block).into());
-
-        // elements.push(Glsl::Line(GlslLine {
-        //     span: Some(item.ident.span()),
-        //     ends_with_semi: false,
-        //     glsl_string: "}".into(),
-        // }));
-
-        Glsl::Fragment(GlslFragment { elements })
+impl TryFrom<&YaslItemFn> for ir::Function {
+    type Error = Error;
+    fn try_from(item: &YaslItemFn) -> Result<ir::Function> {
+        Ok(ir::Function {
+            symbol: (&item.ident).into(),
+            params: item
+                .args
+                .iter()
+                .map(|a| ir::Param {
+                    symbol: (&a.ident).into(),
+                    ty: a.ty.clone(),
+                    kind: a.kind,
+                })
+                .collect(),
+            ret: item.output.clone(),
+            body: (&*item.block).try_into()?,
+            workgroup_size: item.workgroup_size(),
+            span: item.ident.span(),
+        })
     }
 }
 
@@ -89,6 +278,8 @@ impl TryFrom<ItemFn> for YaslItemFn {
 
         //TODO: Error Out On Generics
 
+        let workgroup_size = parse_workgroup_size(&f.attrs)?;
+
         let ident = f.sig.ident;
 
         let inputs = f.sig.inputs;
@@ -97,15 +288,7 @@ impl TryFrom<ItemFn> for YaslItemFn {
 
         for i in inputs.into_iter() {
             if let FnArg::Typed(t) = i {
-                let ident = if let syn::Pat::Ident(i) = *t.pat {
-                    i.ident
-                } else {
-                    return Err(Error::new(t.pat.span(), "Expected Ident"));
-                };
-
-                let ty = (*t.ty).try_into()?;
-
-                args.push((ident.into(), ty));
+                args.push(t.try_into()?);
             } else {
                 return Err(Error::new(i.span(), "Expected Type"));
             };
@@ -118,9 +301,22 @@ impl TryFrom<ItemFn> for YaslItemFn {
             syn::ReturnType::Type(_, t) => (*t).try_into()?,
         };
 
-        let block = (*f.block).try_into()?;
+        // Trailing expression is the return value, `fn add(a: f32, b: f32) -> f32 { a + b }`
+        // A path of a trailing if without else that doesn't return is reported by validate
+        let mut block = *f.block;
+        if output != YaslType::Void
+            && matches!(block.stmts.last(), Some(Stmt::Expr(e)) if !tail::is_statement_if(e))
+        {
+            if let Some(Stmt::Expr(e)) = block.stmts.pop() {
+                let span = e.span();
+                let e = tail::map_tails(e, &tail::return_expr)?;
+                block.stmts.push(Stmt::Semi(e, Token![;](span)));
+            }
+        }
+        let block = block.try_into()?;
 
         Ok(Self {
+            workgroup_size,
             ident: ident.into(),
             args,
             output,
@@ -128,3 +324,36 @@ impl TryFrom<ItemFn> for YaslItemFn {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// First error and its start line and column, None when it compiles
+    fn error(source: &str) -> Option<(String, usize, usize)> {
+        compile(source, &CompileOptions::default()).err().map(|d| {
+            let e = &d.errors[0];
+            let (start, _) = e.location.unwrap();
+            (e.message.clone(), start.line, start.column)
+        })
+    }
+
+    #[test]
+    fn missing_return_is_reported_at_the_end_of_the_body() {
+        let source = "fn f(c: bool) -> f32 {\n    if c {\n        return 1.0;\n    }\n}\nfn main() { let a = f(true); }";
+        assert_eq!(
+            error(source),
+            Some((
+                "f() doesn't return a value on every path, expected float".to_string(),
+                5,
+                0
+            ))
+        );
+
+        let source = "fn f(c: bool) -> f32 { if c { return 1.0; } else { return 2.0; } }\nfn main() { let a = f(true); }";
+        assert_eq!(error(source), None);
+        let source =
+            "fn f(c: bool) -> f32 { if c { 1.0 } else { 2.0 } }\nfn main() { let a = f(true); }";
+        assert_eq!(error(source), None);
+    }
+}
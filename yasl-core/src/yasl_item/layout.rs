@@ -1,17 +1,41 @@
 use std::convert::TryInto;
-use syn::parse::{Parse, ParseStream};
-use syn::{Error, Result, Type};
+use syn::parse::ParseStream;
+use syn::{Error, Ident, Result, Type};
 use syn::{LitInt, Token};
 
-use crate::glsl::{Glsl, GlslLine};
-
+use crate::glsl::Glsl;
+use crate::ir;
+use crate::reflection::{BufferReflection, LayoutReflection, PushConstantReflection};
+use crate::validation::ValidationContext;
+use crate::yasl_const::Consts;
 use crate::yasl_ident::YaslIdent;
-use crate::yasl_type::YaslType;
+use crate::yasl_program::ShaderStage;
+use crate::yasl_type::{std430, std430::StructFields, YaslType};
 
-#[derive(Debug)]
-enum LayoutKind {
+/// Minimal `maxPushConstantsSize` required by Vulkan
+const PUSH_CONSTANT_LIMIT: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LayoutKind {
     Input,
     Output,
+    /// Shader storage buffer, position is a binding instead of a location
+    Buffer,
+    /// Vulkan push constant block, has no position
+    PushConstant,
+}
+
+impl LayoutKind {
+    /// Written like in YASL, `layout<push_constant>`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutKind::Input => "input",
+            LayoutKind::Output => "output",
+            LayoutKind::Buffer => "buffer",
+            LayoutKind::PushConstant => "push_constant",
+        }
+    }
 }
 
 impl From<&LayoutKind> for Glsl {
@@ -20,44 +44,311 @@ impl From<&LayoutKind> for Glsl {
             match kind {
                 LayoutKind::Input => "in",
                 LayoutKind::Output => "out",
+                LayoutKind::Buffer => "buffer",
+                LayoutKind::PushConstant => "uniform",
             }
             .to_string(),
         )
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Interpolation {
+    Flat,
+    NoPerspective,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Sampling {
+    Centroid,
+    Sample,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Precision {
+    High,
+    Medium,
+    Low,
+}
+
+impl From<&Interpolation> for Glsl {
+    fn from(i: &Interpolation) -> Glsl {
+        Glsl::Expr(
+            match i {
+                Interpolation::Flat => "flat",
+                Interpolation::NoPerspective => "noperspective",
+            }
+            .into(),
+        )
+    }
+}
+
+impl From<&Sampling> for Glsl {
+    fn from(s: &Sampling) -> Glsl {
+        Glsl::Expr(
+            match s {
+                Sampling::Centroid => "centroid",
+                Sampling::Sample => "sample",
+            }
+            .into(),
+        )
+    }
+}
+
+impl From<&Precision> for Glsl {
+    fn from(p: &Precision) -> Glsl {
+        Glsl::Expr(
+            match p {
+                Precision::High => "highp",
+                Precision::Medium => "mediump",
+                Precision::Low => "lowp",
+            }
+            .into(),
+        )
+    }
+}
+
+/// Qualifiers following the location, for example `layout<input,0,flat,highp>`
+#[derive(Debug, Default)]
+struct Qualifiers {
+    interpolation: Option<Interpolation>,
+    sampling: Option<Sampling>,
+    precision: Option<Precision>,
+}
+
+impl Qualifiers {
+    fn add(&mut self, ident: &Ident) -> Result<()> {
+        fn set<T>(slot: &mut Option<T>, v: T, ident: &Ident, group: &str) -> Result<()> {
+            if slot.is_some() {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("Only one {} qualifier is allowed", group),
+                ));
+            }
+            *slot = Some(v);
+            Ok(())
+        }
+
+        match ident.to_string().as_str() {
+            "flat" => set(
+                &mut self.interpolation,
+                Interpolation::Flat,
+                ident,
+                "interpolation",
+            ),
+            "noperspective" => set(
+                &mut self.interpolation,
+                Interpolation::NoPerspective,
+                ident,
+                "interpolation",
+            ),
+            "centroid" => set(&mut self.sampling, Sampling::Centroid, ident, "sampling"),
+            "sample" => set(&mut self.sampling, Sampling::Sample, ident, "sampling"),
+            "highp" => set(&mut self.precision, Precision::High, ident, "precision"),
+            "mediump" => set(&mut self.precision, Precision::Medium, ident, "precision"),
+            "lowp" => set(&mut self.precision, Precision::Low, ident, "precision"),
+            _ => Err(Error::new(
+                ident.span(),
+                "Expected flat, noperspective, centroid, sample, highp, mediump or lowp",
+            )),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct YaslItemLayout {
     kind: LayoutKind,
     pos: usize,
+    qualifiers: Qualifiers,
     ident: YaslIdent,
     ty: YaslType,
 }
 
-impl YaslItemLayout {}
-
-impl From<&YaslItemLayout> for Glsl {
-    fn from(item: &YaslItemLayout) -> Glsl {
-        Glsl::Line(GlslLine {
-            span: Some(item.ident.span()),
-            ends_with_semi: true,
-            glsl_string: format!(
-                "layout(location={}) {} {} {}",
-                item.pos.to_string(),
-                Glsl::from(&item.kind),
-                Glsl::from(&item.ty),
-                Glsl::from(&item.ident),
-            ),
+impl YaslItemLayout {
+    pub fn kind(&self) -> &LayoutKind {
+        &self.kind
+    }
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+    pub fn ident(&self) -> &YaslIdent {
+        &self.ident
+    }
+    pub fn ty(&self) -> &YaslType {
+        &self.ty
+    }
+    pub fn get_ident(&self) -> YaslIdent {
+        let mut ident = self.ident.clone();
+        ident.set_type(self.ty.clone());
+        ident
+    }
+    pub fn interpolation(&self) -> Option<Interpolation> {
+        self.qualifiers.interpolation
+    }
+    pub fn sampling(&self) -> Option<Sampling> {
+        self.qualifiers.sampling
+    }
+
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        let (stage, options) = (ctx.stage, ctx.options);
+        let q = &self.qualifiers;
+
+        ctx.check_type(&self.ty, self.ident.span())?;
+
+        if self.kind == LayoutKind::PushConstant {
+            return self.validate_push_constant(&ctx.structs);
+        }
+
+        if q.precision.is_some() && !options.target.is_es() {
+            return Err(Error::new(
+                self.ident.span(),
+                "Precision qualifiers are only allowed on ES targets",
+            ));
+        }
+
+        let interpolated = !matches!(
+            (stage, &self.kind),
+            (ShaderStage::Vertex, LayoutKind::Input)
+                | (ShaderStage::Fragment, LayoutKind::Output)
+                | (_, LayoutKind::Buffer)
+        );
+
+        if !interpolated && (q.interpolation.is_some() || q.sampling.is_some()) {
+            return Err(Error::new(
+                self.ident.span(),
+                "Interpolation qualifiers are not allowed on vertex inputs, fragment outputs and buffers",
+            ));
+        }
+
+        if stage == ShaderStage::Compute && self.kind != LayoutKind::Buffer {
+            return Err(Error::new(
+                self.ident.span(),
+                "Compute stage has no inputs or outputs, use layout<buffer,_>",
+            ));
+        }
+
+        if let YaslType::Array(_, None) = self.ty {
+            if self.kind != LayoutKind::Buffer {
+                return Err(Error::new(
+                    self.ident.span(),
+                    "Runtime sized arrays are only allowed in buffers",
+                ));
+            }
+        }
+
+        let requires_flat = self.ty.scalar().map(|s| s.requires_flat()) == Some(true);
+        if stage == ShaderStage::Fragment
+            && self.kind == LayoutKind::Input
+            && requires_flat
+            && q.interpolation != Some(Interpolation::Flat)
+        {
+            return Err(Error::new(
+                self.ident.span(),
+                format!(
+                    "Fragment input of type {} must be flat, use layout<input,{},flat>",
+                    Glsl::from(&self.ty),
+                    self.pos
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn validate_push_constant(&self, structs: &StructFields) -> Result<()> {
+        let q = &self.qualifiers;
+        if q.interpolation.is_some() || q.sampling.is_some() || q.precision.is_some() {
+            return Err(Error::new(
+                self.ident.span(),
+                "Push constants don't take qualifiers",
+            ));
+        }
+
+        let size = match &self.ty {
+            YaslType::Struct(_) => std430::size_of(&self.ty, structs),
+            _ => {
+                return Err(Error::new(
+                    self.ident.span(),
+                    "Push constant has to be a struct",
+                ))
+            }
+        };
+
+        match size {
+            Some(size) if size > PUSH_CONSTANT_LIMIT => Err(Error::new(
+                self.ident.span(),
+                format!(
+                    "Push constant block is {} bytes, only {} bytes are guaranteed",
+                    size, PUSH_CONSTANT_LIMIT
+                ),
+            )),
+            Some(_) => Ok(()),
+            None => Err(Error::new(
+                self.ident.span(),
+                "Push constant struct has no std430 layout",
+            )),
+        }
+    }
+
+    pub fn reflect_push_constant(&self, structs: &StructFields) -> Option<PushConstantReflection> {
+        let struct_name = if let YaslType::Struct(name) = &self.ty {
+            name.clone()
+        } else {
+            return None;
+        };
+        Some(PushConstantReflection {
+            name: self.ident.name(),
+            size: std430::size_of(&self.ty, structs)?,
+            struct_name,
         })
     }
+
+    pub fn reflect_buffer(&self) -> BufferReflection {
+        BufferReflection {
+            name: self.ident.name(),
+            binding: self.pos,
+            ty: Glsl::from(&self.ty).to_string(),
+        }
+    }
+
+    pub fn reflect(&self) -> LayoutReflection {
+        LayoutReflection {
+            name: self.ident.name(),
+            location: self.pos,
+            ty: Glsl::from(&self.ty).to_string(),
+            interpolation: self.qualifiers.interpolation,
+            sampling: self.qualifiers.sampling,
+            precision: self.qualifiers.precision,
+        }
+    }
+}
+
+impl From<&YaslItemLayout> for ir::Layout {
+    fn from(item: &YaslItemLayout) -> ir::Layout {
+        ir::Layout {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            kind: item.kind,
+            pos: item.pos,
+            interpolation: item.qualifiers.interpolation,
+            sampling: item.qualifiers.sampling,
+            precision: item.qualifiers.precision,
+            span: item.ident.span(),
+        }
+    }
 }
 
 syn::custom_keyword!(layout);
 syn::custom_keyword!(input);
 syn::custom_keyword!(output);
+syn::custom_keyword!(buffer);
+syn::custom_keyword!(push_constant);
 
-impl Parse for YaslItemLayout {
-    fn parse(ps: ParseStream) -> Result<Self> {
+impl YaslItemLayout {
+    pub fn parse(ps: ParseStream, consts: &Consts) -> Result<Self> {
         let let_token = ps.parse::<layout>()?;
         let _ = ps.parse::<Token![<]>()?;
 
@@ -67,26 +358,49 @@ impl Parse for YaslItemLayout {
         } else if ps.peek(output) {
             ps.parse::<output>()?;
             LayoutKind::Output
+        } else if ps.peek(buffer) {
+            ps.parse::<buffer>()?;
+            LayoutKind::Buffer
+        } else if ps.peek(push_constant) {
+            ps.parse::<push_constant>()?;
+            LayoutKind::PushConstant
         } else {
-            return Err(Error::new(let_token.span, "Expected input or output kind"));
+            return Err(Error::new(
+                let_token.span,
+                "Expected input, output, buffer or push_constant kind",
+            ));
         };
 
-        let _ = ps.parse::<Token![,]>()?;
-        let pos = ps.parse::<LitInt>()?;
-        let pos = pos.base10_parse()?;
+        let pos = if kind == LayoutKind::PushConstant {
+            0
+        } else {
+            let _ = ps.parse::<Token![,]>()?;
+            let pos = ps.parse::<LitInt>()?;
+            pos.base10_parse()?
+        };
+
+        let mut qualifiers = Qualifiers::default();
+        while ps.peek(Token![,]) {
+            let _ = ps.parse::<Token![,]>()?;
+            let q: Ident = ps.parse()?;
+            qualifiers.add(&q)?;
+        }
+
         let _ = ps.parse::<Token![>]>()?;
 
         let ident: syn::Ident = ps.parse()?;
 
         let _ = ps.parse::<Token![:]>()?;
 
-        let ty: Type = ps.parse()?;
+        let mut ty: Type = ps.parse()?;
+        consts.fold_type(&mut ty)?;
 
         let _ = ps.parse::<Token![;]>()?;
 
         Ok(Self {
             kind,
             pos,
+            qualifiers,
             ident: ident.into(),
             ty: ty.try_into()?,
         })
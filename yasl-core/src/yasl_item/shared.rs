@@ -0,0 +1,69 @@
+use std::convert::TryInto;
+use syn::parse::ParseStream;
+use syn::{Error, Result, Token, Type};
+
+use crate::ir;
+use crate::validation::ValidationContext;
+use crate::yasl_const::Consts;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_program::ShaderStage;
+use crate::yasl_type::YaslType;
+
+/// Workgroup shared variable, for example `shared tile: [f32; 64];`
+#[derive(Debug)]
+pub struct YaslItemShared {
+    ident: YaslIdent,
+    ty: YaslType,
+}
+
+impl YaslItemShared {
+    pub fn get_ident(&self) -> YaslIdent {
+        let mut ident = self.ident.clone();
+        ident.set_type(self.ty.clone());
+        ident
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        ctx.check_type(&self.ty, self.ident.span())?;
+        if ctx.stage != ShaderStage::Compute {
+            return Err(Error::new(
+                self.ident.span(),
+                "Shared variables are only allowed in compute stage",
+            ));
+        }
+        if let YaslType::Array(_, None) = self.ty {
+            return Err(Error::new(
+                self.ident.span(),
+                "Shared arrays need a fixed size",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<&YaslItemShared> for ir::Shared {
+    fn from(item: &YaslItemShared) -> ir::Shared {
+        ir::Shared {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            span: item.ident.span(),
+        }
+    }
+}
+
+syn::custom_keyword!(shared);
+
+impl YaslItemShared {
+    pub fn parse(ps: ParseStream, consts: &Consts) -> Result<Self> {
+        ps.parse::<shared>()?;
+        let ident: syn::Ident = ps.parse()?;
+        ps.parse::<Token![:]>()?;
+        let mut ty: Type = ps.parse()?;
+        consts.fold_type(&mut ty)?;
+        ps.parse::<Token![;]>()?;
+
+        Ok(Self {
+            ident: ident.into(),
+            ty: ty.try_into()?,
+        })
+    }
+}
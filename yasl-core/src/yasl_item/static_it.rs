@@ -1,4 +1,5 @@
-use crate::glsl::{Glsl, GlslLine};
+use crate::ir;
+use crate::validation::ValidationContext;
 use crate::yasl_expr::YaslExprLineScope;
 use crate::yasl_type::YaslType;
 use std::convert::{TryFrom, TryInto};
@@ -8,8 +9,9 @@ use crate::yasl_ident::YaslIdent;
 
 #[derive(Debug)]
 pub struct YaslItemStatic {
-    static_token: syn::token::Static,
     ident: YaslIdent,
+    /// `static mut`, GLSL globals are always writable
+    mutable: bool,
     ty: YaslType,
     expr: YaslExprLineScope,
 }
@@ -20,19 +22,37 @@ impl YaslItemStatic {
         ident.set_type(self.ty.clone());
         ident
     }
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        ctx.check_type(&self.ty, self.ident.span())?;
+        self.expr.validate(ctx)
+    }
 }
 
-impl From<&YaslItemStatic> for Glsl {
-    fn from(item: &YaslItemStatic) -> Glsl {
-        Glsl::Line(GlslLine {
-            span: Some(item.ident.span()),
-            ends_with_semi: true,
-            glsl_string: format!(
-                "{} {} = {}",
-                Glsl::from(&item.ty),
-                Glsl::from(&item.ident),
-                Glsl::from(&item.expr),
-            ),
+impl TryFrom<&YaslItemStatic> for ir::Global {
+    type Error = Error;
+    fn try_from(item: &YaslItemStatic) -> Result<ir::Global> {
+        Ok(ir::Global {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            value: (&item.expr).try_into()?,
+            span: item.ident.span(),
+        })
+    }
+}
+
+/// Static declared inside of a function, a plain local in GLSL
+impl TryFrom<&YaslItemStatic> for ir::Stmt {
+    type Error = Error;
+    fn try_from(item: &YaslItemStatic) -> Result<ir::Stmt> {
+        Ok(ir::Stmt::Local {
+            symbol: (&item.ident).into(),
+            ty: item.ty.clone(),
+            constant: false,
+            value: Some((&item.expr).try_into()?),
+            span: item.ident.span(),
         })
     }
 }
@@ -46,14 +66,14 @@ impl TryFrom<ItemStatic> for YaslItemStatic {
                 "Visibility Keywords are not supported",
             ));
         }
-        if item.mutability.is_some() {
-            return Err(Error::new(item.span(), "Mut Keyword is not supported"));
-        }
+        let ty = (*item.ty).try_into()?;
+        let mut expr: YaslExprLineScope = (*item.expr).try_into()?;
+        expr.infer_type(&ty);
         Ok(Self {
-            static_token: item.static_token,
             ident: item.ident.into(),
-            ty: (*item.ty).try_into()?,
-            expr: (*item.expr).try_into()?,
+            mutable: item.mutability.is_some(),
+            ty,
+            expr,
         })
     }
 }
@@ -0,0 +1,114 @@
+use std::convert::{TryFrom, TryInto};
+use syn::{spanned::Spanned, Error, ItemStruct, Result};
+
+use crate::ir;
+use crate::validation::ValidationContext;
+use crate::yasl_ident::YaslIdent;
+use crate::yasl_type::YaslType;
+
+/// Struct with named fields, for example `struct DrawParams { color: vec4<f32> }`
+#[derive(Debug)]
+pub struct YaslItemStruct {
+    ident: YaslIdent,
+    fields: Vec<(syn::Ident, YaslType)>,
+}
+
+impl YaslItemStruct {
+    pub fn name(&self) -> String {
+        self.ident.name()
+    }
+    pub fn span(&self) -> proc_macro2::Span {
+        self.ident.span()
+    }
+    pub fn fields(&self) -> Vec<(String, YaslType)> {
+        self.fields
+            .iter()
+            .map(|(i, ty)| (i.to_string(), ty.clone()))
+            .collect()
+    }
+    /// Fields typed with their types, used to type member access
+    pub fn field_idents(&self) -> Vec<YaslIdent> {
+        self.fields
+            .iter()
+            .map(|(i, ty)| {
+                let mut ident: YaslIdent = i.clone().into();
+                ident.set_type(ty.clone());
+                ident
+            })
+            .collect()
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        for (ident, ty) in self.fields.iter() {
+            ctx.check_type(ty, ident.span())?;
+            if let YaslType::Array(_, None) = ty {
+                return Err(Error::new(
+                    ident.span(),
+                    "Runtime sized arrays are only allowed in buffers",
+                ));
+            }
+        }
+        if ctx.is_recursive(&self.name()) {
+            return Err(Error::new(self.ident.span(), "Struct can't contain itself"));
+        }
+        Ok(())
+    }
+}
+
+impl From<&YaslItemStruct> for ir::Struct {
+    fn from(item: &YaslItemStruct) -> ir::Struct {
+        ir::Struct {
+            name: item.name(),
+            fields: item
+                .fields
+                .iter()
+                .map(|(ident, ty)| ir::Field {
+                    name: ident.to_string(),
+                    ty: ty.clone(),
+                    span: ident.span(),
+                })
+                .collect(),
+            span: item.span(),
+        }
+    }
+}
+
+impl TryFrom<ItemStruct> for YaslItemStruct {
+    type Error = Error;
+    fn try_from(item: ItemStruct) -> Result<Self> {
+        if item.vis != syn::Visibility::Inherited {
+            return Err(Error::new(
+                item.span(),
+                "Visibility Keywords are not supported",
+            ));
+        }
+        if !item.generics.params.is_empty() {
+            return Err(Error::new(
+                item.generics.span(),
+                "Generics are not supported",
+            ));
+        }
+
+        let named = if let syn::Fields::Named(n) = item.fields {
+            n
+        } else {
+            return Err(Error::new(item.span(), "Expected struct with named fields"));
+        };
+
+        let mut fields = Vec::new();
+        for f in named.named.into_iter() {
+            if f.vis != syn::Visibility::Inherited {
+                return Err(Error::new(
+                    f.span(),
+                    "Visibility Keywords are not supported",
+                ));
+            }
+            let ident = f.ident.unwrap();
+            fields.push((ident, f.ty.try_into()?));
+        }
+
+        Ok(Self {
+            ident: item.ident.into(),
+            fields,
+        })
+    }
+}
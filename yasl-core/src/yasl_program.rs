@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use syn::parse::ParseStream;
+use syn::{Error, Result};
+
+use crate::glsl::Glsl;
+use crate::options::CompileOptions;
+use crate::yasl_const::Consts;
+use crate::yasl_file::YaslFile;
+use crate::yasl_item::LayoutKind;
+use crate::yasl_type::YaslType;
+
+syn::custom_keyword!(vertex);
+syn::custom_keyword!(fragment);
+syn::custom_keyword!(geometry);
+syn::custom_keyword!(tess_control);
+syn::custom_keyword!(tess_eval);
+syn::custom_keyword!(compute);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEval,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::TessControl => "tess_control",
+            ShaderStage::TessEval => "tess_eval",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Compute => "compute",
+        }
+    }
+
+    /// Stage of a single stage file, from its name, `light_frag.yasl` or `light.frag.yasl`, vertex by default
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let stages = [
+            ("frag", ShaderStage::Fragment),
+            ("comp", ShaderStage::Compute),
+            ("geom", ShaderStage::Geometry),
+            ("tesc", ShaderStage::TessControl),
+            ("tess_control", ShaderStage::TessControl),
+            ("tese", ShaderStage::TessEval),
+            ("tess_eval", ShaderStage::TessEval),
+        ];
+        stages
+            .iter()
+            .find(|(s, _)| name.contains(s))
+            .map_or(ShaderStage::Vertex, |(_, stage)| *stage)
+    }
+}
+
+/// Programs have stage blocks at the top level, `vertex { .. }`, other files are a single stage
+/// A stage block starts an item, so it follows nothing, a `;` or a `{ .. }`, unlike `struct vertex {}`
+pub fn is_program(tokens: &TokenStream) -> bool {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    let stages = [
+        ShaderStage::Vertex,
+        ShaderStage::TessControl,
+        ShaderStage::TessEval,
+        ShaderStage::Geometry,
+        ShaderStage::Fragment,
+        ShaderStage::Compute,
+    ];
+    let starts_item = |i: usize| match i.checked_sub(1).map(|p| &tokens[p]) {
+        None => true,
+        Some(TokenTree::Punct(p)) => p.as_char() == ';',
+        Some(TokenTree::Group(g)) => g.delimiter() == Delimiter::Brace,
+        Some(_) => false,
+    };
+    tokens
+        .windows(2)
+        .enumerate()
+        .any(|(i, w)| match (&w[0], &w[1]) {
+            (TokenTree::Ident(ident), TokenTree::Group(g)) => {
+                g.delimiter() == Delimiter::Brace
+                    && stages.iter().any(|s| ident == s.name())
+                    && starts_item(i)
+            }
+            _ => false,
+        })
+}
+
+/// Multiple shader stages sharing top level items
+/// For example:
+/// ```ignore
+/// fn helper() -> f32 { 1.0 }
+/// vertex { fn main() {} }
+/// fragment { fn main() {} }
+/// ```
+#[derive(Debug)]
+pub struct YaslProgram {
+    pub stages: Vec<(ShaderStage, YaslFile)>,
+}
+
+impl YaslProgram {
+    fn parse_stage(ps: ParseStream) -> Result<Option<ShaderStage>> {
+        let stage = if ps.peek(vertex) {
+            ps.parse::<vertex>()?;
+            ShaderStage::Vertex
+        } else if ps.peek(fragment) {
+            ps.parse::<fragment>()?;
+            ShaderStage::Fragment
+        } else if ps.peek(geometry) {
+            ps.parse::<geometry>()?;
+            ShaderStage::Geometry
+        } else if ps.peek(tess_control) {
+            ps.parse::<tess_control>()?;
+            ShaderStage::TessControl
+        } else if ps.peek(tess_eval) {
+            ps.parse::<tess_eval>()?;
+            ShaderStage::TessEval
+        } else if ps.peek(compute) {
+            ps.parse::<compute>()?;
+            ShaderStage::Compute
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(stage))
+    }
+
+    pub fn validate(&self, options: &CompileOptions) -> Result<()> {
+        for (stage, file) in self.stages.iter() {
+            file.validate(*stage, options)?;
+        }
+        self.check_interface()
+    }
+
+    /// Check that outputs of each stage match inputs of the next one
+    fn check_interface(&self) -> Result<()> {
+        for w in self.stages.windows(2) {
+            Self::check_stage_interface((w[0].0, &w[0].1), (w[1].0, &w[1].1))?;
+        }
+        Ok(())
+    }
+
+    fn check_stage_interface(
+        (producer_stage, producer): (ShaderStage, &YaslFile),
+        (consumer_stage, consumer): (ShaderStage, &YaslFile),
+    ) -> Result<()> {
+        let outputs: Vec<_> = producer
+            .layouts()
+            .into_iter()
+            .filter(|l| *l.kind() == LayoutKind::Output)
+            .collect();
+
+        // Tessellation and geometry stages read an array of their inputs, one per vertex
+        let arrayed = matches!(
+            consumer_stage,
+            ShaderStage::TessControl | ShaderStage::TessEval | ShaderStage::Geometry
+        );
+
+        for input in consumer
+            .layouts()
+            .into_iter()
+            .filter(|l| *l.kind() == LayoutKind::Input)
+        {
+            let output = outputs.iter().find(|o| o.pos() == input.pos());
+
+            let output = if let Some(output) = output {
+                output
+            } else {
+                return Err(Error::new(
+                    input.ident().span(),
+                    format!(
+                        "No {} output at location {} for {} input",
+                        producer_stage.name(),
+                        input.pos(),
+                        consumer_stage.name(),
+                    ),
+                ));
+            };
+
+            let matches = output.ty() == input.ty()
+                || arrayed && matches!(input.ty(), YaslType::Array(ty, _) if **ty == *output.ty());
+            if !matches {
+                let mut err = Error::new(
+                    input.ident().span(),
+                    format!(
+                        "Input at location {} of {} is {}, but {} output is {}",
+                        input.pos(),
+                        consumer_stage.name(),
+                        Glsl::from(input.ty()),
+                        producer_stage.name(),
+                        Glsl::from(output.ty()),
+                    ),
+                );
+                err.combine(Error::new(output.ident().span(), "Output declared here"));
+                return Err(err);
+            }
+
+            if output.interpolation() != input.interpolation()
+                || output.sampling() != input.sampling()
+            {
+                let mut err = Error::new(
+                    input.ident().span(),
+                    format!(
+                        "Interpolation of {} input at location {} does not match {} output",
+                        consumer_stage.name(),
+                        input.pos(),
+                        producer_stage.name(),
+                    ),
+                );
+                err.combine(Error::new(output.ident().span(), "Output declared here"));
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn parse(ps: ParseStream) -> Result<Self> {
+        let mut shared = Vec::new();
+        let mut consts = Consts::default();
+        let mut stages: Vec<(ShaderStage, YaslFile)> = Vec::new();
+
+        while !ps.is_empty() {
+            let span = ps.span();
+            if let Some(stage) = Self::parse_stage(ps)? {
+                if stages.iter().any(|(s, _)| *s == stage) {
+                    return Err(Error::new(
+                        span,
+                        format!("Stage {} is defined more than once", stage.name()),
+                    ));
+                }
+
+                let body;
+                syn::braced!(body in ps);
+                let file = YaslFile::parse(&body, consts.clone())?;
+                stages.push((stage, file));
+            } else {
+                let item: syn::Item = ps.parse().map_err(|e| {
+                    let mut err = Error::new(
+                        e.span(),
+                        "Expected shared item or stage block (vertex, fragment, geometry, tess_control, tess_eval, compute)",
+                    );
+                    err.combine(e);
+                    err
+                })?;
+                let mut item = item;
+                consts.fold_item(&mut item)?;
+                shared.push(item);
+            }
+        }
+
+        let is_compute = stages.iter().any(|(s, _)| *s == ShaderStage::Compute);
+        if is_compute && stages.len() > 1 {
+            return Err(Error::new(
+                ps.span(),
+                "Compute stage can't be combined with graphics stages",
+            ));
+        }
+
+        let required: &[ShaderStage] = if is_compute {
+            &[]
+        } else {
+            &[ShaderStage::Vertex, ShaderStage::Fragment]
+        };
+        for required in required.iter() {
+            if !stages.iter().any(|(s, _)| s == required) {
+                return Err(Error::new(
+                    ps.span(),
+                    format!("Program is missing {} stage", required.name()),
+                ));
+            }
+        }
+
+        for (_, file) in stages.iter_mut() {
+            file.prepend_shared(&shared)?;
+            file.attempt_type_anotation();
+        }
+
+        stages.sort_by_key(|(s, _)| *s as usize);
+
+        Ok(Self { stages })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, CompileOptions};
+
+    /// Errors of a program with `output` in the vertex stage and `input` in the fragment stage
+    fn errors(output: &str, input: &str) -> Vec<String> {
+        let source = format!(
+            "vertex {{ {} fn main() {{}} }}\nfragment {{ {} fn main() {{}} }}",
+            output, input
+        );
+        match compile(&source, &CompileOptions::default()) {
+            Ok(_) => Vec::new(),
+            Err(d) => d.errors.into_iter().map(|e| e.message).collect(),
+        }
+    }
+
+    #[test]
+    fn matching_interface() {
+        assert!(errors(
+            "layout<output,0> uv: vec2<f32>; layout<output,1,flat> id: u32;",
+            "layout<input,1,flat> id: u32; layout<input,0> uv: vec2<f32>;"
+        )
+        .is_empty());
+        // Unused outputs are allowed
+        assert!(errors("layout<output,0> uv: vec2<f32>;", "").is_empty());
+    }
+
+    #[test]
+    fn location_mismatch() {
+        assert_eq!(
+            errors(
+                "layout<output,0> uv: vec2<f32>;",
+                "layout<input,1> uv: vec2<f32>;"
+            ),
+            ["No vertex output at location 1 for fragment input"]
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        assert_eq!(
+            errors(
+                "layout<output,0> uv: vec2<f32>;",
+                "layout<input,0> uv: vec3<f32>;"
+            ),
+            [
+                "Input at location 0 of fragment is vec3, but vertex output is vec2",
+                "Output declared here"
+            ]
+        );
+    }
+
+    #[test]
+    fn interpolation_mismatch() {
+        let mismatch = [
+            "Interpolation of fragment input at location 0 does not match vertex output",
+            "Output declared here",
+        ];
+        assert_eq!(
+            errors(
+                "layout<output,0,flat> uv: vec2<f32>;",
+                "layout<input,0> uv: vec2<f32>;"
+            ),
+            mismatch
+        );
+        assert_eq!(
+            errors(
+                "layout<output,0,noperspective> uv: vec2<f32>;",
+                "layout<input,0,noperspective,centroid> uv: vec2<f32>;"
+            ),
+            mismatch
+        );
+    }
+}
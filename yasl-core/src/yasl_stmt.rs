@@ -1,12 +1,13 @@
-use crate::glsl::Glsl;
+use crate::ir;
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
 };
-use syn::{Error, Result, Stmt};
+use syn::{spanned::Spanned, Error, Result, Stmt};
 
+use crate::validation::ValidationContext;
 use crate::yasl_expr::YaslExprFunctionScope;
-use crate::{yasl_ident::YaslIdent, yasl_item::YaslItem, yasl_type::YaslType};
+use crate::{yasl_ident::YaslIdent, yasl_item::YaslItem};
 
 mod local;
 use local::YaslLocal;
@@ -20,13 +21,40 @@ pub enum YaslStmt {
 }
 
 impl YaslStmt {
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
         match self {
             YaslStmt::Local(l) => l.attempt_type_anotation(idents),
             YaslStmt::Expr(e) => e.attempt_type_anotation(idents),
             _ => {}
         }
     }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        match self {
+            YaslStmt::Local(l) => l.validate(ctx),
+            YaslStmt::Expr(e) => e.validate(ctx),
+            YaslStmt::Item(_) => Ok(()),
+        }
+    }
+    pub fn always_returns(&self) -> bool {
+        match self {
+            YaslStmt::Local(l) => l.always_returns(),
+            YaslStmt::Expr(e) => e.always_returns(),
+            YaslStmt::Item(_) => false,
+        }
+    }
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        match self {
+            YaslStmt::Local(l) => l.returns_non_uniformly(non_uniform),
+            YaslStmt::Expr(e) => e.returns_non_uniformly(non_uniform),
+            YaslStmt::Item(_) => false,
+        }
+    }
+    /// Registers declarations of the statement, see `ValidationContext::declare`
+    pub fn declare(&self, ctx: &mut ValidationContext) {
+        if let YaslStmt::Local(l) = self {
+            l.declare(ctx);
+        }
+    }
     pub fn update_idents(&mut self) -> Vec<YaslIdent> {
         match self {
             YaslStmt::Local(l) => {
@@ -41,23 +69,38 @@ impl YaslStmt {
     }
 }
 
-impl From<&YaslStmt> for Glsl {
-    fn from(item: &YaslStmt) -> Glsl {
-        use YaslStmt::*;
-        let inner = match item {
-            Item(i) => i.into(),
-            Expr(e) => e.into(),
-            // ReturnExpr(e) => e.into(),
-            Local(l) => l.into(),
-        };
-        inner
+impl YaslStmt {
+    /// Appends IR of the statement, a local with branching initializer is followed by the branches
+    pub fn lower(&self, stmts: &mut Vec<ir::Stmt>) -> Result<()> {
+        match self {
+            YaslStmt::Item(YaslItem::Const(c)) => stmts.push(c.try_into()?),
+            YaslStmt::Item(YaslItem::Static(s)) => stmts.push(s.try_into()?),
+            YaslStmt::Item(_) => {
+                unreachable!("only const and static items are parsed in functions")
+            }
+            YaslStmt::Expr(e) => stmts.push(e.try_into()?),
+            YaslStmt::Local(l) => l.lower(stmts)?,
+        }
+        Ok(())
     }
 }
+
 impl TryFrom<Stmt> for YaslStmt {
     type Error = Error;
     fn try_from(stmt: Stmt) -> Result<Self> {
         Ok(match stmt {
-            Stmt::Item(i) => Self::Item(i.try_into()?),
+            Stmt::Item(i) => {
+                let span = i.span();
+                match i.try_into()? {
+                    item @ (YaslItem::Const(_) | YaslItem::Static(_)) => Self::Item(item),
+                    _ => {
+                        return Err(Error::new(
+                            span,
+                            "Only const and static items are supported inside of functions",
+                        ))
+                    }
+                }
+            }
             Stmt::Expr(e) => Self::Expr(e.try_into()?),
             Stmt::Semi(e, _) => Self::Expr(e.try_into()?),
             Stmt::Local(l) => Self::Local(l.try_into()?),
@@ -1,32 +1,127 @@
-use crate::glsl::{Glsl, GlslLine};
+use crate::ir;
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
 };
 
+use proc_macro2::Span;
 use syn::spanned::Spanned;
 use syn::{Error, Local, Pat, Result};
 
-use crate::yasl_expr::YaslExprLineScope;
+use crate::validation::ValidationContext;
+use crate::yasl_expr::{lowered_type, tail, YaslExprFunctionScope, YaslExprLineScope};
 use crate::yasl_ident::YaslIdent;
 use crate::yasl_type::{Typed, YaslType};
 
+/// Place the branches of an initializer assign to, resolved to the local during type anotation.
+/// It's a keyword, so bindings declared inside of the branches can't hide it
+fn branch_target(span: Span) -> syn::Ident {
+    syn::Ident::new("let", span)
+}
+
 #[derive(Debug)]
 pub struct YaslLocal {
     ident: YaslIdent,
+    /// `let mut`, locals without initializer can be assigned later too
+    mutable: bool,
     ty: Option<YaslType>,
     init: Option<YaslExprLineScope>,
+    /// Initializer with statements, for example `let a = if c { .. } else { .. };`
+    /// assigns to the local in every branch
+    init_branches: Option<Box<YaslExprFunctionScope>>,
 }
 
 impl YaslLocal {
-    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslType>) {
-        if let (Some(init), None) = (&mut self.init, &self.ty) {
+    pub fn attempt_type_anotation(&mut self, idents: &HashMap<String, YaslIdent>) {
+        // GLSL doesn't allow redeclaring a name in the same scope, so shadowing bindings are renamed.
+        // Local assigned by enclosing branches is in scope too, under the key of `branch_target`
+        let key = self.ident.key();
+        for prev in idents.values().filter(|i| i.key() == key) {
+            self.ident.shadow(prev);
+        }
+
+        if let Some(init) = &mut self.init {
             init.attempt_type_anotation(idents);
-            if let Some(ty) = init.get_type() {
-                self.ty = Some(ty);
+            if let Some(ty) = &self.ty {
+                init.infer_type(ty);
+            }
+            if self.ty.is_none() {
+                self.ty = init.get_type();
+            }
+        }
+        if let Some(branches) = &mut self.init_branches {
+            // Branches assign to the new binding, not the one it shadows
+            let mut idents = idents.clone();
+            let mut ident = self.ident.clone();
+            if let Some(ty) = &self.ty {
+                ident.set_type(ty.clone());
+            }
+            idents.insert(YaslIdent::from(branch_target(ident.span())).key(), ident);
+            branches.attempt_type_anotation(&idents);
+            // Type is taken from the first branch that doesn't depend on context,
+            // values of the others are inferred from it and checked against it
+            if self.ty.is_none() {
+                self.ty = branches
+                    .fixed_assigned_type()
+                    .or_else(|| branches.assigned_type());
+                if let Some(ty) = &self.ty {
+                    branches.set_assigned_type(&self.ident, ty);
+                }
             }
         }
     }
+    /// Every branch of the initializer returns instead of producing a value
+    pub fn always_returns(&self) -> bool {
+        self.init_branches
+            .as_ref()
+            .is_some_and(|b| b.always_returns())
+    }
+    pub fn returns_non_uniformly(&self, non_uniform: bool) -> bool {
+        self.init_branches
+            .as_ref()
+            .is_some_and(|b| b.returns_non_uniformly(non_uniform))
+    }
+    pub fn validate(&self, ctx: &ValidationContext) -> Result<()> {
+        if let Some(init) = &self.init {
+            init.validate(ctx)?;
+        }
+        match &self.ty {
+            Some(YaslType::Void) => {
+                return Err(Error::new(
+                    self.ident.span(),
+                    format!(
+                        "`{}` can't be assigned a value of type ()",
+                        self.ident.name()
+                    ),
+                ))
+            }
+            Some(ty) => ctx.check_type(ty, self.ident.span())?,
+            None => {
+                return Err(Error::new(
+                    self.ident.span(),
+                    format!(
+                        "Cannot infer type of `{}`, add a type annotation",
+                        self.ident.name()
+                    ),
+                ))
+            }
+        }
+        if let (Some(init), Some(ty)) = (&self.init, &self.ty) {
+            ctx.check_value(ty, init)?;
+        }
+        if let Some(branches) = &self.init_branches {
+            // Branches assign the value
+            let mut ctx = ctx.clone();
+            ctx.declare(&self.ident, None);
+            branches.validate(&ctx)?;
+        }
+        Ok(())
+    }
+    /// Makes the local visible to statements following it
+    pub fn declare(&self, ctx: &mut ValidationContext) {
+        let assignable = self.mutable || (self.init.is_none() && self.init_branches.is_none());
+        ctx.declare(&self.ident, if assignable { None } else { Some("local") });
+    }
     pub fn get_ident(&self) -> Option<YaslIdent> {
         if let Some(ty) = &self.ty {
             let mut ident = self.ident.clone();
@@ -44,44 +139,55 @@ impl Typed for YaslLocal {
     }
 }
 
-impl From<&YaslLocal> for Glsl {
-    fn from(local: &YaslLocal) -> Glsl {
-        let init_glsl = if let Some(init) = &local.init {
-            format!("= {}", Glsl::from(init))
-        } else {
-            String::new()
-        };
-
-        let ty = if let Some(ty) = &local.ty {
-            ty
-        } else {
-            &YaslType::Void
-        };
-
-        Glsl::Line(GlslLine {
-            span: Some(local.ident.span()),
-            ends_with_semi: true,
-            glsl_string: format!(
-                "{} {} {}",
-                Glsl::from(ty).to_string(),
-                Glsl::from(&local.ident),
-                init_glsl
-            ),
-        })
+impl YaslLocal {
+    pub fn lower(&self, stmts: &mut Vec<ir::Stmt>) -> Result<()> {
+        stmts.push(ir::Stmt::Local {
+            symbol: (&self.ident).into(),
+            ty: lowered_type(self.ty.clone(), self.ident.span())?,
+            constant: false,
+            value: self.init.as_ref().map(ir::Expr::try_from).transpose()?,
+            span: self.ident.span(),
+        });
+        if let Some(branches) = &self.init_branches {
+            stmts.push((&**branches).try_into()?);
+        }
+        Ok(())
     }
 }
 
 impl TryFrom<Local> for YaslLocal {
     type Error = Error;
     fn try_from(l: Local) -> Result<Self> {
-        let init = if let Some((_eq, expr)) = l.init {
-            let expr: YaslExprLineScope = (*expr).try_into()?;
-            Some(expr)
-        } else {
-            None
+        let pat_span = l.pat.span();
+        let mutable = match &l.pat {
+            Pat::Type(t) => matches!(&*t.pat, Pat::Ident(i) if i.mutability.is_some()),
+            Pat::Ident(i) => i.mutability.is_some(),
+            _ => false,
         };
 
-        let pat_span = l.pat.span();
+        let mut init_branches = None;
+        let init: Option<YaslExprLineScope> = match l.init {
+            Some((_eq, expr)) if !tail::is_inline(&expr) => {
+                let ident = match &l.pat {
+                    Pat::Type(syn::PatType { pat, .. }) => match &**pat {
+                        Pat::Ident(i) => i.ident.clone(),
+                        _ => return Err(Error::new(pat_span, "Expected Ident")),
+                    },
+                    Pat::Ident(i) => i.ident.clone(),
+                    _ => return Err(Error::new(pat_span, "Expected Ident")),
+                };
+                let place = syn::Expr::Path(syn::ExprPath {
+                    attrs: Vec::new(),
+                    qself: None,
+                    path: branch_target(ident.span()).into(),
+                });
+                let branches = tail::map_tails(*expr, &|e| tail::assign_expr(&place, e))?;
+                init_branches = Some(Box::new(branches.try_into()?));
+                None
+            }
+            Some((_eq, expr)) => Some((*expr).try_into()?),
+            None => None,
+        };
 
         let p: Option<(YaslIdent, Option<YaslType>)> = match l.pat {
             Pat::Type(t) => {
@@ -90,13 +196,10 @@ impl TryFrom<Local> for YaslLocal {
 
                 Some((ident, Some(ty)))
             }
+            // Type is inferred from the initializer during type anotation
             Pat::Ident(i) => {
-                if let Some(init) = &init {
-                    if let Some(t) = init.get_type() {
-                        Some((i.ident.into(), Some(t)))
-                    } else {
-                        Some((i.ident.into(), None))
-                    }
+                if init.is_some() || init_branches.is_some() {
+                    Some((i.ident.into(), None))
                 } else {
                     None
                 }
@@ -110,6 +213,12 @@ impl TryFrom<Local> for YaslLocal {
             return Err(Error::new(pat_span, "Expected Type"));
         };
 
-        Ok(Self { ty, ident, init })
+        Ok(Self {
+            ty,
+            ident,
+            mutable,
+            init,
+            init_branches,
+        })
     }
 }
@@ -3,6 +3,7 @@ use syn::spanned::Spanned;
 use syn::{Error, Result};
 
 use crate::glsl::Glsl;
+use crate::naming;
 
 mod yasl_scalar;
 pub use yasl_scalar::YaslScalarType;
@@ -10,29 +11,147 @@ pub use yasl_scalar::YaslScalarType;
 mod yasl_vec;
 pub use yasl_vec::YaslVecType;
 
+pub mod std430;
+
 pub trait Typed {
     fn get_type(&self) -> Option<YaslType>;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum YaslType {
     ScalarType(YaslScalarType),
     Vec(YaslVecType),
+    /// Fixed size array, or runtime sized one when length is None
+    Array(Box<YaslType>, Option<usize>),
+    /// User defined struct, referenced by name
+    Struct(String),
+    /// Fieldless enum, an `int` in GLSL
+    Enum(String),
     Void,
     // Unknown(String),
 }
 
+impl YaslType {
+    /// Scalar type of a scalar, or component type of a vector
+    pub fn scalar(&self) -> Option<&YaslScalarType> {
+        match self {
+            YaslType::ScalarType(s) => Some(s),
+            YaslType::Vec(v) => Some(v.component()),
+            YaslType::Enum(_) => Some(&YaslScalarType::Int),
+            YaslType::Array(_, _) | YaslType::Struct(_) | YaslType::Void => None,
+        }
+    }
+
+    /// Type built by a GLSL constructor, `uvec3(..)` or `float(..)`
+    pub fn constructed(name: &str) -> Option<YaslType> {
+        use YaslScalarType::*;
+        let scalar = |prefix: &str| match prefix {
+            "" => Some(Float32),
+            "d" => Some(Float64),
+            "i" => Some(Int),
+            "u" => Some(UInt),
+            "b" => Some(Bool),
+            _ => None,
+        };
+        Some(match name {
+            "float" => YaslType::ScalarType(Float32),
+            "double" => YaslType::ScalarType(Float64),
+            "int" => YaslType::ScalarType(Int),
+            "uint" => YaslType::ScalarType(UInt),
+            "bool" => YaslType::ScalarType(Bool),
+            _ => {
+                let (prefix, len) = name.split_at(name.find("vec")?);
+                let s = scalar(prefix)?;
+                YaslType::Vec(match len {
+                    "vec2" => YaslVecType::Vec2(s),
+                    "vec3" => YaslVecType::Vec3(s),
+                    "vec4" => YaslVecType::Vec4(s),
+                    _ => return None,
+                })
+            }
+        })
+    }
+
+    /// Name used in errors, user defined types are named like in source
+    pub fn name(&self) -> String {
+        match self {
+            YaslType::Struct(name) | YaslType::Enum(name) => name.clone(),
+            ty => Glsl::from(ty).to_string(),
+        }
+    }
+
+    /// Written like in YASL source, `vec3<f32>` or `[u32; 4]`
+    pub fn yasl_name(&self) -> String {
+        use YaslScalarType::*;
+        fn scalar(s: &YaslScalarType) -> &'static str {
+            match s {
+                Int => "i32",
+                UInt => "u32",
+                Bool => "bool",
+                Float32 => "f32",
+                Float64 => "f64",
+            }
+        }
+        match self {
+            YaslType::ScalarType(s) => scalar(s).into(),
+            YaslType::Vec(YaslVecType::Vec2(s)) => format!("vec2<{}>", scalar(s)),
+            YaslType::Vec(YaslVecType::Vec3(s)) => format!("vec3<{}>", scalar(s)),
+            YaslType::Vec(YaslVecType::Vec4(s)) => format!("vec4<{}>", scalar(s)),
+            YaslType::Array(ty, Some(len)) => format!("[{}; {}]", ty.yasl_name(), len),
+            YaslType::Array(ty, None) => format!("[{}]", ty.yasl_name()),
+            YaslType::Struct(name) | YaslType::Enum(name) => name.clone(),
+            YaslType::Void => "()".into(),
+        }
+    }
+
+    /// Matching host side type, bool is 4 bytes wide just like in GLSL blocks
+    pub fn rust_type(&self) -> String {
+        use YaslScalarType::*;
+        fn scalar(s: &YaslScalarType) -> &'static str {
+            match s {
+                Int => "i32",
+                UInt | Bool => "u32",
+                Float32 => "f32",
+                Float64 => "f64",
+            }
+        }
+        match self {
+            YaslType::ScalarType(s) => scalar(s).into(),
+            YaslType::Vec(YaslVecType::Vec2(s)) => format!("[{}; 2]", scalar(s)),
+            YaslType::Vec(YaslVecType::Vec3(s)) => format!("[{}; 3]", scalar(s)),
+            YaslType::Vec(YaslVecType::Vec4(s)) => format!("[{}; 4]", scalar(s)),
+            // std430 array of vec3 has a stride of vec4
+            YaslType::Array(ty, Some(len)) => match &**ty {
+                YaslType::Vec(YaslVecType::Vec3(s)) => format!("[[{}; 4]; {}]", scalar(s), len),
+                ty => format!("[{}; {}]", ty.rust_type(), len),
+            },
+            YaslType::Array(ty, None) => format!("[{}]", ty.rust_type()),
+            YaslType::Struct(name) | YaslType::Enum(name) => name.clone(),
+            YaslType::Void => "()".into(),
+        }
+    }
+}
+
 impl From<&YaslType> for Glsl {
     fn from(ty: &YaslType) -> Glsl {
         use YaslType::*;
         Glsl::Expr(match ty {
             ScalarType(s) => Glsl::from(s).into(),
             Vec(st) => Glsl::from(st).into(),
+            Array(ty, Some(len)) => format!("{}[{}]", Glsl::from(&**ty), len),
+            Array(ty, None) => format!("{}[]", Glsl::from(&**ty)),
+            Struct(name) => naming::glsl_name(name, 0),
+            Enum(_) => "int".into(),
             Void => "void".into(),
         })
     }
 }
 
+fn segments_of(path: &syn::Path) -> Vec<String> {
+    path.segments.iter().map(|s| s.ident.to_string()).collect()
+}
+
 impl TryFrom<syn::Type> for YaslType {
     type Error = Error;
     fn try_from(ty: syn::Type) -> Result<Self> {
@@ -48,6 +167,8 @@ impl TryFrom<syn::Type> for YaslType {
                         "f32" => ScalarType(Float32),
                         "f64" => ScalarType(Float64),
                         "bool" => ScalarType(Bool),
+                        // Structs follow Rust naming, existence is checked during validation
+                        s if s.starts_with(char::is_uppercase) => Struct(s.into()),
                         _ => return Err(Error::new(i.span(), "Unknown Type")),
                     }
                 } else {
@@ -72,11 +193,34 @@ impl TryFrom<syn::Type> for YaslType {
                         } else {
                             return Err(Error::new(ident.span(), "Unknown Type"));
                         }
+                    } else if let [first, second] = &segments_of(&p.path)[..] {
+                        // Enum types are marked while folding constants, see `Consts::fold_item`
+                        if first == "enum" {
+                            Enum(second.clone())
+                        } else {
+                            return Err(Error::new(p.span(), "Unknown Type"));
+                        }
                     } else {
                         return Err(Error::new(p.span(), "Unknown Type"));
                     }
                 }
             }
+            syn::Type::Array(a) => {
+                let len = if let syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(i),
+                    ..
+                }) = &a.len
+                {
+                    i.base10_parse()?
+                } else {
+                    return Err(Error::new(
+                        a.len.span(),
+                        "Expected array length literal or constant",
+                    ));
+                };
+                Array(Box::new((*a.elem).try_into()?), Some(len))
+            }
+            syn::Type::Slice(s) => Array(Box::new((*s.elem).try_into()?), None),
             _ => Void,
         })
     }
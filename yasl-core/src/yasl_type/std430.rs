@@ -0,0 +1,94 @@
+//! Memory layout of buffer and push constant blocks
+use std::collections::HashMap;
+
+use super::{YaslScalarType, YaslType, YaslVecType};
+
+/// Fields of every struct, by struct name
+pub type StructFields = HashMap<String, Vec<(String, YaslType)>>;
+
+#[derive(Debug, Clone)]
+pub struct Std430Member {
+    pub name: String,
+    pub ty: YaslType,
+    pub offset: usize,
+    pub size: usize,
+}
+
+fn round_up(v: usize, align: usize) -> usize {
+    v.div_ceil(align) * align
+}
+
+fn scalar_size(s: &YaslScalarType) -> usize {
+    match s {
+        YaslScalarType::Float64 => 8,
+        _ => 4,
+    }
+}
+
+/// Returns None for unknown structs and runtime sized arrays
+pub fn align_of(ty: &YaslType, structs: &StructFields) -> Option<usize> {
+    Some(match ty {
+        YaslType::ScalarType(s) => scalar_size(s),
+        YaslType::Enum(_) => 4,
+        YaslType::Vec(YaslVecType::Vec2(s)) => 2 * scalar_size(s),
+        YaslType::Vec(YaslVecType::Vec3(s)) | YaslType::Vec(YaslVecType::Vec4(s)) => {
+            4 * scalar_size(s)
+        }
+        YaslType::Array(ty, _) => align_of(ty, structs)?,
+        YaslType::Struct(name) => {
+            let mut align = 1;
+            for (_, ty) in structs.get(name)?.iter() {
+                align = align.max(align_of(ty, structs)?);
+            }
+            align
+        }
+        YaslType::Void => return None,
+    })
+}
+
+/// Returns None for unknown structs and runtime sized arrays
+pub fn size_of(ty: &YaslType, structs: &StructFields) -> Option<usize> {
+    Some(match ty {
+        YaslType::ScalarType(s) => scalar_size(s),
+        YaslType::Enum(_) => 4,
+        YaslType::Vec(YaslVecType::Vec2(s)) => 2 * scalar_size(s),
+        YaslType::Vec(YaslVecType::Vec3(s)) => 3 * scalar_size(s),
+        YaslType::Vec(YaslVecType::Vec4(s)) => 4 * scalar_size(s),
+        YaslType::Array(ty, Some(len)) => stride_of(ty, structs)? * len,
+        YaslType::Array(_, None) => return None,
+        YaslType::Struct(name) => struct_layout(structs.get(name)?, structs)?.1,
+        YaslType::Void => return None,
+    })
+}
+
+/// Distance between array elements
+pub fn stride_of(ty: &YaslType, structs: &StructFields) -> Option<usize> {
+    Some(round_up(size_of(ty, structs)?, align_of(ty, structs)?))
+}
+
+/// Offsets of struct members, and size of the whole struct
+pub fn struct_layout(
+    fields: &[(String, YaslType)],
+    structs: &StructFields,
+) -> Option<(Vec<Std430Member>, usize)> {
+    let mut members = Vec::new();
+    let mut offset = 0;
+    let mut struct_align = 1;
+
+    for (name, ty) in fields.iter() {
+        let align = align_of(ty, structs)?;
+        let size = size_of(ty, structs)?;
+        struct_align = struct_align.max(align);
+
+        offset = round_up(offset, align);
+        members.push(Std430Member {
+            name: name.clone(),
+            ty: ty.clone(),
+            offset,
+            size,
+        });
+        offset += size;
+    }
+
+    Some((members, round_up(offset, struct_align)))
+}
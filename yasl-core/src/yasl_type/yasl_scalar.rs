@@ -3,7 +3,8 @@ use std::convert::{TryFrom, TryInto};
 use syn::spanned::Spanned;
 use syn::{Error, Result};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum YaslScalarType {
     Int,
     UInt,
@@ -11,6 +12,16 @@ pub enum YaslScalarType {
     Float64,
     Bool,
 }
+impl YaslScalarType {
+    /// Types that can't be interpolated between vertices
+    pub fn requires_flat(&self) -> bool {
+        match self {
+            YaslScalarType::Int | YaslScalarType::UInt | YaslScalarType::Float64 => true,
+            YaslScalarType::Float32 | YaslScalarType::Bool => false,
+        }
+    }
+}
+
 impl TryFrom<syn::Type> for YaslScalarType {
     type Error = Error;
     fn try_from(ty: syn::Type) -> Result<Self> {
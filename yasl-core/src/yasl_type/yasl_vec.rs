@@ -1,14 +1,21 @@
 use super::YaslScalarType;
 use crate::glsl::Glsl;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum YaslVecType {
     Vec2(YaslScalarType),
     Vec3(YaslScalarType),
     Vec4(YaslScalarType),
 }
 
-impl YaslVecType {}
+impl YaslVecType {
+    pub fn component(&self) -> &YaslScalarType {
+        match self {
+            YaslVecType::Vec2(s) | YaslVecType::Vec3(s) | YaslVecType::Vec4(s) => s,
+        }
+    }
+}
 
 impl From<&YaslVecType> for Glsl {
     fn from(ty: &YaslVecType) -> Glsl {
@@ -1,5 +1,22 @@
-use yasl_macro::yasl_vert;
+use yasl_core::cpu::*;
+use yasl_macro::{shared, yasl_program, yasl_vert};
 
+#[shared]
+fn luminance(c: vec3<f32>) -> f32 {
+    c.dot(vec3(0.2126, 0.7152, 0.0722))
+}
+
+#[shared]
+fn tonemap(c: vec3<f32>) -> vec3<f32> {
+    c / (c + 1.0)
+}
+
+// Every `#[shared]` function generates a macro with its own name, which pastes the function
+// into the shader macro it wraps. Shared functions are only visible to the shaders wrapped in
+// their macro, so using both means nesting: `luminance!` hands its function to `tonemap!`,
+// and `tonemap!` passes both on to `note_vert!`. The order of the wrappers doesn't matter.
+luminance! {
+tonemap! {
 #[yasl_vert]
 note_vert! {
     // layout<input,0> i_color: vec3<f32>;
@@ -70,9 +87,67 @@ note_vert! {
         // let i2 : vec2<u32> = u32::vec2(0,0);
 
         // glsl::gl_Position = vec4(0.0,0.0,0.0,1.0);
+
+        let l = luminance(tonemap(vec3(1.0, 0.5, 0.25)));
+    }
+}
+}
+}
+
+#[yasl_program]
+note_program! {
+    enum Blend {
+        Alpha,
+        Add,
+    }
+
+    struct DrawParams {
+        tint: vec4<f32>,
+        scale: f32,
+        blend: Blend,
+    }
+
+    fn brightness() -> f32 {
+        return 1.0;
+    }
+
+    vertex {
+        layout<push_constant> pc: DrawParams;
+        layout<input,0> i_color: vec3<f32>;
+        layout<output,0> v_color: vec3<f32>;
+
+        fn main() {
+            let b = brightness();
+        }
+    }
+
+    fragment {
+        layout<input,0> v_color: vec3<f32>;
+        layout<output,0> o_color: vec4<f32>;
+
+        fn main() {
+            let b = brightness();
+        }
     }
 }
 
 fn main() {
     println!("Hello, world!");
+    println!(
+        "luminance of tonemapped white: {}",
+        luminance(tonemap(vec3(1.0, 1.0, 1.0)))
+    );
+    println!(
+        "vertex: {} bytes, fragment: {} bytes",
+        note_program::VERTEX.len(),
+        note_program::FRAGMENT.len()
+    );
+
+    let params =
+        note_program::DrawParams::new([1.0, 1.0, 1.0, 1.0], 1.0, note_program::Blend::Alpha);
+    println!(
+        "push constants: {} bytes, {:?}",
+        std::mem::size_of_val(&params),
+        params
+    );
 }
@@ -0,0 +1,353 @@
+//! Compiles a document and answers questions about it, positions are 0-based like in LSP
+use std::path::Path;
+
+use proc_macro2::Span;
+use yasl_core::builtins;
+use yasl_core::ir::{Stmt, YaslType, YaslVecType};
+use yasl_core::outline::DeclarationKind;
+use yasl_core::{CompileOptions, Declaration, LineColumn, ShaderStage};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    fn contains(&self, p: Position) -> bool {
+        self.start <= p && p <= self.end
+    }
+    /// Orders ranges by how much they cover, for picking the innermost one
+    fn size(&self) -> (usize, usize) {
+        (
+            self.end.line - self.start.line,
+            self.end.character.wrapping_sub(self.start.character),
+        )
+    }
+}
+
+impl From<(LineColumn, LineColumn)> for Range {
+    fn from((start, end): (LineColumn, LineColumn)) -> Range {
+        let position = |lc: LineColumn| Position {
+            line: lc.line.saturating_sub(1),
+            character: lc.column,
+        };
+        Range {
+            start: position(start),
+            end: position(end),
+        }
+    }
+}
+
+impl From<Span> for Range {
+    fn from(span: Span) -> Range {
+        Range::from((span.start(), span.end()))
+    }
+}
+
+pub struct Diagnostic {
+    pub range: Range,
+    pub message: String,
+}
+
+/// `let` binding inside of a function
+pub struct Local {
+    pub name: String,
+    pub range: Range,
+    pub ty: YaslType,
+    /// Body of the function it's declared in
+    pub scope: Range,
+}
+
+#[derive(Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub declarations: Vec<Declaration>,
+    pub locals: Vec<Local>,
+    /// Expressions with their types
+    pub types: Vec<(Range, YaslType)>,
+}
+
+/// Bindings the compiler introduces while lowering, like the target of a `match` initializer
+fn is_written(name: &str) -> bool {
+    name != "let" && name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+}
+
+pub fn analyze(uri: &str, text: &str) -> Analysis {
+    let options = CompileOptions {
+        stage: Some(ShaderStage::from_path(Path::new(uri))),
+        ..CompileOptions::default()
+    };
+
+    let mut analysis = Analysis::default();
+    let shaders = match yasl_core::parse(text, &options) {
+        Ok(shaders) => shaders,
+        Err(e) => {
+            analysis.diagnostics = e
+                .errors
+                .into_iter()
+                .map(|e| Diagnostic {
+                    range: e.location.map_or(
+                        Range {
+                            start: Position::default(),
+                            end: Position::default(),
+                        },
+                        Range::from,
+                    ),
+                    message: e.message,
+                })
+                .collect();
+            return analysis;
+        }
+    };
+
+    for shader in shaders.iter() {
+        // Shared items are declared in every stage of a program
+        for d in shader.declarations.iter() {
+            let range = Range::from(d.span);
+            if !analysis
+                .declarations
+                .iter()
+                .any(|o| o.name == d.name && Range::from(o.span) == range)
+            {
+                analysis.declarations.push(d.clone());
+            }
+        }
+        let values = shader.ir.globals().map(|g| &g.value);
+        for value in values.chain(shader.ir.constants().map(|c| &c.value)) {
+            value.walk(&mut |e| analysis.types.push((e.span.into(), e.ty.clone())));
+        }
+        for f in shader.ir.functions() {
+            let scope = Range::from(f.body.span);
+            for s in f.body.stmts.iter() {
+                s.walk(&mut |s| {
+                    if let Stmt::Local {
+                        symbol, ty, span, ..
+                    } = s
+                    {
+                        if is_written(&symbol.name) {
+                            analysis.locals.push(Local {
+                                name: symbol.name.clone(),
+                                range: (*span).into(),
+                                ty: ty.clone(),
+                                scope,
+                            });
+                        }
+                    }
+                });
+            }
+            f.body
+                .walk_exprs(&mut |e| analysis.types.push((e.span.into(), e.ty.clone())));
+        }
+    }
+    analysis
+}
+
+/// Identifier around a position, with the character in front of it
+pub fn word_at(text: &str, p: Position) -> Option<(String, Option<char>)> {
+    let line: Vec<char> = text.lines().nth(p.line)?.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let mut start = p.character.min(line.len());
+    while start > 0 && is_word(&line[start - 1]) {
+        start -= 1;
+    }
+    let mut end = p.character.min(line.len());
+    while end < line.len() && is_word(&line[end]) {
+        end += 1;
+    }
+    let before = start.checked_sub(1).map(|i| line[i]);
+    Some((line[start..end].iter().collect(), before))
+}
+
+impl Analysis {
+    /// Local visible at `p` with the given name, the last one declared before it
+    fn local(&self, name: &str, p: Position) -> Option<&Local> {
+        self.locals
+            .iter()
+            .rfind(|l| l.name == name && l.scope.contains(p) && l.range.start <= p)
+    }
+
+    fn declaration(&self, name: &str) -> Option<&Declaration> {
+        self.declarations.iter().find(|d| d.name == name)
+    }
+
+    /// Type of a local or an expression, or the signature of a declaration
+    pub fn hover(&self, text: &str, p: Position) -> Option<(String, Range)> {
+        if let Some(l) = self.locals.iter().find(|l| l.range.contains(p)) {
+            return Some((format!("let {}: {}", l.name, l.ty.yasl_name()), l.range));
+        }
+        if let Some(d) = self
+            .declarations
+            .iter()
+            .find(|d| Range::from(d.span).contains(p))
+        {
+            return Some((d.detail.clone(), d.span.into()));
+        }
+        if let Some((range, ty)) = self
+            .types
+            .iter()
+            .filter(|(r, _)| r.contains(p))
+            .min_by_key(|(r, _)| r.size())
+        {
+            return Some((ty.yasl_name(), *range));
+        }
+        let (word, _) = word_at(text, p)?;
+        let d = self.declaration(&word)?;
+        Some((d.detail.clone(), d.span.into()))
+    }
+
+    /// Declaration of the name at `p`, locals are looked up in the function around it
+    pub fn definition(&self, text: &str, p: Position) -> Option<Range> {
+        let (word, before) = word_at(text, p)?;
+        if before == Some('.') {
+            return None;
+        }
+        if let Some(l) = self.local(&word, p) {
+            return Some(l.range);
+        }
+        self.declaration(&word).map(|d| d.span.into())
+    }
+
+    /// Builtins, declarations and locals, or swizzles and methods after a `.`
+    pub fn completions(&self, text: &str, p: Position) -> Vec<Completion> {
+        let before_word = word_at(text, p).and_then(|(_, before)| before);
+        if before_word == Some('.') {
+            let receiver = Position {
+                line: p.line,
+                character: text
+                    .lines()
+                    .nth(p.line)
+                    .and_then(|l| {
+                        l[..l
+                            .char_indices()
+                            .nth(p.character)
+                            .map_or(l.len(), |(i, _)| i)]
+                            .rfind('.')
+                    })
+                    .unwrap_or(0),
+            };
+            let ty = word_at(text, receiver).and_then(|(name, _)| {
+                self.local(&name, p)
+                    .map(|l| l.ty.clone())
+                    .or_else(|| self.declaration(&name).and_then(|d| d.ty.clone()))
+                    .or_else(|| {
+                        let var = builtins::VARS.iter().find(|v| v.name == name);
+                        var.map(|v| v.ty.clone())
+                    })
+            });
+            return member_completions(ty);
+        }
+
+        let mut out: Vec<Completion> = builtins::FNS
+            .iter()
+            .map(|f| Completion {
+                label: f.name.to_string(),
+                kind: CompletionKind::Function,
+                detail: format!("builtin, {} in GLSL", f.glsl),
+            })
+            .collect();
+        out.extend(builtins::VARS.iter().map(|v| Completion {
+            label: v.name.to_string(),
+            kind: CompletionKind::Variable,
+            detail: v.ty.yasl_name(),
+        }));
+        out.extend(self.declarations.iter().map(|d| Completion {
+            label: d.name.clone(),
+            kind: match d.kind {
+                DeclarationKind::Function => CompletionKind::Function,
+                DeclarationKind::Const => CompletionKind::Constant,
+                DeclarationKind::Struct => CompletionKind::Struct,
+                DeclarationKind::Enum => CompletionKind::Enum,
+                DeclarationKind::EnumVariant => CompletionKind::EnumMember,
+                _ => CompletionKind::Variable,
+            },
+            detail: d.detail.clone(),
+        }));
+        for l in self.locals.iter().filter(|l| l.scope.contains(p)) {
+            if !out.iter().any(|c| c.label == l.name) {
+                out.push(Completion {
+                    label: l.name.clone(),
+                    kind: CompletionKind::Variable,
+                    detail: l.ty.yasl_name(),
+                });
+            }
+        }
+        out
+    }
+}
+
+/// LSP `CompletionItemKind` values
+#[derive(Debug, Clone, Copy)]
+pub enum CompletionKind {
+    Method = 2,
+    Function = 3,
+    Field = 5,
+    Variable = 6,
+    Enum = 13,
+    Constant = 21,
+    Struct = 22,
+    EnumMember = 20,
+}
+
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+/// Swizzles of a vector, every single component and pair, and the ones in order like `xyz`
+fn member_completions(ty: Option<YaslType>) -> Vec<Completion> {
+    let n = match &ty {
+        Some(YaslType::Vec(YaslVecType::Vec2(_))) => 2,
+        Some(YaslType::Vec(YaslVecType::Vec3(_))) => 3,
+        Some(YaslType::Vec(YaslVecType::Vec4(_))) | None => 4,
+        Some(_) => 0,
+    };
+    let mut out = Vec::new();
+    for set in ["xyzw", "rgba"].iter() {
+        let components: Vec<char> = set.chars().take(n).collect();
+        let mut swizzles: Vec<String> = components.iter().map(|c| c.to_string()).collect();
+        for a in components.iter() {
+            for b in components.iter() {
+                swizzles.push(format!("{}{}", a, b));
+            }
+        }
+        for len in 3..=n {
+            swizzles.push(components[..len].iter().collect());
+        }
+        out.extend(swizzles.into_iter().map(|s| Completion {
+            detail: "swizzle".into(),
+            label: s,
+            kind: CompletionKind::Field,
+        }));
+    }
+    let on_arrays =
+        |f: &builtins::BuiltinFn| matches!(f.params.first(), Some(builtins::BuiltinParam::Array));
+    let methods: Vec<&str> = match ty {
+        Some(YaslType::Vec(_) | YaslType::ScalarType(_)) | None => builtins::FNS
+            .iter()
+            .filter(|f| !f.atomic && !matches!(f.ret, builtins::BuiltinReturn::Void))
+            .filter(|f| !on_arrays(f))
+            .map(|f| f.name)
+            .chain(builtins::METHOD_ALIASES.iter().map(|(alias, _)| *alias))
+            .collect(),
+        Some(YaslType::Array(..)) => builtins::FNS
+            .iter()
+            .filter(|f| on_arrays(f))
+            .map(|f| f.name)
+            .collect(),
+        Some(_) => Vec::new(),
+    };
+    out.extend(methods.into_iter().map(|m| Completion {
+        label: m.to_string(),
+        kind: CompletionKind::Method,
+        detail: "builtin".into(),
+    }));
+    out
+}
@@ -0,0 +1,318 @@
+//! Language server for `.yasl` files, speaks LSP over stdin and stdout
+//!
+//! Documents are compiled when they are opened and saved, diagnostics are published then,
+//! hover, go to definition, completion and document symbols use the last version that compiled.
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+mod analysis;
+mod json;
+
+use analysis::{Analysis, Position, Range};
+use json::{object, Json};
+use yasl_core::outline::DeclarationKind;
+
+/// Reads one message, None at the end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let body =
+        String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Json::parse(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn position(p: &Json) -> Position {
+    Position {
+        line: p.get("line").as_usize().unwrap_or(0),
+        character: p.get("character").as_usize().unwrap_or(0),
+    }
+}
+
+fn range(r: Range) -> Json {
+    let position = |p: Position| {
+        object(vec![
+            ("line", p.line.into()),
+            ("character", p.character.into()),
+        ])
+    };
+    object(vec![("start", position(r.start)), ("end", position(r.end))])
+}
+
+/// LSP `SymbolKind` of a declaration
+fn symbol_kind(kind: DeclarationKind) -> usize {
+    match kind {
+        DeclarationKind::Function => 12,
+        DeclarationKind::Static | DeclarationKind::Layout | DeclarationKind::Shared => 13,
+        DeclarationKind::Const => 14,
+        DeclarationKind::Struct => 23,
+        DeclarationKind::Enum => 10,
+        DeclarationKind::EnumVariant => 22,
+    }
+}
+
+struct Document {
+    text: String,
+    /// Last analysis without errors
+    analysis: Analysis,
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    /// Messages waiting to be written
+    outgoing: Vec<Json>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn capabilities() -> Json {
+        object(vec![
+            (
+                "textDocumentSync",
+                object(vec![
+                    ("openClose", true.into()),
+                    // Full text on every change
+                    ("change", 1usize.into()),
+                    ("save", object(vec![("includeText", false.into())])),
+                ]),
+            ),
+            ("hoverProvider", true.into()),
+            ("definitionProvider", true.into()),
+            (
+                "completionProvider",
+                object(vec![("triggerCharacters", vec![".".into()].into())]),
+            ),
+            ("documentSymbolProvider", true.into()),
+        ])
+    }
+
+    /// Compiles a document and publishes its diagnostics
+    fn compile(&mut self, uri: &str) {
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return,
+        };
+        let analysis = analysis::analyze(uri, &document.text);
+        let diagnostics: Vec<Json> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                object(vec![
+                    ("range", range(d.range)),
+                    // Error
+                    ("severity", 1usize.into()),
+                    ("source", "yasl".into()),
+                    ("message", d.message.as_str().into()),
+                ])
+            })
+            .collect();
+        if analysis.diagnostics.is_empty() {
+            document.analysis = analysis;
+        }
+        self.publish(uri, diagnostics);
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) {
+        self.outgoing.push(object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", diagnostics.into()),
+                ]),
+            ),
+        ]));
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) {
+        let uri = params
+            .get("textDocument")
+            .get("uri")
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").get("text").as_str();
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        text: text.unwrap_or_default().to_string(),
+                        analysis: Analysis::default(),
+                    },
+                );
+                self.compile(&uri);
+            }
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").as_array();
+                let text = changes.last().and_then(|c| c.get("text").as_str());
+                if let (Some(document), Some(text)) = (self.documents.get_mut(&uri), text) {
+                    document.text = text.to_string();
+                }
+            }
+            "textDocument/didSave" => self.compile(&uri),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish(&uri, Vec::new());
+            }
+            _ => {}
+        }
+    }
+
+    /// Result of a request, or a JSON-RPC error code and message
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        if method == "initialize" {
+            return Ok(object(vec![
+                ("capabilities", Self::capabilities()),
+                (
+                    "serverInfo",
+                    object(vec![
+                        ("name", "yasl-lsp".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ]));
+        }
+        if method == "shutdown" {
+            self.shutdown = true;
+            return Ok(Json::Null);
+        }
+
+        let uri = params.get("textDocument").get("uri").as_str();
+        let document = match uri.and_then(|uri| self.documents.get(uri)) {
+            Some(document) => document,
+            None if method.starts_with("textDocument/") => return Ok(Json::Null),
+            None => return Err((-32601, format!("Unknown method {}", method))),
+        };
+        let (text, analysis) = (&document.text, &document.analysis);
+        let p = position(params.get("position"));
+        Ok(match method {
+            "textDocument/hover" => match analysis.hover(text, p) {
+                Some((contents, r)) => object(vec![
+                    (
+                        "contents",
+                        object(vec![
+                            ("kind", "markdown".into()),
+                            ("value", format!("```rust\n{}\n```", contents).into()),
+                        ]),
+                    ),
+                    ("range", range(r)),
+                ]),
+                None => Json::Null,
+            },
+            "textDocument/definition" => match analysis.definition(text, p) {
+                Some(r) => object(vec![
+                    ("uri", uri.unwrap_or_default().into()),
+                    ("range", range(r)),
+                ]),
+                None => Json::Null,
+            },
+            "textDocument/completion" => analysis
+                .completions(text, p)
+                .into_iter()
+                .map(|c| {
+                    object(vec![
+                        ("label", c.label.into()),
+                        ("kind", (c.kind as usize).into()),
+                        ("detail", c.detail.into()),
+                    ])
+                })
+                .collect::<Vec<Json>>()
+                .into(),
+            "textDocument/documentSymbol" => analysis
+                .declarations
+                .iter()
+                .map(|d| {
+                    object(vec![
+                        ("name", d.name.as_str().into()),
+                        ("detail", d.detail.as_str().into()),
+                        ("kind", symbol_kind(d.kind).into()),
+                        ("range", range(d.span.into())),
+                        ("selectionRange", range(d.span.into())),
+                    ])
+                })
+                .collect::<Vec<Json>>()
+                .into(),
+            _ => return Err((-32601, format!("Unknown method {}", method))),
+        })
+    }
+
+    /// Handles a message, returns false once the client asked to exit
+    fn handle(&mut self, message: &Json) -> bool {
+        let method = message.get("method").as_str().unwrap_or_default();
+        let params = message.get("params");
+        let id = message.get("id");
+        if method == "exit" {
+            return false;
+        }
+        if id.is_null() {
+            self.notification(method, params);
+            return true;
+        }
+        // Responses to requests sent by the server have no method
+        if method.is_empty() {
+            return true;
+        }
+        let response = match self.request(method, params) {
+            Ok(result) => object(vec![
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("result", result),
+            ]),
+            Err((code, message)) => object(vec![
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                (
+                    "error",
+                    object(vec![("code", code.into()), ("message", message.into())]),
+                ),
+            ]),
+        };
+        self.outgoing.push(response);
+        true
+    }
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let (mut input, mut output) = (stdin.lock(), stdout.lock());
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        let running = server.handle(&message);
+        for m in server.outgoing.drain(..) {
+            write_message(&mut output, &m)?;
+        }
+        if !running {
+            break;
+        }
+    }
+    std::process::exit(if server.shutdown { 0 } else { 1 })
+}
@@ -1,89 +1,55 @@
-use syn::parse::{Parse, ParseStream, Result};
+use syn::parse::{Parse, ParseStream, Parser, Result};
 use syn::parse_macro_input;
 use syn::Error;
 
-use yasl_core::Shader;
+use yasl_core::reflection::{EnumReflection, StructReflection};
+use yasl_core::{CompileOptions, GlslFormat, GlslTarget, OptLevel, Program, Shader, ShaderStage};
 
 struct Compiler {
     sprv: Vec<u8>,
+    structs: Vec<StructReflection>,
+    enums: Vec<EnumReflection>,
 }
 impl Compiler {
-    fn compile(shader: Shader) -> Result<Self> {
+    fn compile(shader: Shader, options: &CompileOptions) -> Result<Self> {
+        let structs = shader.reflection.structs.clone();
+        let enums = shader.reflection.enums.clone();
+
         #[cfg(feature = "use-shaderc")]
-        let sprv = {
-            let mut compiler = shaderc::Compiler::new().unwrap();
-
-            compiler.compile_into_spirv(
-                &shader.glsl,
-                shaderc::ShaderKind::Vertex,
-                "shader.glsl",
-                "main",
-                None,
-            )
-        };
+        let sprv = shader
+            .spirv(options)?
+            .iter()
+            .flat_map(|w| w.to_ne_bytes().to_vec())
+            .collect();
 
         #[cfg(feature = "use-glsl-to-spirv")]
-        let mut sprv = glsl_to_spirv::compile(&shader.glsl, glsl_to_spirv::ShaderType::Vertex);
-
-        match sprv {
-            Err(e) => {
-                #[cfg(feature = "use-shaderc")]
-                {
-                    if let shaderc::Error::CompilationError(_n, e) = e {
-                        let lines: Vec<&str> = e.split("\n").filter(|l| l.len() > 0).collect();
-                        for l in lines {
-                            let split: Vec<&str> = l.split(":").collect();
-                            if let Some(n) = split.get(1) {
-                                if let Ok(n) = n.parse::<usize>() {
-                                    if let Some(s) = shader.sourcemap[n - 2].span {
-                                        return Err(Error::new(s.into(), l));
-                                    }
-                                }
-                            }
-                        }
-
-                        panic!("{:?}", e);
-                    } else {
-                        panic!("{:?}", e);
-                    }
-                }
+        let sprv = {
+            use std::io::prelude::*;
 
-                #[cfg(feature = "use-glsl-to-spirv")]
-                panic!("{:?}", e);
-                // let split: Vec<&str> = r.split("\n").collect();
-
-                // let mut lines = split.iter();
-
-                // let _file = lines.next();
-
-                // println!("==GLSL VALIDATOR==");
-                // for l in lines.filter(|l| l.len() > 0) {
-                //     if l.starts_with("ERROR:") {
-                //         let split: Vec<&str> = l.split(":").collect();
-                //         if split.len() >= 3 {
-                //             if split[1].starts_with(" /tmp/") {
-                //                 let err = l;
-                //                 // panic!("{:?}", err);
-                //                 // println!("{:?}", split[2]);
-                //             }
-                //         }
-                //     }
-                // }
-            }
-            #[cfg(feature = "use-shaderc")]
-            Ok(sprv) => Ok(Self {
-                sprv: sprv.as_binary_u8().to_vec(),
-            }),
-            #[cfg(feature = "use-glsl-to-spirv")]
-            Ok(mut sprv) => {
-                use std::io::prelude::*;
-
-                let mut buffer = Vec::new();
-                sprv.read_to_end(&mut buffer).unwrap();
-
-                Ok(Self { sprv: buffer })
-            }
-        }
+            // glslang has no optimizer, only the passes over the IR apply
+            let _ = options.opt_level;
+
+            let ty = match shader.stage {
+                ShaderStage::Vertex => glsl_to_spirv::ShaderType::Vertex,
+                ShaderStage::TessControl => glsl_to_spirv::ShaderType::TessellationControl,
+                ShaderStage::TessEval => glsl_to_spirv::ShaderType::TessellationEvaluation,
+                ShaderStage::Geometry => glsl_to_spirv::ShaderType::Geometry,
+                ShaderStage::Fragment => glsl_to_spirv::ShaderType::Fragment,
+                ShaderStage::Compute => glsl_to_spirv::ShaderType::Compute,
+            };
+
+            let mut file =
+                glsl_to_spirv::compile(&shader.glsl, ty).map_err(|e| shader.glsl_errors(&e))?;
+            let mut buffer = Vec::new();
+            file.read_to_end(&mut buffer).unwrap();
+            buffer
+        };
+
+        Ok(Self {
+            sprv,
+            structs,
+            enums,
+        })
     }
 }
 
@@ -92,34 +58,244 @@ struct ShaderMacro {
     // shader: Shader,
     compiler: Compiler,
 }
-impl Parse for ShaderMacro {
-    fn parse(input: ParseStream) -> Result<Self> {
+impl ShaderMacro {
+    fn parse_with(
+        input: ParseStream,
+        stage: ShaderStage,
+        options: &CompileOptions,
+    ) -> Result<Self> {
         let ident: syn::Ident = input.parse()?;
         input.parse::<syn::Token!(!)>()?;
         let body;
 
         syn::braced!(body in input);
 
-        let shader: Shader = body.parse()?;
+        let shader = Shader::parse_with(&body, stage, options)?;
 
-        let compiler = Compiler::compile(shader)?;
+        let compiler = Compiler::compile(shader, options)?;
 
         Ok(Self { ident, compiler })
     }
 }
 
+struct ProgramMacro {
+    ident: syn::Ident,
+    stages: Vec<(ShaderStage, Compiler)>,
+}
+impl ProgramMacro {
+    fn parse_with(input: ParseStream, options: &CompileOptions) -> Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        input.parse::<syn::Token!(!)>()?;
+        let body;
+
+        syn::braced!(body in input);
+
+        let program = Program::parse_with(&body, options)?;
+
+        let mut stages = Vec::new();
+        for shader in program.stages.into_iter() {
+            stages.push((shader.stage, Compiler::compile(shader, options)?));
+        }
+
+        Ok(Self { ident, stages })
+    }
+}
+
+/// Macro attribute arguments, for example `#[yasl_vert(es, O2)]`
+struct MacroArgs {
+    options: CompileOptions,
+}
+impl Parse for MacroArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut options = CompileOptions::default();
+
+        let args = input.parse_terminated::<syn::Ident, syn::Token![,]>(syn::Ident::parse)?;
+        for a in args.iter() {
+            match a.to_string().as_str() {
+                "es" => options.target = GlslTarget::GlslEs310,
+                "O0" => options.opt_level = OptLevel::O0,
+                "O1" => options.opt_level = OptLevel::O1,
+                "O2" => options.opt_level = OptLevel::O2,
+                "minify" => options.format = GlslFormat::Minified,
+                _ => {
+                    return Err(Error::new(
+                        a.span(),
+                        "Unknown argument, expected 'es', 'O0', 'O1', 'O2' or 'minify'",
+                    ))
+                }
+            }
+        }
+
+        Ok(Self { options })
+    }
+}
+
 use proc_macro::TokenStream;
 
-#[proc_macro_attribute]
-pub fn yasl_vert(args: TokenStream, input: TokenStream) -> TokenStream {
-    let ShaderMacro { ident, compiler } = parse_macro_input!(input as ShaderMacro);
+/// `#[repr(C)]` struct with explicit padding, so that it matches std430 layout
+/// Padding is private, so the struct comes with a `new` constructor
+fn rust_struct(s: &StructReflection) -> String {
+    let mut fields = String::new();
+    let mut args = Vec::new();
+    let mut init = String::new();
+    let mut offset = 0;
+    let pad = |fields: &mut String, init: &mut String, i: usize, len: usize| {
+        *fields += &format!("_pad{}: [u8; {}],", i, len);
+        *init += &format!("_pad{}: [0; {}],", i, len);
+    };
+    for (i, m) in s.members.iter().enumerate() {
+        if m.offset > offset {
+            pad(&mut fields, &mut init, i, m.offset - offset);
+        }
+        fields += &format!("pub {}: {},", m.name, m.rust_ty);
+        args.push(format!("{}: {}", m.name, m.rust_ty));
+        init += &format!("{},", m.name);
+        offset = m.offset + m.size;
+    }
+    if s.size > offset {
+        pad(&mut fields, &mut init, s.members.len(), s.size - offset);
+    }
+
+    format!(
+        "#[repr(C)] #[derive(Clone, Copy, Debug)] pub struct {name} {{ {} }} \
+        impl {name} {{ #[allow(clippy::too_many_arguments)] pub fn new({}) -> Self {{ Self {{ {} }} }} }}",
+        fields,
+        args.join(", "),
+        init,
+        name = s.name
+    )
+}
+
+/// `#[repr(i32)]` enum, same size and values as the GLSL `int`
+fn rust_enum(e: &EnumReflection) -> String {
+    let variants: String = e
+        .variants
+        .iter()
+        .map(|(name, value)| format!("{} = {},", name, value))
+        .collect();
+    format!(
+        "#[repr(i32)] #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum {} {{ {} }}",
+        e.name, variants
+    )
+}
+
+fn single_stage(args: TokenStream, input: TokenStream, stage: ShaderStage) -> TokenStream {
+    let MacroArgs { options } = parse_macro_input!(args as MacroArgs);
 
+    let parser = |ps: ParseStream| ShaderMacro::parse_with(ps, stage, &options);
+    let ShaderMacro { ident, compiler } = match parser.parse(input) {
+        Ok(m) => m,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let enums: String = compiler.enums.iter().map(rust_enum).collect();
+    let structs: String = compiler.structs.iter().map(rust_struct).collect();
+
+    // Types go in a module named after the shader, like in `yasl_program`, so shaders can share type names
     format!(
-        "const {}: [u8;{}] = {:?};",
-        ident.to_string(),
+        "mod {ident} {{ {}{} }} const {ident}: [u8;{}] = {:?};",
+        enums,
+        structs,
         compiler.sprv.len(),
-        compiler.sprv
+        compiler.sprv,
+        ident = ident
     )
     .parse()
     .unwrap()
 }
+
+#[proc_macro_attribute]
+pub fn yasl_vert(args: TokenStream, input: TokenStream) -> TokenStream {
+    single_stage(args, input, ShaderStage::Vertex)
+}
+
+#[proc_macro_attribute]
+pub fn yasl_compute(args: TokenStream, input: TokenStream) -> TokenStream {
+    single_stage(args, input, ShaderStage::Compute)
+}
+
+/// Compiles every stage of a program, and exposes them as consts in a module named after the macro
+/// For example `my_program::VERTEX` and `my_program::FRAGMENT`
+#[proc_macro_attribute]
+pub fn yasl_program(args: TokenStream, input: TokenStream) -> TokenStream {
+    let MacroArgs { options } = parse_macro_input!(args as MacroArgs);
+
+    let parser = |ps: ParseStream| ProgramMacro::parse_with(ps, &options);
+    let ProgramMacro { ident, stages } = match parser.parse(input) {
+        Ok(m) => m,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let mut consts = String::new();
+    let mut type_names = Vec::new();
+    for (stage, compiler) in stages.iter() {
+        for e in compiler.enums.iter() {
+            if !type_names.contains(&e.name) {
+                type_names.push(e.name.clone());
+                consts += &rust_enum(e);
+            }
+        }
+        for s in compiler.structs.iter() {
+            if !type_names.contains(&s.name) {
+                type_names.push(s.name.clone());
+                consts += &rust_struct(s);
+            }
+        }
+        consts += &format!(
+            "pub const {}: [u8;{}] = {:?};",
+            stage.name().to_uppercase(),
+            compiler.sprv.len(),
+            compiler.sprv
+        );
+    }
+
+    format!("mod {} {{ {} }}", ident, consts).parse().unwrap()
+}
+
+/// Function compiled as ordinary Rust that can also be used in shaders, see `yasl_core::cpu` for vector types.
+/// Generates a macro with the same name, which prepends the function to the shader macro it wraps:
+/// ```ignore
+/// #[yasl_macro::shared]
+/// fn luminance(c: vec3<f32>) -> f32 {
+///     c.dot(vec3(0.2126, 0.7152, 0.0722))
+/// }
+///
+/// luminance! {
+///     #[yasl_vert]
+///     my_shader! { fn main() { let l = luminance(vec3(1.0, 1.0, 1.0)); } }
+/// }
+/// ```
+/// Macros of multiple shared functions can be nested, `a! { b! { #[yasl_vert] s! { .. } } }`
+#[proc_macro_attribute]
+pub fn shared(args: TokenStream, input: TokenStream) -> TokenStream {
+    let MacroArgs { options } = parse_macro_input!(args as MacroArgs);
+    let item = parse_macro_input!(input as syn::ItemFn);
+
+    // Visibility and attributes like doc comments only matter on the Rust side
+    let mut shader_fn = item.clone();
+    shader_fn.vis = syn::Visibility::Inherited;
+    shader_fn.attrs.clear();
+
+    if let Err(e) = yasl_core::validate_shared(&shader_fn, &options) {
+        return e.to_compile_error().into();
+    }
+
+    let name = &item.sig.ident;
+    quote::quote!(
+        #item
+
+        #[allow(unused_macros)]
+        macro_rules! #name {
+            (@shared { $($shared:tt)* } $(#[$attr:meta])+ $shader:ident ! { $($body:tt)* }) => {
+                $(#[$attr])+ $shader! { $($shared)* #shader_fn $($body)* }
+            };
+            (@shared { $($shared:tt)* } $next:ident ! { $($inner:tt)* }) => {
+                $next! { @shared { $($shared)* #shader_fn } $($inner)* }
+            };
+            ($($input:tt)*) => {
+                #name! { @shared {} $($input)* }
+            };
+        }
+    )
+    .into()
+}
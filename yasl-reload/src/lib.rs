@@ -0,0 +1,95 @@
+//! Recompiles `.yasl` files when they change on disk, so pipelines can be rebuilt without restarting
+//! ```ignore
+//! let mut watcher = Watcher::new(CompileOptions::default());
+//! let shader = watcher.watch("shaders/light_frag.yasl")?;
+//! loop {
+//!     for reload in watcher.poll() {
+//!         match reload.result {
+//!             Ok(shader) => rebuild_pipeline(&reload.path, &shader.stages[0].spirv),
+//!             Err(e) => eprintln!("{}", e),
+//!         }
+//!     }
+//!     draw_frame();
+//! }
+//! ```
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub use yasl_core::{compile_file, CompileOptions, CompiledShader, CompiledStage, Diagnostics};
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// File that changed and what it compiled to
+pub struct Reload {
+    pub path: PathBuf,
+    pub result: Result<CompiledShader, Diagnostics>,
+}
+
+/// Polls modification times of watched files, cheap enough to call every frame
+pub struct Watcher {
+    options: CompileOptions,
+    files: Vec<WatchedFile>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Watcher {
+    pub fn new(options: CompileOptions) -> Self {
+        Self {
+            options,
+            files: Vec::new(),
+        }
+    }
+
+    /// Compiles the file right away, it stays watched even if that fails, so fixing it reloads it
+    pub fn watch(&mut self, path: impl Into<PathBuf>) -> Result<CompiledShader, Diagnostics> {
+        let path = path.into();
+        let modified = modified(&path);
+        self.unwatch(&path);
+        let result = compile_file(&path, &self.options);
+        self.files.push(WatchedFile { path, modified });
+        result
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.files.retain(|f| f.path != path);
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|f| f.path.as_path())
+    }
+
+    /// Recompiles every file modified since it was last compiled.
+    /// Files that are missing, like in the middle of an editor saving them, are tried again on the next poll
+    pub fn poll(&mut self) -> Vec<Reload> {
+        let mut out = Vec::new();
+        for f in self.files.iter_mut() {
+            let m = modified(&f.path);
+            if m.is_none() || m == f.modified {
+                continue;
+            }
+            f.modified = m;
+            out.push(Reload {
+                result: compile_file(&f.path, &self.options),
+                path: f.path.clone(),
+            });
+        }
+        out
+    }
+
+    /// Blocks until a watched file changes, polling every `interval`
+    pub fn wait(&mut self, interval: Duration) -> Vec<Reload> {
+        loop {
+            let reloads = self.poll();
+            if !reloads.is_empty() {
+                return reloads;
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}